@@ -1,5 +1,8 @@
 use arcis_imports::*;
 
+#[cfg(test)]
+mod tests;
+
 #[encrypted]
 mod circuits {
     use arcis_imports::*;
@@ -7,9 +10,57 @@ mod circuits {
     pub const MAX_ORDERS: usize = 4;
     pub const MAX_MATCHES_PER_BATCH: usize = 4;
     pub const POW64: u128 = 18446744073709551616;
-    pub const SCALE_FACTOR: u64 = 100;  // For 2 decimal places (1.34 -> 134)
 
-    pub const POWS_OF_256: [u128; 8] = [
+    pub const ORDER_KIND_LIMIT: u8 = 0;
+    pub const ORDER_KIND_MARKET: u8 = 1;
+    pub const ORDER_KIND_PEG: u8 = 2;
+    // Stop kinds only exist until they trigger, then they rest as market / limit orders
+    pub const ORDER_KIND_STOP: u8 = 3;
+    pub const ORDER_KIND_STOP_LIMIT: u8 = 4;
+    pub const MAX_STOP_ORDERS: usize = 4;
+    // One-cancels-other legs cancel each other once one of them fills or
+    // triggers. A bracket's exit legs wait unarmed until its entry fills and
+    // then behave as one-cancels-other legs.
+    pub const GROUP_ROLE_NONE: u8 = 0;
+    pub const GROUP_ROLE_OCO: u8 = 1;
+    pub const GROUP_ROLE_ENTRY: u8 = 2;
+    pub const GROUP_ROLE_EXIT: u8 = 3;
+    // Recently finished and armed groups, kept with the book for the trigger pass
+    pub const MAX_GROUP_EVENTS: usize = 4;
+    // What submit_order does with a post-only limit order that would cross
+    pub const POST_ONLY_NONE: u8 = 0;
+    pub const POST_ONLY_REJECT: u8 = 1;
+    pub const POST_ONLY_SLIDE: u8 = 2;
+    pub const PRICING_RULE_MIDPOINT: u8 = 0;
+    pub const PRICING_RULE_MAKER: u8 = 1;
+    pub const PRICING_RULE_REFERENCE: u8 = 2;
+    pub const PRICING_RULE_UNIFORM: u8 = 3;
+    pub const STP_NONE: u8 = 0;
+    pub const STP_CANCEL_NEWEST: u8 = 1;
+    pub const STP_CANCEL_OLDEST: u8 = 2;
+    pub const STP_CANCEL_BOTH: u8 = 3;
+    pub const STP_DECREMENT_AND_CANCEL: u8 = 4;
+    pub const ALLOCATION_PRICE_TIME: u8 = 0;
+    pub const ALLOCATION_PRO_RATA: u8 = 1;
+    // Why submit_order_check turned an order away, encrypted for the user
+    pub const REJECT_REASON_NONE: u8 = 0;
+    pub const REJECT_REASON_INSUFFICIENT_BALANCE: u8 = 1;
+    pub const REJECT_REASON_PRICE_BAND: u8 = 2;
+    pub const REJECT_REASON_TICK_SIZE: u8 = 3;
+    pub const REJECT_REASON_LOT_SIZE: u8 = 4;
+    pub const REJECT_REASON_MIN_NOTIONAL: u8 = 5;
+    pub const FEE_TIERS: usize = 3;
+    // Market buys rank ahead of every limit bid, market sells rank at 0
+    pub const MARKET_BUY_RANK_PRICE: u64 = 18446744073709551615;
+    // An order leaves the book at most once per batch, so one slot per book slot is enough
+    pub const MAX_LOCK_RELEASES: usize = 2 * MAX_ORDERS;
+    // Every order in the book can take part in a single auction
+    pub const MAX_AUCTION_FILLS: usize = 2 * MAX_ORDERS;
+    // Every self trade removes at least one order, so on top of the matches a
+    // batch needs at most one round per order to work through them
+    pub const MAX_MATCH_ROUNDS: usize = MAX_MATCHES_PER_BATCH + 2 * MAX_ORDERS;
+
+    pub const POWS_OF_256: [u128; 16] = [
         1,
         256,
         65536,
@@ -18,16 +69,77 @@ mod circuits {
         1099511627776,
         281474976710656,
         72057594037927936,
+        18446744073709551616,
+        4722366482869645213696,
+        1208925819614629174706176,
+        309485009821345068724781056,
+        79228162514264337593543950336,
+        20282409603651670423947251286016,
+        5192296858534827628530496329220096,
+        1329227995784915872903807060280344576,
     ];
 
+    // Fixed point: amounts are raw base units and prices raw quote units per
+    // whole base token, so a quote value is amount * price / price_scale with
+    // price_scale = 10^base decimals (the program passes it in). Products are
+    // taken in u128 and every rounding goes the protocol's way. Mirrors
+    // utils/fixed_point.rs in the program.
+    fn saturate(value: u128) -> u64 {
+        if value >= POW64 {
+            (POW64 - 1) as u64
+        } else {
+            value as u64
+        }
+    }
+
+    // Quote a buyer locks or pays for quantity at price, rounded up
+    pub fn quote_owed(quantity: u64, price: u64, price_scale: u64) -> u64 {
+        let product = quantity as u128 * price as u128;
+        saturate((product + price_scale as u128 - 1) / price_scale as u128)
+    }
+
+    // Quote handed back to a buyer for quantity at price, rounded down
+    pub fn quote_due(quantity: u64, price: u64, price_scale: u64) -> u64 {
+        saturate((quantity as u128 * price as u128) / price_scale as u128)
+    }
+
+    // Base quantity quote pays for at price, rounded down
+    pub fn base_for_quote(quote: u64, price: u64, price_scale: u64) -> u64 {
+        let divisor = if price > 0 { price } else { 1 };
+        saturate((quote as u128 * price_scale as u128) / divisor as u128)
+    }
+
+    // Ledger arithmetic: u64 wraps silently in MPC, so these keep the old
+    // value and flag the error instead. A circuit that sees a flag returns
+    // the balances it was given and reveals the flag.
+    pub fn checked_add(a: u64, b: u64) -> (u64, bool) {
+        let sum = a as u128 + b as u128;
+        let overflow = sum >= POW64;
+        (if overflow { a } else { sum as u64 }, overflow)
+    }
+
+    pub fn checked_sub(a: u64, b: u64) -> (u64, bool) {
+        let underflow = b > a;
+        (if underflow { a } else { a - b }, underflow)
+    }
+
 
     #[derive(Copy, Clone)]
     pub struct Order {
-        pub order_id: u64,  // 8
-        pub amount: u64,    // 8
-        pub price: u64,     // 8
-        pub order_type: u8, // 1
-        pub timestamp: u64, // 8
+        pub order_id: u64,     // 8
+        pub amount: u64,       // 8
+        pub price: u64,        // 8 (worst acceptable price for market and peg orders, 0 = none)
+        pub order_type: u8,    // 1
+        pub timestamp: u64,    // 8
+        pub order_kind: u8,    // 1 (0 = limit, 1 = market, 2 = peg)
+        pub quote_budget: u64, // 8 (remaining locked quote of a market or peg buy)
+        pub min_fill_qty: u64, // 8 (smallest fill the order accepts, 0 = any)
+        pub owner_tag: u64,    // 8 (owner id sealed in the user's ledger, for self trade prevention)
+        pub display_size: u64, // 8 (iceberg clip size, 0 = whole amount is displayed)
+        pub reserve: u64,      // 8 (hidden iceberg quantity behind the displayed amount)
+        pub group_id: u64,     // 8 (order group the order belongs to, 0 = none)
+        pub group_role: u8,    // 1 (GROUP_ROLE_*)
+        pub post_only: bool,   // 1 (never trades against an order that was resting before it)
     }
 
     #[derive(Copy, Clone)]
@@ -48,6 +160,13 @@ mod circuits {
         pub quote_available: u64,
     }
 
+    // Quote traded in the current and the previous volume window
+    #[derive(Copy, Clone)]
+    pub struct TradingVolume {
+        pub current: u64,
+        pub previous: u64,
+    }
+
     impl Order {
         pub fn empty() -> Self {
             Order {
@@ -56,6 +175,15 @@ mod circuits {
                 price: 0,
                 order_type: 0,
                 timestamp: 0,
+                order_kind: 0,
+                quote_budget: 0,
+                min_fill_qty: 0,
+                owner_tag: 0,
+                display_size: 0,
+                reserve: 0,
+                group_id: 0,
+                group_role: 0,
+                post_only: false,
             }
         }
 
@@ -66,6 +194,133 @@ mod circuits {
         pub fn is_sell(&self) -> bool {
             self.order_type == 1
         }
+
+        pub fn is_market(&self) -> bool {
+            self.order_kind == ORDER_KIND_MARKET
+        }
+
+        pub fn is_peg(&self) -> bool {
+            self.order_kind == ORDER_KIND_PEG
+        }
+
+        // Market and peg buys don't know their price up front, they lock a quote budget
+        pub fn uses_quote_budget(&self) -> bool {
+            self.is_market() || self.is_peg()
+        }
+
+        // A peg order can only trade while there is a reference price to follow
+        pub fn has_reference(&self, reference_price: u64) -> bool {
+            !self.is_peg() || reference_price > 0
+        }
+
+        // Price used to rank bids in the heap. A peg bid follows the reference
+        // price but never goes above its cap.
+        pub fn buy_rank_price(&self, reference_price: u64) -> u64 {
+            if self.is_market() {
+                MARKET_BUY_RANK_PRICE
+            } else if self.is_peg() {
+                if reference_price == 0 {
+                    0
+                } else if self.price > 0 && self.price < reference_price {
+                    self.price
+                } else {
+                    reference_price
+                }
+            } else {
+                self.price
+            }
+        }
+
+        // Price used to rank asks in the heap. A peg ask follows the reference
+        // price but never goes below its cap.
+        pub fn sell_rank_price(&self, reference_price: u64) -> u64 {
+            if self.is_market() {
+                0
+            } else if self.is_peg() {
+                if reference_price == 0 {
+                    MARKET_BUY_RANK_PRICE
+                } else if self.price > reference_price {
+                    self.price
+                } else {
+                    reference_price
+                }
+            } else {
+                self.price
+            }
+        }
+
+        // Slippage guard: a market or peg buy without a worst price accepts anything
+        pub fn buy_accepts(&self, execution_price: u64) -> bool {
+            if self.uses_quote_budget() && self.price == 0 {
+                true
+            } else {
+                execution_price <= self.price
+            }
+        }
+
+        pub fn sell_accepts(&self, execution_price: u64) -> bool {
+            execution_price >= self.price
+        }
+
+        // Quantity a bid can take at execution_price, market and peg bids are
+        // also limited by what their remaining quote budget pays for
+        pub fn buy_capacity(&self, execution_price: u64, price_scale: u64) -> u64 {
+            let budget_quantity = if self.uses_quote_budget() && execution_price > 0 {
+                base_for_quote(self.quote_budget, execution_price, price_scale)
+            } else {
+                self.amount
+            };
+            if budget_quantity < self.amount {
+                budget_quantity
+            } else {
+                self.amount
+            }
+        }
+
+        // Once an iceberg's displayed clip is used up the next one comes out of
+        // the reserve and joins the back of its price level
+        pub fn replenish(&mut self, timestamp: u64) {
+            if self.amount == 0 && self.reserve > 0 {
+                let clip = if self.reserve < self.display_size {
+                    self.reserve
+                } else {
+                    self.display_size
+                };
+                self.amount = clip;
+                self.reserve = self.reserve - clip;
+                self.timestamp = timestamp;
+            }
+        }
+
+        // A remainder smaller than the minimum can still be filled in one go,
+        // otherwise partially filled block orders would never leave the book
+        pub fn accepts_fill(&self, quantity: u64) -> bool {
+            quantity >= self.min_fill_qty || quantity == self.amount
+        }
+
+        // Legs that are cancelled once another leg of their group wins
+        pub fn is_sibling(&self) -> bool {
+            self.group_role == GROUP_ROLE_OCO
+        }
+    }
+
+    // Quote or base amount to hand back to a user's available balance when
+    // an order leaves the book without being filled.
+    #[derive(Copy, Clone)]
+    pub struct LockRelease {
+        pub order_id: u64,
+        pub order_type: u8,
+        pub amount: u64,
+    }
+
+    impl LockRelease {
+        pub fn empty() -> Self {
+            LockRelease {
+                order_id: 0,
+                order_type: 0,
+                amount: 0,
+            }
+        }
     }
     // pub
     // in each order there are 4 u64 and a u8
@@ -73,6 +328,16 @@ mod circuits {
     // for each order we will require  2 u128's in this case we will require MAX_ORDERS * 2 * 2 = 4 * 2 = 8 u128's
     // we will take the reamaining u8's of all the orders and store them in as many u128 we will require
     // in this case that will be MAX_ORDERS * 2 *  8 = 4 * 2 * 8 = 64 so only one u128 will be required
+    // order_type sits in bytes 0..8 of order_type_chunk and order_kind in bytes 8..16
+    // order_chunk17..order_chunk24 hold the per order quote_budget in their low 64 bits
+    // and min_fill_qty in their high 64 bits
+    // order_chunk25..order_chunk32 hold the per order owner_tag in their low 64 bits
+    // and display_size in their high 64 bits
+    // order_chunk33..order_chunk40 hold the per order iceberg reserve in their low 64 bits
+    // and group_id in their high 64 bits
+    // order_chunk41 and order_chunk42 hold the done_groups ring, order_chunk43 and
+    // order_chunk44 the armed_groups ring, two ids per chunk
+    // group_role sits in bytes 0..8 of order_flags_chunk and post_only in bytes 8..16, buys first
     #[derive(Copy, Clone)]
     pub struct OrderBookFlat {
         pub order_chunk1: u128,
@@ -91,7 +356,36 @@ mod circuits {
         pub order_chunk14: u128,
         pub order_chunk15: u128,
         pub order_chunk16: u128,
+        pub order_chunk17: u128,
+        pub order_chunk18: u128,
+        pub order_chunk19: u128,
+        pub order_chunk20: u128,
+        pub order_chunk21: u128,
+        pub order_chunk22: u128,
+        pub order_chunk23: u128,
+        pub order_chunk24: u128,
+        pub order_chunk25: u128,
+        pub order_chunk26: u128,
+        pub order_chunk27: u128,
+        pub order_chunk28: u128,
+        pub order_chunk29: u128,
+        pub order_chunk30: u128,
+        pub order_chunk31: u128,
+        pub order_chunk32: u128,
+        pub order_chunk33: u128,
+        pub order_chunk34: u128,
+        pub order_chunk35: u128,
+        pub order_chunk36: u128,
+        pub order_chunk37: u128,
+        pub order_chunk38: u128,
+        pub order_chunk39: u128,
+        pub order_chunk40: u128,
+        pub order_chunk41: u128,
+        pub order_chunk42: u128,
+        pub order_chunk43: u128,
+        pub order_chunk44: u128,
         pub order_type_chunk: u128,
+        pub order_flags_chunk: u128,
         pub order_count: u128,
     }
 
@@ -114,7 +408,36 @@ mod circuits {
                 order_chunk14: 0,
                 order_chunk15: 0,
                 order_chunk16: 0,
+                order_chunk17: 0,
+                order_chunk18: 0,
+                order_chunk19: 0,
+                order_chunk20: 0,
+                order_chunk21: 0,
+                order_chunk22: 0,
+                order_chunk23: 0,
+                order_chunk24: 0,
+                order_chunk25: 0,
+                order_chunk26: 0,
+                order_chunk27: 0,
+                order_chunk28: 0,
+                order_chunk29: 0,
+                order_chunk30: 0,
+                order_chunk31: 0,
+                order_chunk32: 0,
+                order_chunk33: 0,
+                order_chunk34: 0,
+                order_chunk35: 0,
+                order_chunk36: 0,
+                order_chunk37: 0,
+                order_chunk38: 0,
+                order_chunk39: 0,
+                order_chunk40: 0,
+                order_chunk41: 0,
+                order_chunk42: 0,
+                order_chunk43: 0,
+                order_chunk44: 0,
                 order_type_chunk: 0,
+                order_flags_chunk: 0,
                 order_count: 0,
             }
         }
@@ -136,7 +459,36 @@ mod circuits {
             let mut order_chunk14: u128 = 0;
             let mut order_chunk15: u128 = 0;
             let mut order_chunk16: u128 = 0;
+            let mut order_chunk17: u128 = 0;
+            let mut order_chunk18: u128 = 0;
+            let mut order_chunk19: u128 = 0;
+            let mut order_chunk20: u128 = 0;
+            let mut order_chunk21: u128 = 0;
+            let mut order_chunk22: u128 = 0;
+            let mut order_chunk23: u128 = 0;
+            let mut order_chunk24: u128 = 0;
+            let mut order_chunk25: u128 = 0;
+            let mut order_chunk26: u128 = 0;
+            let mut order_chunk27: u128 = 0;
+            let mut order_chunk28: u128 = 0;
+            let mut order_chunk29: u128 = 0;
+            let mut order_chunk30: u128 = 0;
+            let mut order_chunk31: u128 = 0;
+            let mut order_chunk32: u128 = 0;
+            let mut order_chunk33: u128 = 0;
+            let mut order_chunk34: u128 = 0;
+            let mut order_chunk35: u128 = 0;
+            let mut order_chunk36: u128 = 0;
+            let mut order_chunk37: u128 = 0;
+            let mut order_chunk38: u128 = 0;
+            let mut order_chunk39: u128 = 0;
+            let mut order_chunk40: u128 = 0;
+            let mut order_chunk41: u128 = 0;
+            let mut order_chunk42: u128 = 0;
+            let mut order_chunk43: u128 = 0;
+            let mut order_chunk44: u128 = 0;
             let mut order_type_chunk: u128 = 0;
+            let mut order_flags_chunk: u128 = 0;
             let mut order_count: u128 = 0;
 
             order_chunk1 += orderbook.buy_orders[0].order_id as u128;
@@ -179,6 +531,66 @@ mod circuits {
             order_chunk16 += orderbook.sell_orders[3].price as u128;
             order_chunk16 += orderbook.sell_orders[3].timestamp as u128 * POW64;  
 
+            order_chunk17 += orderbook.buy_orders[0].quote_budget as u128;
+            order_chunk17 += orderbook.buy_orders[0].min_fill_qty as u128 * POW64;
+            order_chunk18 += orderbook.buy_orders[1].quote_budget as u128;
+            order_chunk18 += orderbook.buy_orders[1].min_fill_qty as u128 * POW64;
+            order_chunk19 += orderbook.buy_orders[2].quote_budget as u128;
+            order_chunk19 += orderbook.buy_orders[2].min_fill_qty as u128 * POW64;
+            order_chunk20 += orderbook.buy_orders[3].quote_budget as u128;
+            order_chunk20 += orderbook.buy_orders[3].min_fill_qty as u128 * POW64;
+            order_chunk21 += orderbook.sell_orders[0].quote_budget as u128;
+            order_chunk21 += orderbook.sell_orders[0].min_fill_qty as u128 * POW64;
+            order_chunk22 += orderbook.sell_orders[1].quote_budget as u128;
+            order_chunk22 += orderbook.sell_orders[1].min_fill_qty as u128 * POW64;
+            order_chunk23 += orderbook.sell_orders[2].quote_budget as u128;
+            order_chunk23 += orderbook.sell_orders[2].min_fill_qty as u128 * POW64;
+            order_chunk24 += orderbook.sell_orders[3].quote_budget as u128;
+            order_chunk24 += orderbook.sell_orders[3].min_fill_qty as u128 * POW64;
+
+            order_chunk25 += orderbook.buy_orders[0].owner_tag as u128;
+            order_chunk26 += orderbook.buy_orders[1].owner_tag as u128;
+            order_chunk27 += orderbook.buy_orders[2].owner_tag as u128;
+            order_chunk28 += orderbook.buy_orders[3].owner_tag as u128;
+            order_chunk29 += orderbook.sell_orders[0].owner_tag as u128;
+            order_chunk30 += orderbook.sell_orders[1].owner_tag as u128;
+            order_chunk31 += orderbook.sell_orders[2].owner_tag as u128;
+            order_chunk32 += orderbook.sell_orders[3].owner_tag as u128;
+            order_chunk25 += orderbook.buy_orders[0].display_size as u128 * POW64;
+            order_chunk26 += orderbook.buy_orders[1].display_size as u128 * POW64;
+            order_chunk27 += orderbook.buy_orders[2].display_size as u128 * POW64;
+            order_chunk28 += orderbook.buy_orders[3].display_size as u128 * POW64;
+            order_chunk29 += orderbook.sell_orders[0].display_size as u128 * POW64;
+            order_chunk30 += orderbook.sell_orders[1].display_size as u128 * POW64;
+            order_chunk31 += orderbook.sell_orders[2].display_size as u128 * POW64;
+            order_chunk32 += orderbook.sell_orders[3].display_size as u128 * POW64;
+
+            order_chunk33 += orderbook.buy_orders[0].reserve as u128;
+            order_chunk34 += orderbook.buy_orders[1].reserve as u128;
+            order_chunk35 += orderbook.buy_orders[2].reserve as u128;
+            order_chunk36 += orderbook.buy_orders[3].reserve as u128;
+            order_chunk37 += orderbook.sell_orders[0].reserve as u128;
+            order_chunk38 += orderbook.sell_orders[1].reserve as u128;
+            order_chunk39 += orderbook.sell_orders[2].reserve as u128;
+            order_chunk40 += orderbook.sell_orders[3].reserve as u128;
+            order_chunk33 += orderbook.buy_orders[0].group_id as u128 * POW64;
+            order_chunk34 += orderbook.buy_orders[1].group_id as u128 * POW64;
+            order_chunk35 += orderbook.buy_orders[2].group_id as u128 * POW64;
+            order_chunk36 += orderbook.buy_orders[3].group_id as u128 * POW64;
+            order_chunk37 += orderbook.sell_orders[0].group_id as u128 * POW64;
+            order_chunk38 += orderbook.sell_orders[1].group_id as u128 * POW64;
+            order_chunk39 += orderbook.sell_orders[2].group_id as u128 * POW64;
+            order_chunk40 += orderbook.sell_orders[3].group_id as u128 * POW64;
+
+            order_chunk41 += orderbook.done_groups[0] as u128;
+            order_chunk41 += orderbook.done_groups[1] as u128 * POW64;
+            order_chunk42 += orderbook.done_groups[2] as u128;
+            order_chunk42 += orderbook.done_groups[3] as u128 * POW64;
+            order_chunk43 += orderbook.armed_groups[0] as u128;
+            order_chunk43 += orderbook.armed_groups[1] as u128 * POW64;
+            order_chunk44 += orderbook.armed_groups[2] as u128;
+            order_chunk44 += orderbook.armed_groups[3] as u128 * POW64;
+
             order_type_chunk += orderbook.buy_orders[0].order_type as u128 * POWS_OF_256[0];
             order_type_chunk += orderbook.buy_orders[1].order_type as u128 * POWS_OF_256[1];
             order_type_chunk += orderbook.buy_orders[2].order_type as u128 * POWS_OF_256[2]; 
@@ -188,6 +600,15 @@ mod circuits {
             order_type_chunk += orderbook.sell_orders[2].order_type as u128 * POWS_OF_256[6]; 
             order_type_chunk += orderbook.sell_orders[3].order_type as u128 * POWS_OF_256[7]; 
 
+            for i in 0..MAX_ORDERS {
+                order_type_chunk += orderbook.buy_orders[i].order_kind as u128 * POWS_OF_256[2 * MAX_ORDERS + i];
+                order_type_chunk += orderbook.sell_orders[i].order_kind as u128 * POWS_OF_256[3 * MAX_ORDERS + i];
+                order_flags_chunk += orderbook.buy_orders[i].group_role as u128 * POWS_OF_256[i];
+                order_flags_chunk += orderbook.sell_orders[i].group_role as u128 * POWS_OF_256[MAX_ORDERS + i];
+                order_flags_chunk += orderbook.buy_orders[i].post_only as u128 * POWS_OF_256[2 * MAX_ORDERS + i];
+                order_flags_chunk += orderbook.sell_orders[i].post_only as u128 * POWS_OF_256[3 * MAX_ORDERS + i];
+            }
+
             order_count += orderbook.buy_count as u128;
             order_count += orderbook.sell_count as u128 * 256;
 
@@ -208,7 +629,36 @@ mod circuits {
                 order_chunk14,
                 order_chunk15,
                 order_chunk16,
+                order_chunk17,
+                order_chunk18,
+                order_chunk19,
+                order_chunk20,
+                order_chunk21,
+                order_chunk22,
+                order_chunk23,
+                order_chunk24,
+                order_chunk25,
+                order_chunk26,
+                order_chunk27,
+                order_chunk28,
+                order_chunk29,
+                order_chunk30,
+                order_chunk31,
+                order_chunk32,
+                order_chunk33,
+                order_chunk34,
+                order_chunk35,
+                order_chunk36,
+                order_chunk37,
+                order_chunk38,
+                order_chunk39,
+                order_chunk40,
+                order_chunk41,
+                order_chunk42,
+                order_chunk43,
+                order_chunk44,
                 order_type_chunk,
+                order_flags_chunk,
                 order_count,
             }
         }
@@ -256,11 +706,77 @@ mod circuits {
             orderbook.sell_orders[3].price = (self.order_chunk16 % POW64) as u64;
             orderbook.sell_orders[3].timestamp = (self.order_chunk16 / POW64) as u64;
 
+            orderbook.buy_orders[0].quote_budget = self.order_chunk17 as u64;
+            orderbook.buy_orders[0].min_fill_qty = (self.order_chunk17 / POW64) as u64;
+            orderbook.buy_orders[1].quote_budget = self.order_chunk18 as u64;
+            orderbook.buy_orders[1].min_fill_qty = (self.order_chunk18 / POW64) as u64;
+            orderbook.buy_orders[2].quote_budget = self.order_chunk19 as u64;
+            orderbook.buy_orders[2].min_fill_qty = (self.order_chunk19 / POW64) as u64;
+            orderbook.buy_orders[3].quote_budget = self.order_chunk20 as u64;
+            orderbook.buy_orders[3].min_fill_qty = (self.order_chunk20 / POW64) as u64;
+            orderbook.sell_orders[0].quote_budget = self.order_chunk21 as u64;
+            orderbook.sell_orders[0].min_fill_qty = (self.order_chunk21 / POW64) as u64;
+            orderbook.sell_orders[1].quote_budget = self.order_chunk22 as u64;
+            orderbook.sell_orders[1].min_fill_qty = (self.order_chunk22 / POW64) as u64;
+            orderbook.sell_orders[2].quote_budget = self.order_chunk23 as u64;
+            orderbook.sell_orders[2].min_fill_qty = (self.order_chunk23 / POW64) as u64;
+            orderbook.sell_orders[3].quote_budget = self.order_chunk24 as u64;
+            orderbook.sell_orders[3].min_fill_qty = (self.order_chunk24 / POW64) as u64;
+
+            orderbook.buy_orders[0].owner_tag = self.order_chunk25 as u64;
+            orderbook.buy_orders[1].owner_tag = self.order_chunk26 as u64;
+            orderbook.buy_orders[2].owner_tag = self.order_chunk27 as u64;
+            orderbook.buy_orders[3].owner_tag = self.order_chunk28 as u64;
+            orderbook.sell_orders[0].owner_tag = self.order_chunk29 as u64;
+            orderbook.sell_orders[1].owner_tag = self.order_chunk30 as u64;
+            orderbook.sell_orders[2].owner_tag = self.order_chunk31 as u64;
+            orderbook.sell_orders[3].owner_tag = self.order_chunk32 as u64;
+            orderbook.buy_orders[0].display_size = (self.order_chunk25 / POW64) as u64;
+            orderbook.buy_orders[1].display_size = (self.order_chunk26 / POW64) as u64;
+            orderbook.buy_orders[2].display_size = (self.order_chunk27 / POW64) as u64;
+            orderbook.buy_orders[3].display_size = (self.order_chunk28 / POW64) as u64;
+            orderbook.sell_orders[0].display_size = (self.order_chunk29 / POW64) as u64;
+            orderbook.sell_orders[1].display_size = (self.order_chunk30 / POW64) as u64;
+            orderbook.sell_orders[2].display_size = (self.order_chunk31 / POW64) as u64;
+            orderbook.sell_orders[3].display_size = (self.order_chunk32 / POW64) as u64;
+
+            orderbook.buy_orders[0].reserve = self.order_chunk33 as u64;
+            orderbook.buy_orders[1].reserve = self.order_chunk34 as u64;
+            orderbook.buy_orders[2].reserve = self.order_chunk35 as u64;
+            orderbook.buy_orders[3].reserve = self.order_chunk36 as u64;
+            orderbook.sell_orders[0].reserve = self.order_chunk37 as u64;
+            orderbook.sell_orders[1].reserve = self.order_chunk38 as u64;
+            orderbook.sell_orders[2].reserve = self.order_chunk39 as u64;
+            orderbook.sell_orders[3].reserve = self.order_chunk40 as u64;
+            orderbook.buy_orders[0].group_id = (self.order_chunk33 / POW64) as u64;
+            orderbook.buy_orders[1].group_id = (self.order_chunk34 / POW64) as u64;
+            orderbook.buy_orders[2].group_id = (self.order_chunk35 / POW64) as u64;
+            orderbook.buy_orders[3].group_id = (self.order_chunk36 / POW64) as u64;
+            orderbook.sell_orders[0].group_id = (self.order_chunk37 / POW64) as u64;
+            orderbook.sell_orders[1].group_id = (self.order_chunk38 / POW64) as u64;
+            orderbook.sell_orders[2].group_id = (self.order_chunk39 / POW64) as u64;
+            orderbook.sell_orders[3].group_id = (self.order_chunk40 / POW64) as u64;
+
+            orderbook.done_groups[0] = self.order_chunk41 as u64;
+            orderbook.done_groups[1] = (self.order_chunk41 / POW64) as u64;
+            orderbook.done_groups[2] = self.order_chunk42 as u64;
+            orderbook.done_groups[3] = (self.order_chunk42 / POW64) as u64;
+            orderbook.armed_groups[0] = self.order_chunk43 as u64;
+            orderbook.armed_groups[1] = (self.order_chunk43 / POW64) as u64;
+            orderbook.armed_groups[2] = self.order_chunk44 as u64;
+            orderbook.armed_groups[3] = (self.order_chunk44 / POW64) as u64;
+
             for i in 0..MAX_ORDERS {
                 orderbook.buy_orders[i].order_type = (self.order_type_chunk / POWS_OF_256[i]) as u8;
+                orderbook.buy_orders[i].order_kind = (self.order_type_chunk / POWS_OF_256[2 * MAX_ORDERS + i]) as u8;
+                orderbook.buy_orders[i].group_role = (self.order_flags_chunk / POWS_OF_256[i]) as u8;
+                orderbook.buy_orders[i].post_only = (self.order_flags_chunk / POWS_OF_256[2 * MAX_ORDERS + i]) as u8 == 1;
             }
             for i in 0..MAX_ORDERS {
-                orderbook.sell_orders[i].order_type = (self.order_type_chunk / POWS_OF_256[i]) as u8;
+                orderbook.sell_orders[i].order_type = (self.order_type_chunk / POWS_OF_256[MAX_ORDERS + i]) as u8;
+                orderbook.sell_orders[i].order_kind = (self.order_type_chunk / POWS_OF_256[3 * MAX_ORDERS + i]) as u8;
+                orderbook.sell_orders[i].group_role = (self.order_flags_chunk / POWS_OF_256[MAX_ORDERS + i]) as u8;
+                orderbook.sell_orders[i].post_only = (self.order_flags_chunk / POWS_OF_256[3 * MAX_ORDERS + i]) as u8 == 1;
             }
 
             // TODO: add logic to handle buy and sell conunts
@@ -276,6 +792,12 @@ mod circuits {
         pub buy_count: u8,
         pub sell_orders: [Order; MAX_ORDERS],
         pub sell_count: u8,
+        // Groups one of whose legs has won, their remaining siblings are cancelled
+        pub done_groups: [u64; MAX_GROUP_EVENTS],
+        // Brackets whose entry has filled, their exit legs may trigger
+        pub armed_groups: [u64; MAX_GROUP_EVENTS],
+        // Not stored with the book, only known while matching
+        pub reference_price: u64,
     }
 
     impl OrderBook {
@@ -285,7 +807,91 @@ mod circuits {
                 buy_count: 0,
                 sell_orders: [Order::empty(); MAX_ORDERS],
                 sell_count: 0,
+                done_groups: [0u64; MAX_GROUP_EVENTS],
+                armed_groups: [0u64; MAX_GROUP_EVENTS],
+                reference_price: 0,
+            }
+        }
+
+        // Empty book that keeps this book's reference price and group events
+        pub fn emptied(&self) -> Self {
+            let mut book = OrderBook::new();
+            book.done_groups = self.done_groups;
+            book.armed_groups = self.armed_groups;
+            book.reference_price = self.reference_price;
+            book
+        }
+
+        pub fn group_done(&self, group_id: u64) -> bool {
+            let mut done = false;
+            for i in 0..MAX_GROUP_EVENTS {
+                if group_id != 0 && self.done_groups[i] == group_id {
+                    done = true;
+                }
+            }
+            done
+        }
+
+        pub fn group_armed(&self, group_id: u64) -> bool {
+            let mut armed = false;
+            for i in 0..MAX_GROUP_EVENTS {
+                if group_id != 0 && self.armed_groups[i] == group_id {
+                    armed = true;
+                }
+            }
+            armed
+        }
+
+        // Sibling whose group another leg has already won
+        pub fn group_cancelled(&self, order: &Order) -> bool {
+            order.is_sibling() && self.group_done(order.group_id)
+        }
+
+        // The oldest event drops out once the ring is full
+        pub fn mark_group_done(&mut self, group_id: u64) {
+            for i in 0..MAX_GROUP_EVENTS - 1 {
+                self.done_groups[i] = self.done_groups[i + 1];
+            }
+            self.done_groups[MAX_GROUP_EVENTS - 1] = group_id;
+        }
+
+        pub fn mark_group_armed(&mut self, group_id: u64) {
+            for i in 0..MAX_GROUP_EVENTS - 1 {
+                self.armed_groups[i] = self.armed_groups[i + 1];
             }
+            self.armed_groups[MAX_GROUP_EVENTS - 1] = group_id;
+        }
+
+        // A filled leg wins its group: a one-cancels-other leg finishes the
+        // group, a bracket entry arms the exits. Either way the order trades
+        // on as a plain order from here on.
+        pub fn record_group_fill(&mut self, order: Order) -> Order {
+            let mut winner = order;
+            if order.group_role == GROUP_ROLE_OCO {
+                self.mark_group_done(order.group_id);
+            }
+            if order.group_role == GROUP_ROLE_ENTRY {
+                self.mark_group_armed(order.group_id);
+            }
+            winner.group_role = GROUP_ROLE_NONE;
+            winner
+        }
+
+        // Drops the siblings of finished groups. Their collateral stays locked
+        // for the leg that won, so nothing is released.
+        pub fn cancel_done_groups(&mut self) {
+            let mut remaining = self.emptied();
+            for i in 0..MAX_ORDERS {
+                let buyer = self.buy_orders[i];
+                if i < self.buy_count as usize && !self.group_cancelled(&buyer) {
+                    remaining.insert_buy(buyer);
+                }
+                let seller = self.sell_orders[i];
+                if i < self.sell_count as usize && !self.group_cancelled(&seller) {
+                    remaining.insert_sell(seller);
+                }
+            }
+            *self = remaining;
         }
 
         pub fn insert_buy(&mut self, order: Order) -> bool {
@@ -348,8 +954,11 @@ mod circuits {
             let a = &self.buy_orders[i];
             let b = &self.buy_orders[j];
 
-            if a.price != b.price {
-                a.price > b.price
+            let a_price = a.buy_rank_price(self.reference_price);
+            let b_price = b.buy_rank_price(self.reference_price);
+
+            if a_price != b_price {
+                a_price > b_price
             } else {
                 a.timestamp < b.timestamp
             }
@@ -359,8 +968,11 @@ mod circuits {
             let a = &self.sell_orders[i];
             let b = &self.sell_orders[j];
 
-            if a.price != b.price {
-                a.price < b.price
+            let a_price = a.sell_rank_price(self.reference_price);
+            let b_price = b.sell_rank_price(self.reference_price);
+
+            if a_price != b_price {
+                a_price < b_price
             } else {
                 a.timestamp < b.timestamp
             }
@@ -461,6 +1073,195 @@ mod circuits {
         pub fn has_sell(&self) -> bool {
             self.sell_count > 0
         }
+
+        // Quantity buy slot i would take if the auction cleared at price, 0 when it
+        // doesn't cross or its budget / slippage guard rules it out
+        pub fn buy_quantity_at(&self, i: usize, price: u64, price_scale: u64) -> u64 {
+            let order = self.buy_orders[i];
+            let quantity = order.buy_capacity(price, price_scale);
+
+            if i < self.buy_count as usize
+                && !self.group_cancelled(&order)
+                && order.has_reference(self.reference_price)
+                && order.buy_rank_price(self.reference_price) >= price
+                && order.buy_accepts(price)
+            {
+                quantity
+            } else {
+                0
+            }
+        }
+
+        pub fn sell_quantity_at(&self, i: usize, price: u64) -> u64 {
+            let order = self.sell_orders[i];
+
+            if i < self.sell_count as usize
+                && !self.group_cancelled(&order)
+                && order.has_reference(self.reference_price)
+                && order.sell_rank_price(self.reference_price) <= price
+                && order.sell_accepts(price)
+            {
+                order.amount
+            } else {
+                0
+            }
+        }
+
+        // Peg orders were ranked against whatever reference was known when they
+        // came in, so the heaps are rebuilt once the batch reference is known.
+        pub fn set_reference_price(&mut self, reference_price: u64) {
+            let mut repriced = self.emptied();
+            repriced.reference_price = reference_price;
+
+            for i in 0..MAX_ORDERS {
+                if i < self.buy_count as usize {
+                    repriced.insert_buy(self.buy_orders[i]);
+                }
+                if i < self.sell_count as usize {
+                    repriced.insert_sell(self.sell_orders[i]);
+                }
+            }
+
+            *self = repriced;
+        }
+
+        // Market orders only live for one batch: drop whatever is left of them
+        // from the book and report the lock each one still holds.
+        pub fn cancel_market_orders(&mut self) -> [LockRelease; MAX_LOCK_RELEASES] {
+            let mut releases = [LockRelease::empty(); MAX_LOCK_RELEASES];
+            let mut remaining = self.emptied();
+
+            for i in 0..MAX_ORDERS {
+                let order = self.buy_orders[i];
+                if i < self.buy_count as usize {
+                    if order.is_market() {
+                        releases[i] = LockRelease {
+                            order_id: order.order_id,
+                            order_type: order.order_type,
+                            amount: order.quote_budget,
+                        };
+                    } else {
+                        remaining.insert_buy(order);
+                    }
+                }
+            }
+
+            for i in 0..MAX_ORDERS {
+                let order = self.sell_orders[i];
+                if i < self.sell_count as usize {
+                    if order.is_market() {
+                        releases[MAX_ORDERS + i] = LockRelease {
+                            order_id: order.order_id,
+                            order_type: order.order_type,
+                            amount: order.amount,
+                        };
+                    } else {
+                        remaining.insert_sell(order);
+                    }
+                }
+            }
+
+            *self = remaining;
+            releases
+        }
+    }
+
+    // Stop and stop-limit orders wait here until the reference price reaches
+    // their trigger. The order is stored in the form it takes once triggered.
+    #[derive(Copy, Clone)]
+    pub struct TriggerBook {
+        pub orders: [Order; MAX_STOP_ORDERS],
+        pub trigger_prices: [u64; MAX_STOP_ORDERS],
+        pub count: u8,
+    }
+
+    impl TriggerBook {
+        pub fn new() -> Self {
+            TriggerBook {
+                orders: [Order::empty(); MAX_STOP_ORDERS],
+                trigger_prices: [0u64; MAX_STOP_ORDERS],
+                count: 0,
+            }
+        }
+
+        pub fn insert(&mut self, order: Order, trigger_price: u64) -> bool {
+            let success = self.count < MAX_STOP_ORDERS as u8;
+            for i in 0..MAX_STOP_ORDERS {
+                if success && i == self.count as usize {
+                    self.orders[i] = order;
+                    self.trigger_prices[i] = trigger_price;
+                }
+            }
+            if success {
+                self.count += 1;
+            }
+            success
+        }
+
+        // Buy stops fire once the reference trades up to the trigger, sell stops
+        // once it trades down to it. Nothing fires without a reference price.
+        pub fn is_triggered(&self, i: usize, reference_price: u64) -> bool {
+            let order = self.orders[i];
+            let trigger_price = self.trigger_prices[i];
+            let crossed = if order.is_buy() {
+                reference_price >= trigger_price
+            } else {
+                reference_price <= trigger_price
+            };
+            i < self.count as usize && reference_price > 0 && crossed
+        }
+    }
+
+    // Each stop takes five chunks: order_id | amount, price | trigger_price,
+    // quote_budget | min_fill_qty, owner_tag | order_type | order_kind and
+    // group_id | group_role
+    #[derive(Copy, Clone)]
+    pub struct TriggerBookFlat {
+        pub stop_chunks: [u128; 5 * MAX_STOP_ORDERS],
+        pub stop_count: u128,
+    }
+
+    impl TriggerBookFlat {
+        pub fn from_trigger_book(book: TriggerBook) -> Self {
+            let mut stop_chunks = [0u128; 5 * MAX_STOP_ORDERS];
+            for i in 0..MAX_STOP_ORDERS {
+                let order = book.orders[i];
+                stop_chunks[5 * i] = order.order_id as u128 + order.amount as u128 * POW64;
+                stop_chunks[5 * i + 1] = order.price as u128 + book.trigger_prices[i] as u128 * POW64;
+                stop_chunks[5 * i + 2] = order.quote_budget as u128 + order.min_fill_qty as u128 * POW64;
+                stop_chunks[5 * i + 3] = order.owner_tag as u128
+                    + order.order_type as u128 * POW64
+                    + order.order_kind as u128 * POW64 * 256;
+                stop_chunks[5 * i + 4] = order.group_id as u128 + order.group_role as u128 * POW64;
+            }
+
+            TriggerBookFlat {
+                stop_chunks,
+                stop_count: book.count as u128,
+            }
+        }
+
+        pub fn to_trigger_book(self) -> TriggerBook {
+            let mut book = TriggerBook::new();
+            for i in 0..MAX_STOP_ORDERS {
+                let mut order = Order::empty();
+                order.order_id = self.stop_chunks[5 * i] as u64;
+                order.amount = (self.stop_chunks[5 * i] / POW64) as u64;
+                order.price = self.stop_chunks[5 * i + 1] as u64;
+                order.quote_budget = self.stop_chunks[5 * i + 2] as u64;
+                order.min_fill_qty = (self.stop_chunks[5 * i + 2] / POW64) as u64;
+                order.owner_tag = self.stop_chunks[5 * i + 3] as u64;
+                order.order_type = (self.stop_chunks[5 * i + 3] / POW64) as u8;
+                order.order_kind = (self.stop_chunks[5 * i + 3] / (POW64 * 256)) as u8;
+                order.group_id = self.stop_chunks[5 * i + 4] as u64;
+                order.group_role = (self.stop_chunks[5 * i + 4] / POW64) as u8;
+
+                book.orders[i] = order;
+                book.trigger_prices[i] = (self.stop_chunks[5 * i + 1] / POW64) as u64;
+            }
+            book.count = self.stop_count as u8;
+            book
+        }
     }
 
     #[derive(Copy, Clone)]
@@ -487,6 +1288,8 @@ mod circuits {
     pub struct MatchResult {
         pub matches: [MatchedOrder; MAX_MATCHES_PER_BATCH],
         pub num_matches: u8,
+        pub releases: [LockRelease; MAX_LOCK_RELEASES],
+        pub num_releases: u8,
     }
 
     impl MatchResult {
@@ -494,6 +1297,8 @@ mod circuits {
             MatchResult {
                 matches: [MatchedOrder::empty(); MAX_MATCHES_PER_BATCH],
                 num_matches: 0,
+                releases: [LockRelease::empty(); MAX_LOCK_RELEASES],
+                num_releases: 0,
             }
         }
 
@@ -505,6 +1310,81 @@ mod circuits {
                 }
             }
         }
+
+        // Appends a release, empty releases are skipped
+        pub fn push_release(&mut self, release: LockRelease) {
+            if release.amount > 0 {
+                for i in 0..MAX_LOCK_RELEASES {
+                    if i == self.num_releases as usize {
+                        self.releases[i] = release;
+                    }
+                }
+                self.num_releases += 1;
+            }
+        }
+    }
+
+    // One buyer and one seller trading quantity at the clearing price, settled
+    // like a continuous match
+    #[derive(Copy, Clone)]
+    pub struct AuctionFill {
+        pub buyer_order_id: u64,
+        pub seller_order_id: u64,
+        pub quantity: u64,
+    }
+
+    impl AuctionFill {
+        pub fn empty() -> Self {
+            AuctionFill {
+                buyer_order_id: 0,
+                seller_order_id: 0,
+                quantity: 0,
+            }
+        }
+    }
+
+    // Every fill of a batch auction trades at the revealed clearing price, so
+    // only the per order quantities are kept secret
+    pub struct AuctionResult {
+        pub fills: [AuctionFill; MAX_AUCTION_FILLS],
+        pub num_fills: u8,
+        pub releases: [LockRelease; MAX_LOCK_RELEASES],
+        pub num_releases: u8,
+    }
+
+    impl AuctionResult {
+        pub fn empty() -> Self {
+            AuctionResult {
+                fills: [AuctionFill::empty(); MAX_AUCTION_FILLS],
+                num_fills: 0,
+                releases: [LockRelease::empty(); MAX_LOCK_RELEASES],
+                num_releases: 0,
+            }
+        }
+
+        // Appends a fill, empty fills are skipped
+        pub fn push_fill(&mut self, fill: AuctionFill) {
+            if fill.quantity > 0 {
+                for i in 0..MAX_AUCTION_FILLS {
+                    if i == self.num_fills as usize {
+                        self.fills[i] = fill;
+                    }
+                }
+                self.num_fills += 1;
+            }
+        }
+
+        // Appends a release, empty releases are skipped
+        pub fn push_release(&mut self, release: LockRelease) {
+            if release.amount > 0 {
+                for i in 0..MAX_LOCK_RELEASES {
+                    if i == self.num_releases as usize {
+                        self.releases[i] = release;
+                    }
+                }
+                self.num_releases += 1;
+            }
+        }
     }
 
     #[instruction]
@@ -515,44 +1395,93 @@ mod circuits {
     }
 
     #[instruction]
-    pub fn init_user_ledger(user: Shared) -> Enc<Shared, Balances> {
+    pub fn init_user_ledger(
+        user: Shared,
+        mxe: Mxe,
+        owner_tag: u64, // derived from the ledger's owner by the program
+    ) -> (Enc<Shared, Balances>, Enc<Mxe, u64>) {
         let balances = Balances {
             base_total: 0,
             base_available: 0,
             quote_total: 0,
             quote_available: 0,
         };
-        user.from_arcis(balances)
+        // Sealed once here, the order circuits read it back from the ledger
+        // for self trade prevention
+        (user.from_arcis(balances), mxe.from_arcis(owner_tag))
     }
     pub struct UserSensitiveData {
         pub amount: u64,
         pub price: u64,
+        pub quote_budget: u64, // max quote a market or peg buy may spend, ignored otherwise
+        pub min_fill_qty: u64, // smallest fill the order accepts, 0 = any
+        pub display_size: u64, // iceberg clip size for limit orders, 0 = fully displayed
+    }
+
+    // What submit_order_check took from the ledger for an order, kept for the
+    // MXE on the order account. The steps after the check work from this
+    // record instead of sizes and prices the user sends again.
+    #[derive(Copy, Clone)]
+    pub struct OrderLock {
+        pub amount: u64,
+        pub price: u64,
+        pub quote_budget: u64,
+        pub locked: u64, // collateral still held for the order, 0 for a shared or rejected leg
     }
 
 
-    #[instruction]
-    pub fn submit_order_check(
-        user_sensitive: Enc<Shared, UserSensitiveData>, // User's x25519
-        user_ledger: Enc<Shared, &Balances>,               // Shared
+    // Whether value sits on a grid of step, 0 always does
+    fn on_grid(value: u64, step: u64) -> bool {
+        (value / step) * step == value
+    }
+
+    // Status = 5: Insufficient balance, 6: Outside the entry price band,
+    // 7: Price off the tick grid, 8: Size off the lot grid, 9: Below the minimum notional
+    fn reject_status(reason: u8) -> u8 {
+        if reason == REJECT_REASON_PRICE_BAND {
+            6
+        } else if reason == REJECT_REASON_TICK_SIZE {
+            7
+        } else if reason == REJECT_REASON_LOT_SIZE {
+            8
+        } else if reason == REJECT_REASON_MIN_NOTIONAL {
+            9
+        } else {
+            5
+        }
+    }
+
+    // Balance, entry band and grid checks of submit_order_check. Returns
+    // whether the order goes on, the ledger with its lock taken, the status
+    // and reject reason for the user and the lock to record.
+    pub fn entry_check(
+        sensitive: UserSensitiveData,
+        balances: Balances,
+        first: OrderLock,
         order_type: u8,
-        timestamp: u64,
-    ) -> (
-        bool,
-        Enc<Shared, Balances>,
-        Enc<Shared, OrderStatus>,
-    ) {
-        let sensitive = user_sensitive.to_arcis();
-        let mut ledger = *(user_ledger.to_arcis());
+        order_kind: u8,
+        lock_shared: u8,
+        first_order_type: u8,
+        group_role: u8,
+        band_reference: u64,
+        band_bps: u64,
+        tick_size: u64,
+        lot_size: u64,
+        min_notional: u64,
+        price_scale: u64,
+    ) -> (bool, Balances, OrderStatus, u8, OrderLock) {
+        let mut ledger = balances;
 
         // Calculate required amount
-        // Note: amount and price are scaled by SCALE_FACTOR (100 for 2 decimals)
-        // When multiplying two scaled values, divide by SCALE_FACTOR to get correct scale
-        let required = if order_type == 0 {
+        let priced = order_kind == ORDER_KIND_LIMIT || order_kind == ORDER_KIND_STOP_LIMIT;
+        let required = if order_type == 0 && !priced {
+            // Market, peg and stop buys have no fixed price, they lock the user chosen quote budget
+            sensitive.quote_budget
+        } else if order_type == 0 {
             // Buy order needs quote token
-            // amount * price gives result scaled by SCALE_FACTOR^2, so divide by SCALE_FACTOR
-            (sensitive.amount * sensitive.price) / SCALE_FACTOR
+            quote_owed(sensitive.amount, sensitive.price, price_scale)
         } else {
-            // Sell order needs base token (amount is already scaled correctly)
+            // Sell order needs base token
             sensitive.amount
         };
 
@@ -564,14 +1493,62 @@ mod circuits {
         };
 
         let mut possible = true;
+        let mut reason = REJECT_REASON_NONE;
+        let shared = lock_shared == 1;
 
-        if available < required {
+        if available < required && !shared {
             // Insufficient balance
             possible = false;
+            reason = REJECT_REASON_INSUFFICIENT_BALANCE;
+        }
+
+        // A shared leg has to fit inside what the first leg holds. An OCO leg
+        // is an alternative to the first one, so it trades the same side for
+        // no more than was locked; a bracket exit unwinds the entry, so it
+        // trades the other side for no more than the entry buys or sells.
+        let exit = group_role == GROUP_ROLE_EXIT;
+        let side_matches = if exit { order_type != first_order_type } else { order_type == first_order_type };
+        let covered = if exit { sensitive.amount <= first.amount } else { required <= first.locked };
+        if shared && !(side_matches && covered) {
+            possible = false;
+            reason = REJECT_REASON_INSUFFICIENT_BALANCE;
+        }
+
+        // Fat finger guard: a limit price too far from the feed is turned away
+        // before it locks anything. Unpriced kinds take the book's price.
+        if priced && !within_band(sensitive.price, band_reference, band_bps) {
+            possible = false;
+            reason = REJECT_REASON_PRICE_BAND;
+        }
+
+        // Dust and off grid orders would only clog the book. Budget buys are
+        // worth their budget, unpriced sells can't be valued until they trade.
+        let notional = if priced {
+            quote_due(sensitive.amount, sensitive.price, price_scale)
+        } else if order_type == 0 {
+            sensitive.quote_budget
+        } else {
+            min_notional
+        };
+        if notional < min_notional {
+            possible = false;
+            reason = REJECT_REASON_MIN_NOTIONAL;
+        }
+        // Iceberg clips and minimum fills trade in whole lots as well
+        let lots = on_grid(sensitive.amount, lot_size)
+            && on_grid(sensitive.display_size, lot_size)
+            && on_grid(sensitive.min_fill_qty, lot_size);
+        if !lots {
+            possible = false;
+            reason = REJECT_REASON_LOT_SIZE;
+        }
+        if priced && !on_grid(sensitive.price, tick_size) {
+            possible = false;
+            reason = REJECT_REASON_TICK_SIZE;
         }
 
-        // Lock funds only when the its possible
-        if possible {
+        // Lock funds only when the its possible, grouped legs lock once per group
+        if possible && !shared {
             if order_type == 1 {
                 ledger.quote_available -= required;
                 // Note: We don't track locked separately in this simplified version
@@ -587,7 +1564,7 @@ mod circuits {
                 amount: sensitive.amount,
                 price: sensitive.price,
                 status: if possible { 1 } else { 2 }, // 1=processing, 2=rejected
-                locked_amount: if possible { required } else { 0 },
+                locked_amount: if possible && !shared { required } else { 0 },
                 filled_amount: 0,
                 execution_price: 0,
             }
@@ -596,85 +1573,251 @@ mod circuits {
                 order_type,
                 amount: sensitive.amount,
                 price: sensitive.price,
-                status: 5, // Status = 5: Insufficient balance
+                status: reject_status(reason),
                 locked_amount: 0,
                 filled_amount: 0,
                 execution_price: 0,
             }
         };
 
-        (
-            possible.reveal(),
-            user_ledger.owner.from_arcis(ledger),
-            user_sensitive.owner.from_arcis(status),
-        )
-
+        let lock = OrderLock {
+            amount: sensitive.amount,
+            price: sensitive.price,
+            quote_budget: sensitive.quote_budget,
+            locked: if possible && !shared { required } else { 0 },
+        };
 
+        (possible, ledger, status, reason, lock)
     }
 
     #[instruction]
-    pub fn submit_order(
+    pub fn submit_order_check(
         user_sensitive: Enc<Shared, UserSensitiveData>, // User's x25519
-        orderbook_ctx: Enc<Mxe, &OrderBookFlat>,            // MXE
-        order_id: u64,
+        user_ledger: Enc<Shared, &Balances>,               // Shared
+        first_lock: Enc<Mxe, &OrderLock>, // MXE, the group's first leg, only read for a shared leg
         order_type: u8,
+        order_kind: u8,
+        lock_shared: u8, // 1 when an earlier leg of the order's group already holds the collateral
+        first_order_type: u8, // side the first leg locked for
+        group_role: u8,
+        band_reference: u64, // price feed reading, 0 = no entry band
+        band_bps: u64,
+        tick_size: u64, // price grid, at least 1
+        lot_size: u64,  // size grid, at least 1
+        min_notional: u64, // smallest quote value an order may carry, 0 = any
+        price_scale: u64,  // 10^base decimals
         timestamp: u64,
+        mxe: Mxe,
     ) -> (
-        Enc<Mxe, OrderBookFlat>,      // Updated orderbook
-        // Enc<Shared, Balances>,       // Updated ledger
+        bool,
+        Enc<Shared, Balances>,
+        Enc<Shared, OrderStatus>,
+        Enc<Shared, u8>,
+        Enc<Mxe, OrderLock>,
+    ) {
+        let (possible, ledger, status, reason, lock) = entry_check(
+            user_sensitive.to_arcis(),
+            *(user_ledger.to_arcis()),
+            *(first_lock.to_arcis()),
+            order_type,
+            order_kind,
+            lock_shared,
+            first_order_type,
+            group_role,
+            band_reference,
+            band_bps,
+            tick_size,
+            lot_size,
+            min_notional,
+            price_scale,
+        );
+
+        (
+            possible.reveal(),
+            user_ledger.owner.from_arcis(ledger),
+            user_sensitive.owner.from_arcis(status),
+            user_sensitive.owner.from_arcis(reason),
+            mxe.from_arcis(lock),
+        )
+    }
+
+    // Price a limit order rests at under its post-only setting: its own
+    // price unless it would take, one tick behind the best opposite price
+    // once it slides. A taking order that can't slide is turned away (false).
+    pub fn post_only_price(
+        orderbook: &OrderBook,
+        order_type: u8,
+        price: u64,
+        post_only: u8,
+        tick_size: u64,
+    ) -> (u64, bool) {
+        // Best prices resting on each side, a market order on the other side
+        // crosses anything
+        let mut best_bid = 0u64;
+        let mut best_ask = MARKET_BUY_RANK_PRICE;
+        let mut has_bid = false;
+        let mut has_ask = false;
+        for i in 0..MAX_ORDERS {
+            let bid = orderbook.buy_orders[i].buy_rank_price(0);
+            if i < orderbook.buy_count as usize && !orderbook.buy_orders[i].is_peg() {
+                has_bid = true;
+                if bid > best_bid {
+                    best_bid = bid;
+                }
+            }
+            let ask = orderbook.sell_orders[i].sell_rank_price(0);
+            if i < orderbook.sell_count as usize && !orderbook.sell_orders[i].is_peg() {
+                has_ask = true;
+                if ask < best_ask {
+                    best_ask = ask;
+                }
+            }
+        }
+
+        let crosses = if order_type == 0 {
+            has_ask && price >= best_ask
+        } else {
+            has_bid && price <= best_bid
+        };
+        let can_slide = if order_type == 0 {
+            best_ask > tick_size
+        } else {
+            best_bid < MARKET_BUY_RANK_PRICE - tick_size
+        };
+        let slid_price = if order_type == 0 {
+            best_ask - if can_slide { tick_size } else { 0 }
+        } else {
+            best_bid + if can_slide { tick_size } else { 0 }
+        };
+        let slide = post_only == POST_ONLY_SLIDE && crosses && can_slide;
+        let rejected = post_only != POST_ONLY_NONE && crosses && !slide;
+
+        (if slide { slid_price } else { price }, !rejected)
+    }
+
+    #[instruction]
+    pub fn submit_order(
+        user_sensitive: Enc<Shared, UserSensitiveData>, // User's x25519
+        orderbook_ctx: Enc<Mxe, &OrderBookFlat>,            // MXE
+        user_ledger: Enc<Shared, &Balances>,                // Shared, refunded on a post-only reject
+        order_lock: Enc<Mxe, &OrderLock>,                   // MXE, recorded by submit_order_check
+        order_id: u64,
+        order_type: u8,
+        order_kind: u8,
+        ledger_owner: Enc<Mxe, &u64>, // MXE, sealed by init_user_ledger
+        group_id: u64,
+        group_role: u8,
+        post_only: u8,
+        tick_size: u64,
+        lot_size: u64,
+        min_notional: u64,
+        price_scale: u64,
+        timestamp: u64,
+    ) -> (
+        Enc<Mxe, OrderBookFlat>,      // Updated orderbook
+        Enc<Shared, Balances>,        // Updated ledger
+        Enc<Mxe, OrderLock>,          // Lock left after a refund
         // Enc<Shared, OrderStatus>, // For user to view
         bool,                     // Success
+        bool,                     // Refund overflowed the ledger
     ) {
     // ) -> Enc<Mxe, OrderBookFlat> {
         let sensitive = user_sensitive.to_arcis();
-        // let mut ledger = *(user_ledger.to_arcis());
+        let owner_tag = *(ledger_owner.to_arcis());
+        let original = *(user_ledger.to_arcis());
+        let mut ledger = original;
+        let mut lock = *(order_lock.to_arcis());
         let mut orderbook_flat = *(orderbook_ctx.to_arcis());
 
         let mut orderbook = OrderBookFlat::to_orderbook(orderbook_flat);
 
-        // Calculate required amount
-        // Note: amount and price are scaled by SCALE_FACTOR (100 for 2 decimals)
-        // When multiplying two scaled values, divide by SCALE_FACTOR to get correct scale
-        let required = if order_type == 0 {
-            // Buy order needs quote token
-            sensitive.amount * sensitive.price
+        // Size and price are the ones submit_order_check locked for, only the
+        // fill and display settings come from this submission
+        let amount = lock.amount;
+        let is_budget_buy = order_type == 0 && order_kind != ORDER_KIND_LIMIT;
+        let required = lock.locked;
+
+        // A post-only limit that would take is either rejected or slid to one
+        // tick behind the best opposite price
+        let is_post_only = post_only != POST_ONLY_NONE && order_kind == ORDER_KIND_LIMIT;
+        let (price, post_only_ok) = post_only_price(
+            &orderbook,
+            order_type,
+            lock.price,
+            if is_post_only { post_only } else { POST_ONLY_NONE },
+            tick_size,
+        );
+        let slide = price != lock.price;
+
+        // The recorded size and price have to sit on the market's grid as it
+        // stands now, and the clip and minimum fill sent here on its lots
+        let priced = order_kind == ORDER_KIND_LIMIT || order_kind == ORDER_KIND_STOP_LIMIT;
+        let notional = if priced {
+            quote_due(amount, lock.price, price_scale)
+        } else if order_type == 0 {
+            lock.quote_budget
         } else {
-            // Sell order needs base token (amount is already scaled correctly)
-            sensitive.amount
+            min_notional
         };
+        let on_market_grid = notional >= min_notional
+            && on_grid(amount, lot_size)
+            && (!priced || on_grid(lock.price, tick_size))
+            && on_grid(sensitive.display_size, lot_size)
+            && on_grid(sensitive.min_fill_qty, lot_size);
+
+        let rejected = !post_only_ok || !on_market_grid;
+
+        // A rejected order gets its whole lock back, a slid bid the part its
+        // lower price no longer needs. Never more than was recorded.
+        let still_owed = quote_owed(amount, price, price_scale);
+        let refund = if rejected {
+            required
+        } else if slide && order_type == 0 && still_owed < required {
+            required - still_owed
+        } else {
+            0
+        };
+        // Mirror the lock taken in submit_order_check
+        let (quote_available, quote_overflow) = checked_add(ledger.quote_available, refund);
+        let (base_available, base_overflow) = checked_add(ledger.base_available, refund);
+        let overflow = if order_type == 1 {
+            ledger.quote_available = quote_available;
+            quote_overflow
+        } else {
+            ledger.base_available = base_available;
+            base_overflow
+        };
+        if !overflow {
+            lock.locked = required - refund;
+            lock.price = price;
+        }
 
-        // Check available balance
-        // let available = if order_type == 0 {
-        //     ledger.quote_available
-        // } else {
-        //     ledger.base_available
-        // };
-
-        let mut possible = true;
-
-        // if available < required {
-        //     // Insufficient balance
-        //     possible = false;
-        // }
-
-        // Lock funds
-        // if order_type == 1 {
-        //     ledger.quote_available -= required;
-        //     // Note: We don't track locked separately in this simplified version
-        //     // In production, you'd have base_locked and quote_locked fields
-        // } else {
-        //     ledger.base_available -= required;
-        // }
+        // An order whose refund can't be booked stays out of the book
+        let possible = !rejected && !overflow;
 
         // Add to orderbook
 
+        // An iceberg shows one clip and keeps the rest of its amount in reserve
+        let iceberg = order_kind == ORDER_KIND_LIMIT
+            && sensitive.display_size > 0
+            && sensitive.display_size < amount;
+
         let order = if possible {
             Order {
                 order_id,
-                amount: sensitive.amount,
-                price: sensitive.price,
+                amount: if iceberg { sensitive.display_size } else { amount },
+                price,
                 order_type,
                 timestamp,
+                order_kind,
+                quote_budget: if is_budget_buy { lock.quote_budget } else { 0 },
+                min_fill_qty: sensitive.min_fill_qty,
+                owner_tag,
+                display_size: if iceberg { sensitive.display_size } else { 0 },
+                reserve: if iceberg { amount - sensitive.display_size } else { 0 },
+                group_id,
+                group_role,
+                post_only: is_post_only,
             }
         } else {
             Order::empty()
@@ -690,126 +1833,1130 @@ mod circuits {
             false
         };
 
-        let status = if possible {
-            OrderStatus {
-                order_type,
-                amount: sensitive.amount,
-                price: sensitive.price,
-                status: if success { 1 } else { 2 }, // 1=processing, 2=rejected
-                locked_amount: if success { required } else { 0 },
-                filled_amount: 0,
-                execution_price: 0,
-            }
-        } else {
-            OrderStatus {
-                order_type,
-                amount: sensitive.amount,
-                price: sensitive.price,
-                status: 5, // Status = 5: Insufficient balance
-                locked_amount: 0,
-                filled_amount: 0,
-                execution_price: 0,
-            }
-        };
-
         // orderbook_ctx.owner.from_arcis(OrderBookFlat::from_orderbook(orderbook))
         (
             orderbook_ctx.owner.from_arcis(OrderBookFlat::from_orderbook(orderbook)),
-        //     user_ledger.owner.from_arcis(ledger),
+            user_ledger.owner.from_arcis(if overflow { original } else { ledger }),
+            order_lock.owner.from_arcis(lock),
             // user_sensitive.owner.from_arcis(status),
             success.reveal(),
+            overflow.reveal(),
         )
     }
 
-    #[instruction]
-    pub fn match_orders(
-        clanker_authority: Shared,
-        order_book_ctxt: Enc<Mxe, OrderBookFlat>,
-    ) -> (
-        Enc<Mxe, OrderBookFlat>, 
-        Enc<Shared, MatchResult>, 
-        u8
-    ) {
-        let mut orderbook_flat = order_book_ctxt.to_arcis();
-        let mut order_book = OrderBookFlat::to_orderbook(orderbook_flat);
+    // Price a crossing pair trades at under the market's pricing rule.
+    // clearing_price is only used by the uniform rule.
+    pub fn pair_execution_price(
+        buy: &Order,
+        sell: &Order,
+        reference_price: u64,
+        pricing_rule: u8,
+        clearing_price: u64,
+    ) -> u64 {
+        let buy_price = buy.buy_rank_price(reference_price);
+        let sell_price = sell.sell_rank_price(reference_price);
+
+        // A market order trades at the resting price it meets and a peg order
+        // trades at its pegged price against a limit; two market orders have
+        // no price to trade at and are left for the cancel pass.
+        let midpoint = if buy.is_market() {
+            sell_price
+        } else if sell.is_market() {
+            buy_price
+        } else if buy.is_peg() && !sell.is_peg() {
+            buy_price
+        } else if sell.is_peg() && !buy.is_peg() {
+            sell_price
+        } else {
+            (buy_price + sell_price) / 2
+        };
+
+        // The order that rested first sets the price, ties fall back to the midpoint
+        let maker = if buy.is_market() || sell.is_market() {
+            midpoint
+        } else if buy.timestamp < sell.timestamp {
+            buy_price
+        } else if sell.timestamp < buy.timestamp {
+            sell_price
+        } else {
+            midpoint
+        };
+
+        // Reference price clamped into the pair's spread
+        let reference = if reference_price == 0 {
+            midpoint
+        } else if reference_price > buy_price {
+            buy_price
+        } else if reference_price < sell_price {
+            sell_price
+        } else {
+            reference_price
+        };
+
+        let uniform = if clearing_price == 0 {
+            midpoint
+        } else {
+            clearing_price
+        };
+
+        if pricing_rule == PRICING_RULE_MAKER {
+            maker
+        } else if pricing_rule == PRICING_RULE_REFERENCE {
+            reference
+        } else if pricing_rule == PRICING_RULE_UNIFORM {
+            uniform
+        } else {
+            midpoint
+        }
+    }
+
+    // Splits total across the eligible orders of one price level. Orders in
+    // better (priced through the level, in an auction) fill in full first.
+    // What is left goes out in time priority under ALLOCATION_PRICE_TIME;
+    // under ALLOCATION_PRO_RATA the earliest order first gets up to
+    // top_priority_pct percent of it, the rest is shared in proportion to size
+    // and the rounding dust goes out in time priority.
+    pub fn allocate_level(
+        quantities: [u64; MAX_ORDERS],
+        eligible: [bool; MAX_ORDERS],
+        better: [bool; MAX_ORDERS],
+        timestamps: [u64; MAX_ORDERS],
+        total: u64,
+        allocation: u8,
+        top_priority_pct: u8,
+    ) -> [u64; MAX_ORDERS] {
+        let mut better_total = 0u64;
+        for i in 0..MAX_ORDERS {
+            if eligible[i] && better[i] {
+                better_total += quantities[i];
+            }
+        }
+
+        // Can't happen at a volume maximising price, but ration everyone if it does
+        let better_fits = better_total <= total;
+        let mut fills = [0u64; MAX_ORDERS];
+        let mut margin = [false; MAX_ORDERS];
+        for i in 0..MAX_ORDERS {
+            if better_fits && eligible[i] && better[i] {
+                fills[i] = quantities[i];
+            } else if eligible[i] && quantities[i] > 0 {
+                margin[i] = true;
+            }
+        }
+        let share = if better_fits { total - better_total } else { total };
+
+        let top_allotment = (share * top_priority_pct as u64) / 100;
+        let mut top = [0u64; MAX_ORDERS];
+        for i in 0..MAX_ORDERS {
+            let mut earliest = margin[i];
+            for j in 0..MAX_ORDERS {
+                let earlier = timestamps[j] < timestamps[i] || (timestamps[j] == timestamps[i] && j < i);
+                if margin[j] && earlier {
+                    earliest = false;
+                }
+            }
+            if earliest {
+                top[i] = if quantities[i] < top_allotment { quantities[i] } else { top_allotment };
+            }
+        }
+
+        let mut rest = share;
+        let mut room = [0u64; MAX_ORDERS];
+        let mut room_total = 0u64;
+        for i in 0..MAX_ORDERS {
+            rest = rest - top[i];
+            if margin[i] {
+                room[i] = quantities[i] - top[i];
+                room_total += room[i];
+            }
+        }
+
+        let mut pro_rata = [0u64; MAX_ORDERS];
+        let mut allocated = 0u64;
+        for i in 0..MAX_ORDERS {
+            if margin[i] && room_total > 0 {
+                pro_rata[i] = ((room[i] as u128 * rest as u128) / room_total as u128) as u64;
+            }
+            allocated += pro_rata[i];
+        }
+
+        let mut dust_room = [0u64; MAX_ORDERS];
+        for i in 0..MAX_ORDERS {
+            dust_room[i] = room[i] - pro_rata[i];
+        }
+        let dust = fill_by_time(dust_room, margin, timestamps, rest - allocated);
+        let by_time = fill_by_time(quantities, margin, timestamps, share);
+
+        for i in 0..MAX_ORDERS {
+            if margin[i] {
+                fills[i] = if allocation == ALLOCATION_PRO_RATA {
+                    top[i] + pro_rata[i] + dust[i]
+                } else {
+                    by_time[i]
+                };
+            }
+        }
+        fills
+    }
+
+    // Quantity and price a bid and an ask would trade at, and whether they can
+    // trade at all under both sides' price, budget and minimum fill limits.
+    // A post-only order only ever trades as the maker.
+    fn pair_fill(
+        buy: &Order,
+        sell: &Order,
+        reference_price: u64,
+        pricing_rule: u8,
+        clearing_price: u64,
+        price_scale: u64,
+    ) -> (u64, u64, bool) {
+        let execution_price = pair_execution_price(
+            buy,
+            sell,
+            reference_price,
+            pricing_rule,
+            clearing_price,
+        );
+        let priced = !(buy.is_market() && sell.is_market())
+            && buy.has_reference(reference_price)
+            && sell.has_reference(reference_price);
+
+        let buy_quantity = buy.buy_capacity(execution_price, price_scale);
+        let fill_quantity = if buy_quantity < sell.amount {
+            buy_quantity
+        } else {
+            sell.amount
+        };
+
+        let compatible = priced
+            && buy.buy_rank_price(reference_price) >= sell.sell_rank_price(reference_price)
+            && buy.buy_accepts(execution_price)
+            && sell.sell_accepts(execution_price)
+            && fill_quantity > 0
+            && buy.accepts_fill(fill_quantity)
+            && sell.accepts_fill(fill_quantity)
+            && !(buy.post_only && sell.timestamp < buy.timestamp)
+            && !(sell.post_only && buy.timestamp < sell.timestamp);
+
+        (fill_quantity, execution_price, compatible)
+    }
+
+    // Circuit breaker: an execution price more than band_bps basis points away
+    // from the band reference is never traded. No reference or no band = off.
+    pub fn within_band(execution_price: u64, band_reference: u64, band_bps: u64) -> bool {
+        let price = execution_price as u128 * 10000;
+        let lower = band_reference as u128 * (10000 - band_bps) as u128;
+        let upper = band_reference as u128 * (10000 + band_bps) as u128;
+        band_bps == 0 || band_reference == 0 || (price >= lower && price <= upper)
+    }
 
+    // Crosses up to MAX_MATCHES_PER_BATCH pairs. Each round pairs the best bid
+    // that has a compatible ask with the best such ask, so an order whose
+    // minimum fill can't be met is passed over but keeps its place in the book.
+    // When the best pair belongs to one owner the stp_policy is applied instead.
+    // In pro rata mode the newer order of the best pair trades against the whole
+    // opposite price level, split by allocate_level, rather than its best order.
+    // An iceberg trades one displayed clip at a time and is refilled from its
+    // reserve with the batch timestamp once that clip is gone.
+    // The first fill of a grouped leg records its group on the book, from then
+    // on the leg's one-cancels-other siblings can't trade and are dropped.
+    // Market orders don't outlive the batch; every lock an order gives up is
+    // summed per slot and released once when the book is rebuilt.
+    fn match_batch(
+        order_book: OrderBook,
+        pricing_rule: u8,
+        clearing_price: u64,
+        stp_policy: u8,
+        allocation_mode: u8,
+        top_priority_pct: u8,
+        band_reference: u64,
+        band_bps: u64,
+        price_scale: u64,
+        timestamp: u64,
+    ) -> (OrderBook, MatchResult, u8, u8) {
+        let reference_price = order_book.reference_price;
+        let mut book = order_book;
         let mut result = MatchResult::empty();
+        let mut buy_release = [0u64; MAX_ORDERS];
+        let mut sell_release = [0u64; MAX_ORDERS];
 
         let mut match_count = 0u8;
         let mut next_match_id = 0u64;
 
-        for match_idx in 0..MAX_MATCHES_PER_BATCH {
-            if order_book.has_buy() && order_book.has_sell() {
-                let buy = order_book.peek_buy();
-                let sell = order_book.peek_sell();
+        for _ in 0..MAX_MATCH_ROUNDS {
+            let mut found = false;
+            let mut best_buy = 0usize;
+            let mut best_sell = 0usize;
+            let mut best_quantity = 0u64;
+            let mut best_price = 0u64;
+
+            let mut buy_cancelled = [false; MAX_ORDERS];
+            let mut sell_cancelled = [false; MAX_ORDERS];
+            for i in 0..MAX_ORDERS {
+                buy_cancelled[i] = book.group_cancelled(&book.buy_orders[i]);
+                sell_cancelled[i] = book.group_cancelled(&book.sell_orders[i]);
+            }
+
+            for i in 0..MAX_ORDERS {
+                for j in 0..MAX_ORDERS {
+                    let (fill_quantity, execution_price, compatible) = pair_fill(
+                        &book.buy_orders[i],
+                        &book.sell_orders[j],
+                        reference_price,
+                        pricing_rule,
+                        clearing_price,
+                        price_scale,
+                    );
+                    let live = i < book.buy_count as usize
+                        && j < book.sell_count as usize
+                        && !buy_cancelled[i]
+                        && !sell_cancelled[j];
+                    let preferred = !found
+                        || book.compare_buy(i, best_buy)
+                        || (i == best_buy && book.compare_sell(j, best_sell));
+                    let in_band = within_band(execution_price, band_reference, band_bps);
+
+                    if live && compatible && in_band && preferred {
+                        found = true;
+                        best_buy = i;
+                        best_sell = j;
+                        best_quantity = fill_quantity;
+                        best_price = execution_price;
+                    }
+                }
+            }
 
-                if buy.price >= sell.price {
-                    let mut buyer = order_book.pop_buy();
-                    let mut seller = order_book.pop_sell();
+            let mut buyer = book.buy_orders[best_buy];
+            let mut seller = book.sell_orders[best_sell];
+            let self_trade = found && stp_policy != STP_NONE && buyer.owner_tag == seller.owner_tag;
+            let trade = found && !self_trade && match_count < MAX_MATCHES_PER_BATCH as u8;
+            let pro_rata = allocation_mode == ALLOCATION_PRO_RATA;
+
+            if trade && !pro_rata {
+                result.set_match(
+                    match_count,
+                    MatchedOrder {
+                        match_id: next_match_id,
+                        buyer_order_id: buyer.order_id,
+                        seller_order_id: seller.order_id,
+                        quantity: best_quantity,
+                        execution_price: best_price,
+                    },
+                );
+
+                buyer.amount = buyer.amount - best_quantity;
+                seller.amount = seller.amount - best_quantity;
+                if buyer.uses_quote_budget() {
+                    buyer.quote_budget = buyer.quote_budget - quote_owed(best_quantity, best_price, price_scale);
+                }
+                buyer = book.record_group_fill(buyer);
+                seller = book.record_group_fill(seller);
 
-                    let execution_price = (buyer.price + seller.price) / 2;
-                    let fill_quantity = if buyer.amount < seller.amount {
-                        buyer.amount
+                match_count += 1;
+                next_match_id += 1;
+            }
+
+            if self_trade {
+                // Ties count the buy as the newer order
+                let buy_newest = buyer.timestamp >= seller.timestamp;
+                let cancel_buy = stp_policy == STP_CANCEL_BOTH
+                    || (stp_policy == STP_CANCEL_NEWEST && buy_newest)
+                    || (stp_policy == STP_CANCEL_OLDEST && !buy_newest);
+                let cancel_sell = stp_policy == STP_CANCEL_BOTH
+                    || (stp_policy == STP_CANCEL_NEWEST && !buy_newest)
+                    || (stp_policy == STP_CANCEL_OLDEST && buy_newest);
+
+                // Decrement and cancel shrinks both by the smaller size, which
+                // cancels the smaller order (or both when equal)
+                let decrement = if stp_policy == STP_DECREMENT_AND_CANCEL {
+                    if buyer.amount < seller.amount { buyer.amount } else { seller.amount }
+                } else {
+                    0
+                };
+                let buy_removed = if cancel_buy { buyer.amount } else { decrement };
+                let sell_removed = if cancel_sell { seller.amount } else { decrement };
+                // A cancelled iceberg takes its hidden reserve with it
+                let buy_dropped = buy_removed + if cancel_buy { buyer.reserve } else { 0 };
+                let sell_dropped = sell_removed + if cancel_sell { seller.reserve } else { 0 };
+
+                // Budget buys hand back their whole budget once they leave the book
+                if !buyer.uses_quote_budget() {
+                    buy_release[best_buy] += quote_due(buy_dropped, buyer.price, price_scale);
+                }
+                sell_release[best_sell] += sell_dropped;
+
+                buyer.amount = buyer.amount - buy_removed;
+                seller.amount = seller.amount - sell_removed;
+                if cancel_buy {
+                    buyer.reserve = 0;
+                }
+                if cancel_sell {
+                    seller.reserve = 0;
+                }
+            }
+
+            // Filled and cancelled orders stay in their slot until the batch is done
+            book.buy_orders[best_buy] = buyer;
+            book.sell_orders[best_sell] = seller;
+
+            // Ties count the sell as the newer order, so the bids are the level
+            let buy_aggressor = buyer.timestamp > seller.timestamp;
+            let mut aggressor = if buy_aggressor { buyer } else { seller };
+            let mut level = if buy_aggressor { book.sell_orders } else { book.buy_orders };
+            let level_count = if buy_aggressor { book.sell_count } else { book.buy_count };
+            let level_price = if buy_aggressor {
+                seller.sell_rank_price(reference_price)
+            } else {
+                buyer.buy_rank_price(reference_price)
+            };
+
+            let mut quantities = [0u64; MAX_ORDERS];
+            let mut prices = [0u64; MAX_ORDERS];
+            let mut eligible = [false; MAX_ORDERS];
+            let mut timestamps = [0u64; MAX_ORDERS];
+            let mut level_total = 0u64;
+            for k in 0..MAX_ORDERS {
+                let resting = level[k];
+                let (bid, ask) = if buy_aggressor { (aggressor, resting) } else { (resting, aggressor) };
+                let (_, execution_price, compatible) =
+                    pair_fill(&bid, &ask, reference_price, pricing_rule, clearing_price, price_scale);
+                let rank_price = if buy_aggressor {
+                    resting.sell_rank_price(reference_price)
+                } else {
+                    resting.buy_rank_price(reference_price)
+                };
+                let same_owner = stp_policy != STP_NONE && resting.owner_tag == aggressor.owner_tag;
+
+                eligible[k] = k < level_count as usize
+                    && compatible
+                    && within_band(execution_price, band_reference, band_bps)
+                    && rank_price == level_price
+                    && !same_owner
+                    && !book.group_cancelled(&resting);
+                quantities[k] = if buy_aggressor { resting.amount } else { resting.buy_capacity(execution_price, price_scale) };
+                prices[k] = execution_price;
+                timestamps[k] = resting.timestamp;
+                if eligible[k] {
+                    level_total += quantities[k];
+                }
+            }
+
+            let aggressor_capacity = if buy_aggressor {
+                aggressor.buy_capacity(best_price, price_scale)
+            } else {
+                aggressor.amount
+            };
+            let crossing = if aggressor_capacity < level_total { aggressor_capacity } else { level_total };
+            let shares = allocate_level(
+                quantities,
+                eligible,
+                [false; MAX_ORDERS],
+                timestamps,
+                crossing,
+                ALLOCATION_PRO_RATA,
+                top_priority_pct,
+            );
+
+            // A share that breaks either side's minimum fill is left unfilled
+            for k in 0..MAX_ORDERS {
+                let quantity = shares[k];
+                let mut resting = level[k];
+                let fills = trade
+                    && pro_rata
+                    && eligible[k]
+                    && quantity > 0
+                    && resting.accepts_fill(quantity)
+                    && aggressor.accepts_fill(quantity)
+                    && !book.group_cancelled(&resting)
+                    && match_count < MAX_MATCHES_PER_BATCH as u8;
+
+                if fills {
+                    let (buyer_order_id, seller_order_id) = if buy_aggressor {
+                        (aggressor.order_id, resting.order_id)
                     } else {
-                        seller.amount
+                        (resting.order_id, aggressor.order_id)
                     };
-
                     result.set_match(
-                        match_idx as u8,
+                        match_count,
                         MatchedOrder {
                             match_id: next_match_id,
-                            buyer_order_id: buyer.order_id,
-                            seller_order_id: seller.order_id,
-                            quantity: fill_quantity,
-                            execution_price,
+                            buyer_order_id,
+                            seller_order_id,
+                            quantity,
+                            execution_price: prices[k],
                         },
                     );
 
-                    buyer.amount = buyer.amount - fill_quantity;
-                    seller.amount = seller.amount - fill_quantity;
-
-                    if buyer.amount > 0 {
-                        order_book.insert_buy(buyer);
+                    let spent = quote_owed(quantity, prices[k], price_scale);
+                    aggressor.amount = aggressor.amount - quantity;
+                    resting.amount = resting.amount - quantity;
+                    if buy_aggressor && aggressor.uses_quote_budget() {
+                        aggressor.quote_budget = aggressor.quote_budget - spent;
                     }
-
-                    if seller.amount > 0 {
-                        order_book.insert_sell(seller);
+                    if !buy_aggressor && resting.uses_quote_budget() {
+                        resting.quote_budget = resting.quote_budget - spent;
                     }
+                    resting = book.record_group_fill(resting);
+                    aggressor = book.record_group_fill(aggressor);
 
-                    match_count = match_idx as u8 + 1;
+                    match_count += 1;
                     next_match_id += 1;
                 }
+                level[k] = resting;
+            }
+
+            if buy_aggressor {
+                book.sell_orders = level;
+                book.buy_orders[best_buy] = aggressor;
+            } else {
+                book.buy_orders = level;
+                book.sell_orders[best_sell] = aggressor;
+            }
+
+            for i in 0..MAX_ORDERS {
+                book.buy_orders[i].replenish(timestamp);
+                book.sell_orders[i].replenish(timestamp);
             }
         }
 
         result.num_matches = match_count;
 
+        // Crossing pairs the band kept apart, counted before market remainders
+        // are cancelled
+        let mut skipped_count = 0u8;
+        for i in 0..MAX_ORDERS {
+            for j in 0..MAX_ORDERS {
+                let buyer = book.buy_orders[i];
+                let seller = book.sell_orders[j];
+                let (_, execution_price, compatible) =
+                    pair_fill(&buyer, &seller, reference_price, pricing_rule, clearing_price, price_scale);
+                let live = i < book.buy_count as usize
+                    && j < book.sell_count as usize
+                    && buyer.amount > 0
+                    && seller.amount > 0
+                    && !book.group_cancelled(&buyer)
+                    && !book.group_cancelled(&seller);
+                if live && compatible && !within_band(execution_price, band_reference, band_bps) {
+                    skipped_count += 1;
+                }
+            }
+        }
+
+        // Spent orders, market remainders and the siblings of finished groups
+        // leave the book, the rest are heaped again. A cancelled sibling's
+        // collateral backs the leg that won, so it isn't released.
+        let mut remaining = book.emptied();
+        for i in 0..MAX_ORDERS {
+            let buyer = book.buy_orders[i];
+            let buy_cancelled = book.group_cancelled(&buyer);
+            let mut buy_lock = buy_release[i];
+            if buyer.amount > 0 && !buyer.is_market() && !buy_cancelled {
+                remaining.insert_buy(buyer);
+            } else if buyer.uses_quote_budget() && !buy_cancelled {
+                buy_lock += buyer.quote_budget;
+            }
+
+            let seller = book.sell_orders[i];
+            let sell_cancelled = book.group_cancelled(&seller);
+            let mut sell_lock = sell_release[i];
+            if seller.amount > 0 && !seller.is_market() && !sell_cancelled {
+                remaining.insert_sell(seller);
+            } else if seller.is_market() && !sell_cancelled {
+                sell_lock += seller.amount;
+            }
+
+            if i < book.buy_count as usize {
+                result.push_release(LockRelease {
+                    order_id: buyer.order_id,
+                    order_type: buyer.order_type,
+                    amount: buy_lock,
+                });
+            }
+            if i < book.sell_count as usize {
+                result.push_release(LockRelease {
+                    order_id: seller.order_id,
+                    order_type: seller.order_type,
+                    amount: sell_lock,
+                });
+            }
+        }
+
+        (remaining, result, match_count, skipped_count)
+    }
+
+    // One matching pass over the book. Unfilled market remainders and self
+    // trades are cancelled in the batch, the backend refunds their locks from
+    // the releases. Matches outside the price band are skipped, only how many
+    // crossing pairs it held back is revealed. The last trade's price becomes
+    // the band reference for the next batch, 0 when nothing traded.
+    pub fn match_book(
+        order_book: OrderBook,
+        pricing_rule: u8,
+        clearing_price: u64,
+        stp_policy: u8,
+        allocation_mode: u8,
+        top_priority_pct: u8,
+        band_reference: u64,
+        band_bps: u64,
+        price_scale: u64,
+        timestamp: u64,
+    ) -> (OrderBook, MatchResult, u8, u8, u64) {
+        let (order_book, result, match_count, skipped_count) = match_batch(
+            order_book,
+            pricing_rule,
+            clearing_price,
+            stp_policy,
+            allocation_mode,
+            top_priority_pct,
+            band_reference,
+            band_bps,
+            price_scale,
+            timestamp,
+        );
+
+        let mut last_trade_price = 0u64;
+        for i in 0..MAX_MATCHES_PER_BATCH {
+            if i + 1 == match_count as usize {
+                last_trade_price = result.matches[i].execution_price;
+            }
+        }
+
+        (order_book, result, match_count, skipped_count, last_trade_price)
+    }
+
+    #[instruction]
+    pub fn match_orders(
+        clanker_authority: Shared,
+        order_book_ctxt: Enc<Mxe, OrderBookFlat>,
+        reference_price: u64,
+        pricing_rule: u8,
+        stp_policy: u8,
+        allocation_mode: u8,
+        top_priority_pct: u8,
+        band_reference: u64,
+        band_bps: u64,
+        price_scale: u64,
+        timestamp: u64,
+    ) -> (
+        Enc<Mxe, OrderBookFlat>, 
+        Enc<Shared, MatchResult>, 
+        u8,
+        u8,
+        u8,
+        u64,
+    ) {
+        let orderbook_flat = order_book_ctxt.to_arcis();
+        let mut order_book = OrderBookFlat::to_orderbook(orderbook_flat);
+        order_book.set_reference_price(reference_price);
+
+        let (order_book, result, match_count, skipped_count, last_trade_price) = match_book(
+            order_book,
+            pricing_rule,
+            0,
+            stp_policy,
+            allocation_mode,
+            top_priority_pct,
+            band_reference,
+            band_bps,
+            price_scale,
+            timestamp,
+        );
+        let release_count = result.num_releases;
+
         (
             order_book_ctxt.owner.from_arcis(OrderBookFlat::from_orderbook(order_book)),
             clanker_authority.from_arcis(result),
             match_count.reveal(),
+            release_count.reveal(),
+            skipped_count.reveal(),
+            last_trade_price.reveal(),
         )
     }
 
+    // The uniform price is the midpoint of the last pair that crosses, found
+    // by a midpoint pass whose book is thrown away
+    pub fn uniform_clearing_price(
+        order_book: OrderBook,
+        stp_policy: u8,
+        allocation_mode: u8,
+        top_priority_pct: u8,
+        band_reference: u64,
+        band_bps: u64,
+        price_scale: u64,
+        timestamp: u64,
+    ) -> u64 {
+        let (_, _, _, _, clearing_price) = match_book(
+            order_book,
+            PRICING_RULE_MIDPOINT,
+            0,
+            stp_policy,
+            allocation_mode,
+            top_priority_pct,
+            band_reference,
+            band_bps,
+            price_scale,
+            timestamp,
+        );
+        clearing_price
+    }
+
+    // Uniform price markets have their own computation so match_orders stays a
+    // single pass
+    #[instruction]
+    pub fn match_orders_uniform(
+        clanker_authority: Shared,
+        order_book_ctxt: Enc<Mxe, OrderBookFlat>,
+        reference_price: u64,
+        stp_policy: u8,
+        allocation_mode: u8,
+        top_priority_pct: u8,
+        band_reference: u64,
+        band_bps: u64,
+        price_scale: u64,
+        timestamp: u64,
+    ) -> (
+        Enc<Mxe, OrderBookFlat>,
+        Enc<Shared, MatchResult>,
+        u8,
+        u8,
+        u8,
+        u64,
+    ) {
+        let orderbook_flat = order_book_ctxt.to_arcis();
+        let mut order_book = OrderBookFlat::to_orderbook(orderbook_flat);
+        order_book.set_reference_price(reference_price);
+
+        let clearing_price = uniform_clearing_price(
+            order_book,
+            stp_policy,
+            allocation_mode,
+            top_priority_pct,
+            band_reference,
+            band_bps,
+            price_scale,
+            timestamp,
+        );
+        let (order_book, result, match_count, skipped_count, last_trade_price) = match_book(
+            order_book,
+            PRICING_RULE_UNIFORM,
+            clearing_price,
+            stp_policy,
+            allocation_mode,
+            top_priority_pct,
+            band_reference,
+            band_bps,
+            price_scale,
+            timestamp,
+        );
+        let release_count = result.num_releases;
+
+        (
+            order_book_ctxt.owner.from_arcis(OrderBookFlat::from_orderbook(order_book)),
+            clanker_authority.from_arcis(result),
+            match_count.reveal(),
+            release_count.reveal(),
+            skipped_count.reveal(),
+            last_trade_price.reveal(),
+        )
+    }
+
+    // Hands out amount in time priority, each order taking up to its capacity
+    fn fill_by_time(
+        capacity: [u64; MAX_ORDERS],
+        eligible: [bool; MAX_ORDERS],
+        timestamps: [u64; MAX_ORDERS],
+        amount: u64,
+    ) -> [u64; MAX_ORDERS] {
+        let mut fills = [0u64; MAX_ORDERS];
+
+        for i in 0..MAX_ORDERS {
+            let mut ahead = 0u64;
+            for j in 0..MAX_ORDERS {
+                let earlier = timestamps[j] < timestamps[i] || (timestamps[j] == timestamps[i] && j < i);
+                if eligible[j] && earlier {
+                    ahead += capacity[j];
+                }
+            }
+
+            let left = if amount > ahead { amount - ahead } else { 0 };
+            if eligible[i] {
+                fills[i] = if left < capacity[i] { left } else { capacity[i] };
+            }
+        }
+
+        fills
+    }
+
+    // Pairs the per order buy and sell allocations off in book order. Each
+    // step uses up at least one side's allocation and the last one uses up
+    // both, so MAX_AUCTION_FILLS steps always cover the matched volume.
+    fn pair_auction_fills(
+        order_book: OrderBook,
+        buy_fills: [u64; MAX_ORDERS],
+        sell_fills: [u64; MAX_ORDERS],
+    ) -> AuctionResult {
+        let mut result = AuctionResult::empty();
+        let mut buy_left = buy_fills;
+        let mut sell_left = sell_fills;
+        let mut buy_index = 0u8;
+        let mut sell_index = 0u8;
+
+        for _ in 0..MAX_AUCTION_FILLS {
+            let mut buy_quantity = 0u64;
+            let mut sell_quantity = 0u64;
+            let mut buyer_order_id = 0u64;
+            let mut seller_order_id = 0u64;
+            for i in 0..MAX_ORDERS {
+                if i == buy_index as usize {
+                    buy_quantity = buy_left[i];
+                    buyer_order_id = order_book.buy_orders[i].order_id;
+                }
+                if i == sell_index as usize {
+                    sell_quantity = sell_left[i];
+                    seller_order_id = order_book.sell_orders[i].order_id;
+                }
+            }
+
+            let quantity = if buy_quantity < sell_quantity { buy_quantity } else { sell_quantity };
+            result.push_fill(AuctionFill {
+                buyer_order_id,
+                seller_order_id,
+                quantity,
+            });
+
+            for i in 0..MAX_ORDERS {
+                if i == buy_index as usize {
+                    buy_left[i] = buy_quantity - quantity;
+                }
+                if i == sell_index as usize {
+                    sell_left[i] = sell_quantity - quantity;
+                }
+            }
+            if buy_quantity == quantity {
+                buy_index += 1;
+            }
+            if sell_quantity == quantity {
+                sell_index += 1;
+            }
+        }
+
+        result
+    }
+
+    // Self trade prevention for the call auction. Every crossing buy and sell
+    // of the same owner is settled by the policy before the clearing price is
+    // searched, the same way match_batch treats a self trade, so the auction
+    // never pairs an owner with themselves. Returns what each slot gave up.
+    fn prevent_auction_self_trades(
+        order_book: OrderBook,
+        stp_policy: u8,
+        reference_price: u64,
+        price_scale: u64,
+    ) -> (OrderBook, [u64; MAX_ORDERS], [u64; MAX_ORDERS]) {
+        let mut book = order_book;
+        let mut buy_release = [0u64; MAX_ORDERS];
+        let mut sell_release = [0u64; MAX_ORDERS];
+
+        for i in 0..MAX_ORDERS {
+            for j in 0..MAX_ORDERS {
+                let mut buyer = book.buy_orders[i];
+                let mut seller = book.sell_orders[j];
+                let self_trade = stp_policy != STP_NONE
+                    && i < book.buy_count as usize
+                    && j < book.sell_count as usize
+                    && buyer.amount > 0
+                    && seller.amount > 0
+                    && buyer.has_reference(reference_price)
+                    && seller.has_reference(reference_price)
+                    && buyer.owner_tag == seller.owner_tag
+                    && buyer.buy_rank_price(reference_price) >= seller.sell_rank_price(reference_price);
+
+                if self_trade {
+                    // Ties count the buy as the newer order
+                    let buy_newest = buyer.timestamp >= seller.timestamp;
+                    let cancel_buy = stp_policy == STP_CANCEL_BOTH
+                        || (stp_policy == STP_CANCEL_NEWEST && buy_newest)
+                        || (stp_policy == STP_CANCEL_OLDEST && !buy_newest);
+                    let cancel_sell = stp_policy == STP_CANCEL_BOTH
+                        || (stp_policy == STP_CANCEL_NEWEST && !buy_newest)
+                        || (stp_policy == STP_CANCEL_OLDEST && buy_newest);
+
+                    let decrement = if stp_policy == STP_DECREMENT_AND_CANCEL {
+                        if buyer.amount < seller.amount { buyer.amount } else { seller.amount }
+                    } else {
+                        0
+                    };
+                    let buy_removed = if cancel_buy { buyer.amount } else { decrement };
+                    let sell_removed = if cancel_sell { seller.amount } else { decrement };
+                    let buy_dropped = buy_removed + if cancel_buy { buyer.reserve } else { 0 };
+                    let sell_dropped = sell_removed + if cancel_sell { seller.reserve } else { 0 };
+
+                    // Budget buys hand back their whole budget once they leave the book
+                    if !buyer.uses_quote_budget() {
+                        buy_release[i] += quote_due(buy_dropped, buyer.price, price_scale);
+                    }
+                    sell_release[j] += sell_dropped;
+
+                    buyer.amount = buyer.amount - buy_removed;
+                    seller.amount = seller.amount - sell_removed;
+                    if cancel_buy {
+                        buyer.reserve = 0;
+                    }
+                    if cancel_sell {
+                        seller.reserve = 0;
+                    }
+                }
+
+                book.buy_orders[i] = buyer;
+                book.sell_orders[j] = seller;
+            }
+        }
+
+        (book, buy_release, sell_release)
+    }
+
+    // Uniform price call auction over the whole book. The clearing price is the
+    // resting price that matches the most volume, ties go to the smaller
+    // imbalance and then to the lower price. Icebergs take part with their
+    // displayed clip only and are refilled at timestamp for the next auction.
+    // Same owner orders that would cross are settled by stp_policy first.
+    #[instruction]
+    pub fn batch_auction(
+        clanker_authority: Shared,
+        order_book_ctxt: Enc<Mxe, OrderBookFlat>,
+        reference_price: u64,
+        allocation: u8,
+        stp_policy: u8,
+        price_scale: u64,
+        timestamp: u64,
+    ) -> (
+        Enc<Mxe, OrderBookFlat>,
+        Enc<Shared, AuctionResult>,
+        u64,
+        u64,
+    ) {
+        let orderbook_flat = order_book_ctxt.to_arcis();
+        let mut order_book = OrderBookFlat::to_orderbook(orderbook_flat);
+        order_book.set_reference_price(reference_price);
+        let (order_book, buy_release, sell_release) =
+            prevent_auction_self_trades(order_book, stp_policy, reference_price, price_scale);
+
+        // Candidate prices are the resting prices in the book, market orders have none
+        let mut candidates = [0u64; 2 * MAX_ORDERS];
+        let mut valid = [false; 2 * MAX_ORDERS];
+        for i in 0..MAX_ORDERS {
+            let buy = order_book.buy_orders[i];
+            let sell = order_book.sell_orders[i];
+            candidates[i] = buy.buy_rank_price(reference_price);
+            valid[i] = i < order_book.buy_count as usize
+                && !buy.is_market()
+                && buy.has_reference(reference_price);
+            candidates[MAX_ORDERS + i] = sell.sell_rank_price(reference_price);
+            valid[MAX_ORDERS + i] = i < order_book.sell_count as usize
+                && !sell.is_market()
+                && sell.has_reference(reference_price);
+        }
+
+        let mut clearing_price = 0u64;
+        let mut volume = 0u64;
+        let mut best_imbalance = 0u64;
+        for c in 0..2 * MAX_ORDERS {
+            let price = candidates[c];
+            let mut demand = 0u64;
+            let mut supply = 0u64;
+            for i in 0..MAX_ORDERS {
+                demand += order_book.buy_quantity_at(i, price, price_scale);
+                supply += order_book.sell_quantity_at(i, price);
+            }
+
+            let matched = if demand < supply { demand } else { supply };
+            let imbalance = if demand > supply { demand - supply } else { supply - demand };
+            let better = matched > volume
+                || (matched == volume && matched > 0 && imbalance < best_imbalance)
+                || (matched == volume && matched > 0 && imbalance == best_imbalance && price < clearing_price);
+
+            if valid[c] && better {
+                clearing_price = price;
+                volume = matched;
+                best_imbalance = imbalance;
+            }
+        }
+
+        let mut buy_quantities = [0u64; MAX_ORDERS];
+        let mut sell_quantities = [0u64; MAX_ORDERS];
+        let mut buy_better = [false; MAX_ORDERS];
+        let mut sell_better = [false; MAX_ORDERS];
+        let mut buy_timestamps = [0u64; MAX_ORDERS];
+        let mut sell_timestamps = [0u64; MAX_ORDERS];
+        for i in 0..MAX_ORDERS {
+            let buy = order_book.buy_orders[i];
+            let sell = order_book.sell_orders[i];
+            buy_quantities[i] = order_book.buy_quantity_at(i, clearing_price, price_scale);
+            sell_quantities[i] = order_book.sell_quantity_at(i, clearing_price);
+            buy_better[i] = buy.buy_rank_price(reference_price) > clearing_price;
+            sell_better[i] = sell.sell_rank_price(reference_price) < clearing_price;
+            buy_timestamps[i] = buy.timestamp;
+            sell_timestamps[i] = sell.timestamp;
+        }
+
+        // Orders priced through the clearing price fill in full, orders at the
+        // margin share what is left
+        let listed = [true; MAX_ORDERS];
+        let buy_fills = allocate_level(buy_quantities, listed, buy_better, buy_timestamps, volume, allocation, 0);
+        let sell_fills = allocate_level(sell_quantities, listed, sell_better, sell_timestamps, volume, allocation, 0);
+
+        let mut result = pair_auction_fills(order_book, buy_fills, sell_fills);
+        let mut remaining = order_book.emptied();
+
+        for i in 0..MAX_ORDERS {
+            let mut buyer = order_book.buy_orders[i];
+            buyer.amount = buyer.amount - buy_fills[i];
+            if buyer.uses_quote_budget() {
+                buyer.quote_budget = buyer.quote_budget - quote_owed(buy_fills[i], clearing_price, price_scale);
+            }
+            if buy_fills[i] > 0 {
+                buyer = remaining.record_group_fill(buyer);
+            }
+            buyer.replenish(timestamp);
+
+            if i < order_book.buy_count as usize {
+                let mut buy_lock = buy_release[i];
+                if buyer.amount > 0 {
+                    remaining.insert_buy(buyer);
+                } else if buyer.uses_quote_budget() {
+                    // Filled below its budget, hand back the change
+                    buy_lock += buyer.quote_budget;
+                }
+                result.push_release(LockRelease {
+                    order_id: buyer.order_id,
+                    order_type: buyer.order_type,
+                    amount: buy_lock,
+                });
+            }
+        }
+
+        // A market sell still in the book is released below with its
+        // remainder, whatever self trade prevention took goes out with it
+        let mut sell_deferred = [false; MAX_ORDERS];
+        for i in 0..MAX_ORDERS {
+            let mut seller = order_book.sell_orders[i];
+            seller.amount = seller.amount - sell_fills[i];
+            if sell_fills[i] > 0 {
+                seller = remaining.record_group_fill(seller);
+            }
+            seller.replenish(timestamp);
+
+            if i < order_book.sell_count as usize {
+                sell_deferred[i] = seller.amount > 0 && seller.is_market();
+                if seller.amount > 0 {
+                    remaining.insert_sell(seller);
+                }
+                if !sell_deferred[i] {
+                    result.push_release(LockRelease {
+                        order_id: seller.order_id,
+                        order_type: seller.order_type,
+                        amount: sell_release[i],
+                    });
+                }
+            }
+        }
+
+        // Siblings of legs that filled and market orders don't rest between auctions
+        remaining.cancel_done_groups();
+        let releases = remaining.cancel_market_orders();
+        for k in 0..MAX_LOCK_RELEASES {
+            let mut release = releases[k];
+            for i in 0..MAX_ORDERS {
+                let seller = order_book.sell_orders[i];
+                if sell_deferred[i] && release.order_type == seller.order_type && release.order_id == seller.order_id {
+                    release.amount += sell_release[i];
+                }
+            }
+            result.push_release(release);
+        }
+
+        (
+            order_book_ctxt.owner.from_arcis(OrderBookFlat::from_orderbook(remaining)),
+            clanker_authority.from_arcis(result),
+            clearing_price.reveal(),
+            volume.reveal(),
+        )
+    }
+
+    #[instruction]
+    pub fn release_order_lock(
+        user_ledger: Enc<Shared, &Balances>,
+        order_lock: Enc<Mxe, &OrderLock>,
+        amount: u64,
+        order_type: u8,
+    ) -> (Enc<Shared, Balances>, Enc<Mxe, OrderLock>, bool) {
+        let original = *(user_ledger.to_arcis());
+        let mut ledger = original;
+        let mut lock = *(order_lock.to_arcis());
+
+        // Never hand back more than the order still holds, whatever the
+        // cranker decrypted
+        let release = if amount < lock.locked { amount } else { lock.locked };
+
+        // Mirror the lock taken in submit_order_check
+        let (quote_available, quote_overflow) = checked_add(ledger.quote_available, release);
+        let (base_available, base_overflow) = checked_add(ledger.base_available, release);
+        let overflow = if order_type == 1 {
+            ledger.quote_available = quote_available;
+            quote_overflow
+        } else {
+            ledger.base_available = base_available;
+            base_overflow
+        };
+
+        // Only what was handed back leaves the lock, a later release can't
+        // get it a second time
+        if !overflow {
+            lock.locked -= release;
+        }
+
+        (
+            user_ledger.owner.from_arcis(if overflow { original } else { ledger }),
+            order_lock.owner.from_arcis(lock),
+            overflow.reveal(),
+        )
+    }
+
+    // amount and fee come back with the result, the program hands both back
+    // when the ledger can't take the deposit
     #[instruction]
     pub fn update_ledger_deposit(
         ledger_ctx: Enc<Shared, &Balances>, // Current encrypted balances
         amount: u64,
+        fee: u64, // deposit fee already taken, not credited
         is_base: u8,
-    ) -> Enc<Shared, Balances> {
-        let mut balances = *(ledger_ctx.to_arcis());
+    ) -> (Enc<Shared, Balances>, bool, u64, u64) {
+        let original = *(ledger_ctx.to_arcis());
+        let mut balances = original;
+
+        let (base_total, base_total_overflow) = checked_add(balances.base_total, amount);
+        let (base_available, base_available_overflow) = checked_add(balances.base_available, amount);
+        let (quote_total, quote_total_overflow) = checked_add(balances.quote_total, amount);
+        let (quote_available, quote_available_overflow) = checked_add(balances.quote_available, amount);
 
-        if is_base == 0 {
+        let overflow = if is_base == 0 {
             // Deposit base token
-            balances.base_total += amount;
-            balances.base_available += amount;
+            balances.base_total = base_total;
+            balances.base_available = base_available;
+            base_total_overflow || base_available_overflow
         } else {
             // Deposit quote token
-            balances.quote_total += amount;
-            balances.quote_available += amount;
-        }
+            balances.quote_total = quote_total;
+            balances.quote_available = quote_available;
+            quote_total_overflow || quote_available_overflow
+        };
 
-        ledger_ctx.owner.from_arcis(balances)
+        (
+            ledger_ctx.owner.from_arcis(if overflow { original } else { balances }),
+            overflow.reveal(),
+            amount,
+            fee,
+        )
     }
 
 
@@ -818,8 +2965,9 @@ mod circuits {
         ledger: Enc<Shared, Balances>,
         amount: u64,
         is_base: u8,
-    ) -> (Enc<Shared, Balances>, bool) {
-        let mut balances = ledger.to_arcis();
+    ) -> (Enc<Shared, Balances>, bool, bool) {
+        let original = ledger.to_arcis();
+        let mut balances = original;
 
         let available = if is_base == 0 {
             balances.base_available
@@ -828,46 +2976,720 @@ mod circuits {
         };
 
         let mut possible = false;
+        let mut underflow = false;
 
         if available >= amount {
             // Insufficient balance
             possible = true;
+            let (base_total, base_total_underflow) = checked_sub(balances.base_total, amount);
+            let (base_available, _) = checked_sub(balances.base_available, amount);
+            let (quote_total, quote_total_underflow) = checked_sub(balances.quote_total, amount);
+            let (quote_available, _) = checked_sub(balances.quote_available, amount);
+            // available >= amount already, only the totals can fall short
             if is_base == 0 {
-                balances.base_total -= amount;
-                balances.base_available -= amount;
+                balances.base_total = base_total;
+                balances.base_available = base_available;
+                underflow = base_total_underflow;
             } else {
-                balances.quote_total -= amount;
-                balances.quote_available -= amount;
+                balances.quote_total = quote_total;
+                balances.quote_available = quote_available;
+                underflow = quote_total_underflow;
+            }
+        }
+
+        let possible = possible && !underflow;
+
+        (
+            ledger.owner.from_arcis(if underflow { original } else { balances }),
+            possible.reveal(),
+            underflow.reveal(),
+        )
+    }
+
+    // Moves a volume counter into the current window. windows_elapsed is how
+    // many windows passed since it was last touched, 2 or more clears it.
+    pub fn roll_volume(volume: TradingVolume, windows_elapsed: u8) -> TradingVolume {
+        let mut rolled = volume;
+        if windows_elapsed == 1 {
+            rolled.previous = volume.current;
+            rolled.current = 0;
+        }
+        if windows_elapsed >= 2 {
+            rolled.previous = 0;
+            rolled.current = 0;
+        }
+        rolled
+    }
+
+    // Sliding window estimate: all of the current window plus the share of the
+    // previous one still inside the trailing window
+    pub fn rolling_volume(volume: TradingVolume, previous_weight_bps: u64) -> u64 {
+        saturate(
+            volume.current as u128
+                + volume.previous as u128 * previous_weight_bps as u128 / 10000,
+        )
+    }
+
+    // Credits earnings like a deposit, to total and available of one asset,
+    // so they can be withdrawn through the verified withdraw path
+    fn accrue(balances: Balances, is_base: u8, amount: u64) -> (Balances, bool) {
+        let mut credited = balances;
+        let (base_total, base_total_overflow) = checked_add(balances.base_total, amount);
+        let (base_available, base_overflow) = checked_add(balances.base_available, amount);
+        let (quote_total, quote_total_overflow) = checked_add(balances.quote_total, amount);
+        let (quote_available, quote_overflow) = checked_add(balances.quote_available, amount);
+        let overflow = if is_base == 0 {
+            credited.base_total = base_total;
+            credited.base_available = base_available;
+            base_total_overflow || base_overflow
+        } else {
+            credited.quote_total = quote_total;
+            credited.quote_available = quote_available;
+            quote_total_overflow || quote_overflow
+        };
+        (credited, overflow)
+    }
+
+    // Highest tier whose threshold the volume reaches, 0 thresholds are unused
+    pub fn tier_fee_bps(
+        volume: u64,
+        base_fee_bps: u64,
+        thresholds: [u64; FEE_TIERS],
+        tier_fees_bps: [u64; FEE_TIERS],
+    ) -> u64 {
+        let mut fee_bps = base_fee_bps;
+        for i in 0..FEE_TIERS {
+            if thresholds[i] > 0 && volume >= thresholds[i] {
+                fee_bps = tier_fees_bps[i];
             }
         }
+        fee_bps
+    }
+
+    // Fee on a settled amount, rounded up so dust fills still pay
+    pub fn settlement_fee(amount: u64, fee_bps: u64) -> u64 {
+        saturate((amount as u128 * fee_bps as u128 + 9999) / 10000)
+    }
+
+    // Referrer's cut of a fee, rounding dust stays with the protocol
+    pub fn referral_cut(fee: u64, referral_share_bps: u64) -> u64 {
+        saturate(fee as u128 * referral_share_bps as u128 / 10000)
+    }
 
-        (ledger.owner.from_arcis(balances), possible.reveal())
+    // Hands the part of a payment the order's lock still covers back to the
+    // asset the lock was taken from, so the payer pays for a fill once and
+    // the lock only keeps what is still held for the rest of the order
+    pub fn take_fill_from_lock(
+        balances: Balances,
+        lock: OrderLock,
+        order_type: u8,
+        paid: u64,
+    ) -> (Balances, OrderLock, bool) {
+        let mut ledger = balances;
+        let mut rest = lock;
+        let covered = if paid < lock.locked { paid } else { lock.locked };
+        let (quote_available, quote_overflow) = checked_add(ledger.quote_available, covered);
+        let (base_available, base_overflow) = checked_add(ledger.base_available, covered);
+        // Mirror the lock taken in submit_order_check
+        let overflow = if order_type == 1 {
+            ledger.quote_available = quote_available;
+            quote_overflow
+        } else {
+            ledger.base_available = base_available;
+            base_overflow
+        };
+        rest.locked -= covered;
+        (ledger, rest, overflow)
     }
 
     #[instruction]
     pub fn execute_settlement(
         user1_ledger: Enc<Shared, &Balances>,
         user2_ledger: Enc<Shared, &Balances>,
+        fee_ledger: Enc<Shared, &Balances>, // protocol fee ledger of the market authority
+        referrer_ledger: Enc<Shared, &Balances>, // receiver's referrer, the fee ledger again when it has none
+        user1_volume: Enc<Shared, &TradingVolume>,
+        user2_volume: Enc<Shared, &TradingVolume>,
+        payer_lock: Enc<Mxe, &OrderLock>, // lock of the order user1 pays for
         execution_price: u64,
         is_base: u8,
+        payer_order_type: u8,
+        fee_bps: u64, // maker or taker rate of the receiving side
+        referral_share_bps: u64, // share of the fee paid to the referrer, 0 without one
+        tier1_threshold: u64,
+        tier2_threshold: u64,
+        tier3_threshold: u64,
+        tier1_fee_bps: u64,
+        tier2_fee_bps: u64,
+        tier3_fee_bps: u64,
+        user1_windows_elapsed: u8,
+        user2_windows_elapsed: u8,
+        previous_weight_bps: u64, // share of the previous window still in the trailing 30 days
+        volume_window: u64,
     ) -> (
         Enc<Shared, Balances>,
         Enc<Shared, Balances>,
+        Enc<Shared, Balances>,
+        Enc<Shared, Balances>,
+        Enc<Shared, TradingVolume>,
+        Enc<Shared, TradingVolume>,
+        Enc<Mxe, OrderLock>,
+        bool,
+        u64,
     ) {
-        let mut user1_balances = *(user1_ledger.to_arcis());
-        let mut user2_balances = *(user2_ledger.to_arcis());
-
-        if is_base == 0 {
-            user1_balances.base_available -= execution_price;
-            user2_balances.base_available += execution_price;
+        let user1_original = *(user1_ledger.to_arcis());
+        let user2_original = *(user2_ledger.to_arcis());
+        let fee_original = *(fee_ledger.to_arcis());
+        let referrer_original = *(referrer_ledger.to_arcis());
+        let (mut user1_balances, lock, lock_overflow) = take_fill_from_lock(
+            user1_original,
+            *(payer_lock.to_arcis()),
+            payer_order_type,
+            execution_price,
+        );
+        let mut user2_balances = user2_original;
+        let mut user1_traded = roll_volume(*(user1_volume.to_arcis()), user1_windows_elapsed);
+        let mut user2_traded = roll_volume(*(user2_volume.to_arcis()), user2_windows_elapsed);
+
+        // The receiver's tier is picked here so neither its volume nor the
+        // rate it pays leaves MPC
+        let receiver_fee_bps = tier_fee_bps(
+            rolling_volume(user2_traded, previous_weight_bps),
+            fee_bps,
+            [tier1_threshold, tier2_threshold, tier3_threshold],
+            [tier1_fee_bps, tier2_fee_bps, tier3_fee_bps],
+        );
+
+        // The receiver pays the fee out of what it receives
+        let fee = settlement_fee(execution_price, receiver_fee_bps);
+        let (received, fee_too_large) = checked_sub(execution_price, fee);
+        let referral_fee = referral_cut(fee, referral_share_bps);
+
+        let (user1_base, user1_base_underflow) =
+            checked_sub(user1_balances.base_available, execution_price);
+        let (user2_base, user2_base_overflow) =
+            checked_add(user2_balances.base_available, received);
+        let (user1_quote, user1_quote_underflow) =
+            checked_sub(user1_balances.quote_available, execution_price);
+        let (user2_quote, user2_quote_overflow) =
+            checked_add(user2_balances.quote_available, received);
+        let (mut fee_balances, fee_overflow) = accrue(fee_original, is_base, fee - referral_fee);
+        let (mut referrer_balances, referrer_overflow) =
+            accrue(referrer_original, is_base, referral_fee);
+
+        let moved_failed = if is_base == 0 {
+            user1_balances.base_available = user1_base;
+            user2_balances.base_available = user2_base;
+            user1_base_underflow || user2_base_overflow
         } else {
-            user1_balances.quote_available -= execution_price;
-            user2_balances.quote_available += execution_price;
+            user1_balances.quote_available = user1_quote;
+            user2_balances.quote_available = user2_quote;
+            user1_quote_underflow || user2_quote_overflow
+        };
+        let failed =
+            moved_failed || fee_too_large || fee_overflow || referrer_overflow || lock_overflow;
+        // Nothing moves unless every ledger can take it
+        let lock = if failed { *(payer_lock.to_arcis()) } else { lock };
+        if failed {
+            user1_balances = user1_original;
+            user2_balances = user2_original;
+            fee_balances = fee_original;
+            referrer_balances = referrer_original;
         }
 
+        // Volume is counted in quote, once per match on its quote leg
+        let traded = if is_base == 0 || failed { 0 } else { execution_price };
+        user1_traded.current = saturate(user1_traded.current as u128 + traded as u128);
+        user2_traded.current = saturate(user2_traded.current as u128 + traded as u128);
+
         (
             user1_ledger.owner.from_arcis(user1_balances), 
-            user2_ledger.owner.from_arcis(user2_balances)
+            user2_ledger.owner.from_arcis(user2_balances),
+            fee_ledger.owner.from_arcis(fee_balances),
+            referrer_ledger.owner.from_arcis(referrer_balances),
+            user1_volume.owner.from_arcis(user1_traded),
+            user2_volume.owner.from_arcis(user2_traded),
+            payer_lock.owner.from_arcis(lock),
+            failed.reveal(),
+            volume_window,
+        )
+    }
+
+    #[instruction]
+    pub fn init_trigger_book(mxe: Mxe) -> Enc<Mxe, TriggerBookFlat> {
+        mxe.from_arcis(TriggerBookFlat::from_trigger_book(TriggerBook::new()))
+    }
+
+    pub struct StopOrderData {
+        pub amount: u64,
+        pub price: u64,         // limit price of a stop-limit, worst acceptable price of a stop (0 = none)
+        pub quote_budget: u64,  // max quote a stop buy may spend once it fires
+        pub min_fill_qty: u64,
+        pub trigger_price: u64,
+    }
+
+    // Parks a stop order in the trigger book. The lock was already taken by
+    // submit_order_check and the stop is built from its record, the trigger
+    // price never leaves MPC. A stop the full trigger book turns away gets its
+    // lock back.
+    #[instruction]
+    pub fn submit_stop_order(
+        user_sensitive: Enc<Shared, StopOrderData>,
+        trigger_book_ctx: Enc<Mxe, &TriggerBookFlat>,
+        user_ledger: Enc<Shared, &Balances>,
+        order_lock: Enc<Mxe, &OrderLock>,
+        order_id: u64,
+        order_type: u8,
+        order_kind: u8,
+        ledger_owner: Enc<Mxe, &u64>, // MXE, sealed by init_user_ledger
+        group_id: u64,
+        group_role: u8,
+    ) -> (Enc<Mxe, TriggerBookFlat>, Enc<Shared, Balances>, Enc<Mxe, OrderLock>, bool, bool) {
+        let owner_tag = *(ledger_owner.to_arcis());
+        let sensitive = user_sensitive.to_arcis();
+        let mut trigger_book = (*(trigger_book_ctx.to_arcis())).to_trigger_book();
+        let original = *(user_ledger.to_arcis());
+        let mut ledger = original;
+        let mut lock = *(order_lock.to_arcis());
+
+        // A stop becomes a market order when it fires, a stop-limit a limit order
+        let is_stop = order_kind == ORDER_KIND_STOP;
+        let order = Order {
+            order_id,
+            amount: lock.amount,
+            price: lock.price,
+            order_type,
+            timestamp: 0,
+            order_kind: if is_stop { ORDER_KIND_MARKET } else { ORDER_KIND_LIMIT },
+            quote_budget: if is_stop && order_type == 0 { lock.quote_budget } else { 0 },
+            min_fill_qty: sensitive.min_fill_qty,
+            owner_tag,
+            display_size: 0,
+            reserve: 0,
+            group_id,
+            group_role,
+            post_only: false,
+        };
+
+        let success = trigger_book.insert(order, sensitive.trigger_price);
+
+        // Mirror the lock taken in submit_order_check
+        let refund = if success { 0 } else { lock.locked };
+        let (quote_available, quote_overflow) = checked_add(ledger.quote_available, refund);
+        let (base_available, base_overflow) = checked_add(ledger.base_available, refund);
+        let overflow = if order_type == 1 {
+            ledger.quote_available = quote_available;
+            quote_overflow
+        } else {
+            ledger.base_available = base_available;
+            base_overflow
+        };
+        if !overflow {
+            lock.locked -= refund;
+        }
+
+        (
+            trigger_book_ctx.owner.from_arcis(TriggerBookFlat::from_trigger_book(trigger_book)),
+            user_ledger.owner.from_arcis(if overflow { original } else { ledger }),
+            order_lock.owner.from_arcis(lock),
+            success.reveal(),
+            overflow.reveal(),
+        )
+    }
+
+    // Moves every stop whose trigger the reference price has reached into the
+    // live book, stamped with the evaluation time. A triggered stop that finds
+    // its side of the book full stays armed for the next run.
+    // Grouped stops follow their group: a bracket exit waits until its entry
+    // has filled (one without a trigger price then rests as a plain limit
+    // leg), the siblings of a finished group are dropped, and a one-cancels-
+    // other stop that fires cancels its siblings resting in the live book.
+    pub fn trigger_pass(
+        trigger_book: TriggerBook,
+        live_book: OrderBook,
+        reference_price: u64,
+        timestamp: u64,
+    ) -> (TriggerBook, OrderBook, u8) {
+        let mut order_book = live_book;
+
+        let mut armed = TriggerBook::new();
+        let mut triggered_count = 0u8;
+        for i in 0..MAX_STOP_ORDERS {
+            let stop = trigger_book.orders[i];
+            let listed = i < trigger_book.count as usize;
+            let exit = stop.group_role == GROUP_ROLE_EXIT;
+            let exit_armed = exit && order_book.group_armed(stop.group_id);
+            let sibling = stop.is_sibling() || exit_armed;
+            let dropped = listed && sibling && order_book.group_done(stop.group_id);
+            let resting_exit = listed && exit_armed && trigger_book.trigger_prices[i] == 0 && !dropped;
+
+            let triggered = trigger_book.is_triggered(i, reference_price)
+                && !dropped
+                && (!exit || exit_armed)
+                && !resting_exit;
+
+            // A resting exit stays tied to its siblings until one of them wins,
+            // a stop that fires has won its group
+            let mut order = stop;
+            order.timestamp = timestamp;
+            order.group_role = if resting_exit {
+                GROUP_ROLE_OCO
+            } else if stop.group_role == GROUP_ROLE_ENTRY {
+                GROUP_ROLE_ENTRY
+            } else {
+                GROUP_ROLE_NONE
+            };
+
+            let mut moved = false;
+            if triggered || resting_exit {
+                moved = if order.is_buy() {
+                    order_book.insert_buy(order)
+                } else {
+                    order_book.insert_sell(order)
+                };
+            }
+            if moved && triggered && sibling {
+                order_book.mark_group_done(stop.group_id);
+            }
+
+            if moved {
+                triggered_count += 1;
+            } else if listed && !dropped {
+                let mut waiting = stop;
+                if exit_armed {
+                    waiting.group_role = GROUP_ROLE_OCO;
+                }
+                armed.insert(waiting, trigger_book.trigger_prices[i]);
+            }
+        }
+
+        order_book.cancel_done_groups();
+
+        (armed, order_book, triggered_count)
+    }
+
+    // Only the number of orders moved is revealed
+    #[instruction]
+    pub fn evaluate_triggers(
+        trigger_book_ctxt: Enc<Mxe, TriggerBookFlat>,
+        order_book_ctxt: Enc<Mxe, OrderBookFlat>,
+        reference_price: u64,
+        timestamp: u64,
+    ) -> (Enc<Mxe, TriggerBookFlat>, Enc<Mxe, OrderBookFlat>, u8) {
+        let (armed, order_book, triggered_count) = trigger_pass(
+            trigger_book_ctxt.to_arcis().to_trigger_book(),
+            OrderBookFlat::to_orderbook(order_book_ctxt.to_arcis()),
+            reference_price,
+            timestamp,
+        );
+
+        (
+            trigger_book_ctxt.owner.from_arcis(TriggerBookFlat::from_trigger_book(armed)),
+            order_book_ctxt.owner.from_arcis(OrderBookFlat::from_orderbook(order_book)),
+            triggered_count.reveal(),
+        )
+    }
+
+    pub struct TwapOrderData {
+        pub slice_size: u64, // quantity released per interval, 0 = everything at once
+    }
+
+    #[derive(Copy, Clone)]
+    pub struct TwapParent {
+        pub remaining: u64, // quantity not yet released into the book
+        pub price: u64,
+        pub slice_size: u64,
+        pub locked: u64,    // collateral for the remaining quantity
+    }
+
+    // Encrypts a TWAP parent for the MXE from the lock submit_order_check
+    // recorded for it, only the slice size comes from the user. The parent
+    // takes the lock over and hands each slice its share, the order account
+    // keeps none.
+    #[instruction]
+    pub fn submit_twap_order(
+        user_sensitive: Enc<Shared, TwapOrderData>,
+        order_lock: Enc<Mxe, &OrderLock>,
+        mxe: Mxe,
+    ) -> (Enc<Mxe, TwapParent>, Enc<Mxe, OrderLock>) {
+        let sensitive = user_sensitive.to_arcis();
+        let mut lock = *(order_lock.to_arcis());
+        let parent = TwapParent {
+            remaining: lock.amount,
+            price: lock.price,
+            slice_size: sensitive.slice_size,
+            locked: lock.locked,
+        };
+        lock.locked = 0;
+        (mxe.from_arcis(parent), order_lock.owner.from_arcis(lock))
+    }
+
+    // Releases the next slice of a TWAP parent into the live book as a limit
+    // order under its own order id, with a lock record of its own. A slice
+    // that finds its side of the book full is retried on the next interval.
+    // Once the parent has expired nothing more is released and the lock of
+    // whatever is left goes back to the ledger. Returns whether a slice went
+    // out, whether the parent is done and whether the expiry refund
+    // overflowed the ledger.
+    pub fn twap_step(
+        twap_parent: TwapParent,
+        live_book: OrderBook,
+        balances: Balances,
+        order_id: u64,
+        order_type: u8,
+        owner_tag: u64,
+        expired: u8,
+        price_scale: u64,
+        timestamp: u64,
+    ) -> (TwapParent, OrderBook, Balances, OrderLock, bool, bool, bool) {
+        let mut parent = twap_parent;
+        let mut order_book = live_book;
+        let mut ledger = balances;
+
+        let is_expired = expired == 1;
+        let slice = if parent.slice_size == 0 || parent.slice_size > parent.remaining {
+            parent.remaining
+        } else {
+            parent.slice_size
+        };
+
+        let child = Order {
+            order_id,
+            amount: slice,
+            price: parent.price,
+            order_type,
+            timestamp,
+            order_kind: ORDER_KIND_LIMIT,
+            quote_budget: 0,
+            min_fill_qty: 0,
+            owner_tag,
+            display_size: 0,
+            reserve: 0,
+            group_id: 0,
+            group_role: GROUP_ROLE_NONE,
+            post_only: false,
+        };
+
+        let released = if !is_expired && slice > 0 {
+            if order_type == 0 {
+                order_book.insert_buy(child)
+            } else {
+                order_book.insert_sell(child)
+            }
+        } else {
+            false
+        };
+        if released {
+            parent.remaining -= slice;
+        }
+
+        // A released slice takes its share of the parent's lock along to its
+        // own lock, the last one takes whatever rounding left over
+        let slice_lock = if order_type == 0 {
+            quote_owed(slice, parent.price, price_scale)
+        } else {
+            slice
+        };
+        let share = if parent.remaining == 0 || slice_lock > parent.locked {
+            parent.locked
+        } else {
+            slice_lock
+        };
+        if released {
+            parent.locked -= share;
+        }
+        let lock = OrderLock {
+            amount: if released { slice } else { 0 },
+            price: parent.price,
+            quote_budget: 0,
+            locked: if released { share } else { 0 },
+        };
+
+        // What is left of the recorded lock goes back once the parent expires.
+        // A refund the ledger can't take leaves the parent open to retry.
+        let refund = if is_expired { parent.locked } else { 0 };
+        // Mirror the lock taken in submit_order_check
+        let (quote_available, quote_overflow) = checked_add(ledger.quote_available, refund);
+        let (base_available, base_overflow) = checked_add(ledger.base_available, refund);
+        let overflow = if order_type == 1 {
+            ledger.quote_available = quote_available;
+            quote_overflow
+        } else {
+            ledger.base_available = base_available;
+            base_overflow
+        };
+        if is_expired && !overflow {
+            parent.remaining = 0;
+            parent.locked = 0;
+        }
+
+        let done = parent.remaining == 0;
+
+        (parent, order_book, ledger, lock, released, done, overflow)
+    }
+
+    // Whether a slice went out and whether the parent is done are revealed,
+    // the sizes are not
+    #[instruction]
+    pub fn release_twap_slice(
+        twap_ctxt: Enc<Mxe, TwapParent>,
+        order_book_ctxt: Enc<Mxe, OrderBookFlat>,
+        user_ledger: Enc<Shared, &Balances>,
+        order_id: u64,
+        order_type: u8,
+        ledger_owner: Enc<Mxe, &u64>, // MXE, sealed by init_user_ledger
+        expired: u8,
+        price_scale: u64,
+        timestamp: u64,
+        mxe: Mxe,
+    ) -> (
+        Enc<Mxe, TwapParent>,
+        Enc<Mxe, OrderBookFlat>,
+        Enc<Shared, Balances>,
+        Enc<Mxe, OrderLock>,
+        bool,
+        bool,
+        bool, // Expiry refund overflowed the ledger
+    ) {
+        let (parent, order_book, ledger, lock, released, done, overflow) = twap_step(
+            twap_ctxt.to_arcis(),
+            OrderBookFlat::to_orderbook(order_book_ctxt.to_arcis()),
+            *(user_ledger.to_arcis()),
+            order_id,
+            order_type,
+            *(ledger_owner.to_arcis()),
+            expired,
+            price_scale,
+            timestamp,
+        );
+
+        (
+            twap_ctxt.owner.from_arcis(parent),
+            order_book_ctxt.owner.from_arcis(OrderBookFlat::from_orderbook(order_book)),
+            user_ledger.owner.from_arcis(ledger),
+            mxe.from_arcis(lock),
+            released.reveal(),
+            done.reveal(),
+            overflow.reveal(),
+        )
+    }
+
+    pub struct RecurringOrderData {
+        pub quote_amount: u64, // quote spent each period
+        pub max_price: u64,    // worst price a child buys at, 0 = none
+    }
+
+    #[derive(Copy, Clone)]
+    pub struct RecurringOrder {
+        pub quote_amount: u64,
+        pub max_price: u64,
+    }
+
+    // Encrypts a recurring buy schedule for the MXE. Nothing is locked up
+    // front, every period's child is funded on its own when it is placed.
+    #[instruction]
+    pub fn create_recurring_order(
+        user_sensitive: Enc<Shared, RecurringOrderData>,
+        mxe: Mxe,
+    ) -> Enc<Mxe, RecurringOrder> {
+        let sensitive = user_sensitive.to_arcis();
+        let schedule = RecurringOrder {
+            quote_amount: sensitive.quote_amount,
+            max_price: sensitive.max_price,
+        };
+        mxe.from_arcis(schedule)
+    }
+
+    // Places one period of a recurring buy as a market buy spending the
+    // period's quote amount, under the child's own order id. The child is funded the way submit_order_check
+    // funds a market buy; when the ledger can't cover it nothing is placed and
+    // the schedule stops. A funded child that finds the bid side full locks
+    // nothing and is retried. Returns whether it was funded and placed.
+    pub fn recurring_child(
+        schedule: RecurringOrder,
+        live_book: OrderBook,
+        balances: Balances,
+        order_id: u64,
+        owner_tag: u64,
+        price_scale: u64,
+        timestamp: u64,
+    ) -> (OrderBook, Balances, OrderLock, bool, bool) {
+        let mut order_book = live_book;
+        let mut ledger = balances;
+
+        // Same balance check and lock submit_order_check applies to a market buy
+        let required = schedule.quote_amount;
+        let funded = required > 0 && ledger.base_available >= required;
+
+        // The quote budget is what limits the child, its amount only has to be
+        // large enough never to bind at a price of one tick
+        let child = Order {
+            order_id,
+            amount: base_for_quote(required, 1, price_scale),
+            price: schedule.max_price,
+            order_type: 0,
+            timestamp,
+            order_kind: ORDER_KIND_MARKET,
+            quote_budget: required,
+            min_fill_qty: 0,
+            owner_tag,
+            display_size: 0,
+            reserve: 0,
+            group_id: 0,
+            group_role: GROUP_ROLE_NONE,
+            post_only: false,
+        };
+
+        let placed = if funded {
+            order_book.insert_buy(child)
+        } else {
+            false
+        };
+        // Locked only once the child rests, so a full bid side has nothing to
+        // hand back
+        if placed {
+            ledger.base_available -= required;
+        }
+
+        // Recorded on the child's order account like submit_order_check does
+        let lock = OrderLock {
+            amount: child.amount,
+            price: child.price,
+            quote_budget: required,
+            locked: if placed { required } else { 0 },
+        };
+
+        (order_book, ledger, lock, funded, placed)
+    }
+
+    // Only whether the child was funded and placed is revealed
+    #[instruction]
+    pub fn execute_recurring_order(
+        schedule_ctxt: Enc<Mxe, RecurringOrder>,
+        order_book_ctxt: Enc<Mxe, OrderBookFlat>,
+        user_ledger: Enc<Shared, &Balances>,
+        order_id: u64,
+        ledger_owner: Enc<Mxe, &u64>, // MXE, sealed by init_user_ledger
+        price_scale: u64,
+        timestamp: u64,
+        mxe: Mxe,
+    ) -> (Enc<Mxe, OrderBookFlat>, Enc<Shared, Balances>, Enc<Mxe, OrderLock>, bool, bool) {
+        let (order_book, ledger, lock, funded, placed) = recurring_child(
+            schedule_ctxt.to_arcis(),
+            OrderBookFlat::to_orderbook(order_book_ctxt.to_arcis()),
+            *(user_ledger.to_arcis()),
+            order_id,
+            *(ledger_owner.to_arcis()),
+            price_scale,
+            timestamp,
+        );
+
+        (
+            order_book_ctxt.owner.from_arcis(OrderBookFlat::from_orderbook(order_book)),
+            user_ledger.owner.from_arcis(ledger),
+            mxe.from_arcis(lock),
+            funded.reveal(),
+            placed.reveal(),
         )
     }
 }
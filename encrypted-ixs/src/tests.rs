@@ -0,0 +1,699 @@
+// Plain runs of the circuit helpers, no MPC involved. Prices are in
+// hundredths of a quote token like the test markets.
+use crate::circuits::*;
+use matching_engine::utils as program;
+
+const SCALE: u64 = 100;
+const BATCH_TIME: u64 = 10;
+
+#[derive(Clone, Copy)]
+struct Rules {
+    reference_price: u64,
+    pricing_rule: u8,
+    stp_policy: u8,
+    allocation_mode: u8,
+    top_priority_pct: u8,
+    band_reference: u64,
+    band_bps: u64,
+}
+
+const MIDPOINT: Rules = Rules {
+    reference_price: 0,
+    pricing_rule: PRICING_RULE_MIDPOINT,
+    stp_policy: STP_NONE,
+    allocation_mode: ALLOCATION_PRICE_TIME,
+    top_priority_pct: 0,
+    band_reference: 0,
+    band_bps: 0,
+};
+
+fn limit(order_id: u64, order_type: u8, amount: u64, price: u64, timestamp: u64) -> Order {
+    Order {
+        order_id,
+        amount,
+        price,
+        order_type,
+        timestamp,
+        owner_tag: order_id,
+        ..Order::empty()
+    }
+}
+
+// Split the way submit_order splits an iceberg
+fn iceberg(order_id: u64, order_type: u8, amount: u64, price: u64, timestamp: u64, display_size: u64) -> Order {
+    Order {
+        display_size,
+        reserve: amount - display_size,
+        ..limit(order_id, order_type, display_size, price, timestamp)
+    }
+}
+
+fn grouped(order: Order, group_id: u64, group_role: u8) -> Order {
+    Order { group_id, group_role, ..order }
+}
+
+fn owned_by(order: Order, owner_tag: u64) -> Order {
+    Order { owner_tag, ..order }
+}
+
+fn book(buys: &[Order], sells: &[Order]) -> OrderBook {
+    let mut book = OrderBook::new();
+    for order in buys {
+        assert!(book.insert_buy(*order));
+    }
+    for order in sells {
+        assert!(book.insert_sell(*order));
+    }
+    book
+}
+
+fn full_bid_side() -> OrderBook {
+    let bids: Vec<Order> = (1..=MAX_ORDERS as u64).map(|i| limit(100 + i, 0, 100, 9000, i)).collect();
+    book(&bids, &[])
+}
+
+fn run(buys: &[Order], sells: &[Order], rules: Rules) -> (OrderBook, MatchResult, u8) {
+    let mut order_book = book(buys, sells);
+    order_book.set_reference_price(rules.reference_price);
+    // Uniform price markets find their price the way match_orders_uniform does
+    let clearing_price = if rules.pricing_rule == PRICING_RULE_UNIFORM {
+        uniform_clearing_price(
+            order_book,
+            rules.stp_policy,
+            rules.allocation_mode,
+            rules.top_priority_pct,
+            rules.band_reference,
+            rules.band_bps,
+            SCALE,
+            BATCH_TIME,
+        )
+    } else {
+        0
+    };
+    let (order_book, result, _, skipped, _) = match_book(
+        order_book,
+        rules.pricing_rule,
+        clearing_price,
+        rules.stp_policy,
+        rules.allocation_mode,
+        rules.top_priority_pct,
+        rules.band_reference,
+        rules.band_bps,
+        SCALE,
+        BATCH_TIME,
+    );
+    (order_book, result, skipped)
+}
+
+// (buyer, seller, quantity, price) of each match in order
+fn fills(result: &MatchResult) -> Vec<(u64, u64, u64, u64)> {
+    result.matches[..result.num_matches as usize]
+        .iter()
+        .map(|m| (m.buyer_order_id, m.seller_order_id, m.quantity, m.execution_price))
+        .collect()
+}
+
+// (order, side, amount) of each release, sorted by order id
+fn releases(result: &MatchResult) -> Vec<(u64, u8, u64)> {
+    let mut releases: Vec<(u64, u8, u64)> = result.releases[..result.num_releases as usize]
+        .iter()
+        .map(|r| (r.order_id, r.order_type, r.amount))
+        .collect();
+    releases.sort();
+    releases
+}
+
+fn prices(result: &MatchResult) -> Vec<u64> {
+    fills(result).iter().map(|f| f.3).collect()
+}
+
+// Two crossing pairs: 105 bid (t=1) vs 95 ask (t=2), 102 bid (t=3) vs 100 ask (t=4)
+fn two_pairs(pricing_rule: u8, reference_price: u64) -> Vec<u64> {
+    let buys = [limit(1, 0, 1000, 10500, 1), limit(3, 0, 1000, 10200, 3)];
+    let sells = [limit(2, 1, 1000, 9500, 2), limit(4, 1, 1000, 10000, 4)];
+    let rules = Rules { pricing_rule, reference_price, ..MIDPOINT };
+    prices(&run(&buys, &sells, rules).1)
+}
+
+#[test]
+fn pricing_rules() {
+    // Midpoint trades each pair at the average of its limits
+    assert_eq!(two_pairs(PRICING_RULE_MIDPOINT, 0), vec![10000, 10100]);
+    // Maker trades at the earlier order's limit
+    assert_eq!(two_pairs(PRICING_RULE_MAKER, 0), vec![10500, 10200]);
+    assert_eq!(
+        pair_execution_price(&limit(5, 0, 1000, 10500, 9), &limit(6, 1, 1000, 9500, 1), 0, PRICING_RULE_MAKER, 0),
+        9500
+    );
+    // Reference is clamped into each spread, without one it is the midpoint
+    assert_eq!(two_pairs(PRICING_RULE_REFERENCE, 10050), vec![10050, 10050]);
+    assert_eq!(two_pairs(PRICING_RULE_REFERENCE, 11000), vec![10500, 10200]);
+    assert_eq!(two_pairs(PRICING_RULE_REFERENCE, 0), vec![10000, 10100]);
+    // Uniform trades every pair at the marginal pair's midpoint
+    assert_eq!(two_pairs(PRICING_RULE_UNIFORM, 0), vec![10100, 10100]);
+}
+
+#[test]
+fn peg_orders_follow_the_reference() {
+    let peg_buy = Order {
+        order_kind: ORDER_KIND_PEG,
+        quote_budget: 200_000,
+        ..limit(7, 0, 1000, 0, 1)
+    };
+    let rules = Rules { reference_price: 10000, ..MIDPOINT };
+    let (_, result, _) = run(&[peg_buy], &[limit(8, 1, 1000, 9500, 2)], rules);
+    assert_eq!(fills(&result), vec![(7, 8, 1000, 10000)]);
+}
+
+#[test]
+fn minimum_fill_quantity() {
+    // A 500 lot block bid passes over the 100 lot ask for the 400 lot
+    // one, then its 100 lot remainder may take the small ask
+    let block = Order { min_fill_qty: 300, ..limit(1, 0, 500, 10500, 1) };
+    let small_ask = limit(2, 1, 100, 9500, 2);
+    let big_ask = limit(3, 1, 400, 10000, 3);
+    let (_, result, _) = run(&[block], &[small_ask, big_ask], MIDPOINT);
+    let matched: Vec<(u64, u64)> = fills(&result).iter().map(|f| (f.1, f.2)).collect();
+    assert_eq!(matched, vec![(3, 400), (2, 100)]);
+
+    // The skipped ask still trades with another compatible bid
+    let small_bid = limit(4, 0, 100, 9800, 4);
+    let (_, result, _) = run(&[block, small_bid], &[small_ask], MIDPOINT);
+    let buyers: Vec<u64> = fills(&result).iter().map(|f| f.0).collect();
+    assert_eq!(buyers, vec![4]);
+
+    // A remainder smaller than the minimum fills in one go
+    let block = Order { min_fill_qty: 300, ..limit(1, 0, 200, 10500, 1) };
+    let (_, result, _) = run(&[block], &[limit(2, 1, 200, 9500, 2)], MIDPOINT);
+    assert_eq!(result.num_matches, 1);
+}
+
+// Owner 7 rests a 100 lot ask at t=1, then sends a crossing 60 lot bid
+// at t=5. Another owner's 100 lot ask sits behind it.
+fn self_trade(stp_policy: u8) -> MatchResult {
+    let own_bid = owned_by(limit(1, 0, 60, 10500, 5), 7);
+    let own_ask = owned_by(limit(2, 1, 100, 9500, 1), 7);
+    let other_ask = limit(3, 1, 100, 10000, 2);
+    run(&[own_bid], &[own_ask, other_ask], Rules { stp_policy, ..MIDPOINT }).1
+}
+
+#[test]
+fn self_trade_prevention() {
+    // Without a policy the owner trades with itself
+    assert_eq!(fills(&self_trade(STP_NONE))[0].1, 2);
+
+    let result = self_trade(STP_CANCEL_NEWEST);
+    assert_eq!(result.num_matches, 0);
+    assert_eq!(releases(&result), vec![(1, 0, 6300)]);
+
+    let result = self_trade(STP_CANCEL_OLDEST);
+    let sellers: Vec<u64> = fills(&result).iter().map(|f| f.1).collect();
+    assert_eq!(sellers, vec![3]);
+    assert_eq!(releases(&result), vec![(2, 1, 100)]);
+
+    let result = self_trade(STP_CANCEL_BOTH);
+    assert_eq!(result.num_matches, 0);
+    assert_eq!(releases(&result), vec![(1, 0, 6300), (2, 1, 100)]);
+
+    // Decrement and cancel shrinks both by the smaller size
+    let result = self_trade(STP_DECREMENT_AND_CANCEL);
+    assert_eq!(result.num_matches, 0);
+    assert_eq!(releases(&result), vec![(1, 0, 6300), (2, 1, 60)]);
+}
+
+// A 10 lot bid rests ahead of a 990 lot bid at the same price, then a
+// 500 lot ask arrives
+fn level_fills(allocation_mode: u8, top_priority_pct: u8) -> Vec<(u64, u64)> {
+    let bids = [limit(1, 0, 10, 10000, 1), limit(2, 0, 990, 10000, 2)];
+    let rules = Rules { allocation_mode, top_priority_pct, ..MIDPOINT };
+    let (_, result, _) = run(&bids, &[limit(3, 1, 500, 10000, 3)], rules);
+    let mut fills: Vec<(u64, u64)> = fills(&result).iter().map(|f| (f.0, f.2)).collect();
+    fills.sort();
+    fills
+}
+
+#[test]
+fn pro_rata_allocation() {
+    assert_eq!(level_fills(ALLOCATION_PRICE_TIME, 0), vec![(1, 10), (2, 490)]);
+    assert_eq!(level_fills(ALLOCATION_PRO_RATA, 0), vec![(1, 5), (2, 495)]);
+    // The top of book allotment goes to the earliest order first
+    assert_eq!(level_fills(ALLOCATION_PRO_RATA, 20), vec![(1, 10), (2, 490)]);
+}
+
+#[test]
+fn allocate_level_shares() {
+    // Rounding dust goes out in time priority
+    assert_eq!(
+        allocate_level(
+            [100, 100, 100, 0],
+            [true, true, true, false],
+            [false; MAX_ORDERS],
+            [3, 1, 2, 0],
+            100,
+            ALLOCATION_PRO_RATA,
+            0,
+        ),
+        [33, 34, 33, 0]
+    );
+    // The earliest order keeps its pro rata share of what is left after its allotment
+    assert_eq!(
+        allocate_level(
+            [100, 300, 0, 0],
+            [true, true, false, false],
+            [false; MAX_ORDERS],
+            [1, 2, 0, 0],
+            200,
+            ALLOCATION_PRO_RATA,
+            20,
+        ),
+        [67, 133, 0, 0]
+    );
+    // Orders priced through the level fill in full first
+    assert_eq!(
+        allocate_level(
+            [100, 100, 0, 0],
+            [true, true, false, false],
+            [false, true, false, false],
+            [1, 2, 0, 0],
+            150,
+            ALLOCATION_PRICE_TIME,
+            0,
+        ),
+        [50, 100, 0, 0]
+    );
+}
+
+#[test]
+fn icebergs_refill_at_the_back_of_the_queue() {
+    // A 300 lot iceberg ask showing 100 rests ahead of a plain 200 lot ask
+    let ice = iceberg(1, 1, 300, 10000, 1, 100);
+    let plain_ask = limit(2, 1, 200, 10000, 5);
+    let (_, result, _) = run(&[limit(3, 0, 250, 10000, 6)], &[ice, plain_ask], MIDPOINT);
+    let matched: Vec<(u64, u64)> = fills(&result).iter().map(|f| (f.1, f.2)).collect();
+    assert_eq!(matched, vec![(1, 100), (2, 150)]);
+
+    // A cancelled iceberg releases its hidden reserve too
+    let own_bid = owned_by(limit(4, 0, 50, 10000, 6), 7);
+    let rules = Rules { stp_policy: STP_CANCEL_OLDEST, ..MIDPOINT };
+    let (_, result, _) = run(&[own_bid], &[owned_by(ice, 7)], rules);
+    assert_eq!(releases(&result), vec![(1, 1, 300)]);
+}
+
+#[test]
+fn price_band() {
+    assert!(within_band(10500, 10000, 500));
+    assert!(within_band(9500, 10000, 500));
+    assert!(!within_band(10501, 10000, 500));
+    assert!(within_band(20000, 0, 500));
+    assert!(within_band(20000, 10000, 0));
+
+    // The far ask is out of the band, the bid takes the near one and
+    // the remaining crossing is counted
+    let rules = Rules {
+        pricing_rule: PRICING_RULE_MAKER,
+        band_reference: 10000,
+        band_bps: 500,
+        ..MIDPOINT
+    };
+    let far_ask = limit(2, 1, 100, 11000, 2);
+    let near_ask = limit(3, 1, 50, 10200, 3);
+    let (_, result, skipped) = run(&[limit(1, 0, 100, 12000, 5)], &[far_ask, near_ask], rules);
+    assert_eq!(fills(&result), vec![(1, 3, 50, 10200)]);
+    assert_eq!(skipped, 1);
+}
+
+#[test]
+fn order_groups() {
+    // A one-cancels-other fill finishes the group, the sibling leg is dropped
+    let take_profit = grouped(limit(1, 1, 500, 11000, 1), 7, GROUP_ROLE_OCO);
+    let other_leg = grouped(limit(2, 1, 500, 12000, 2), 7, GROUP_ROLE_OCO);
+    let (left, result, _) = run(&[limit(3, 0, 1000, 12500, 3)], &[take_profit, other_leg], MIDPOINT);
+    let matched: Vec<(u64, u64)> = fills(&result).iter().map(|f| (f.1, f.2)).collect();
+    assert_eq!(matched, vec![(1, 500)]);
+    assert!(left.group_done(7));
+    assert_eq!(left.sell_count, 0);
+
+    // A bracket entry fill arms its exits
+    let entry = grouped(limit(1, 0, 500, 10000, 1), 9, GROUP_ROLE_ENTRY);
+    let (left, result, _) = run(&[entry], &[limit(2, 1, 500, 10000, 2)], MIDPOINT);
+    assert_eq!(result.num_matches, 1);
+    assert!(left.group_armed(9));
+    assert!(!left.group_done(9));
+}
+
+#[test]
+fn stop_triggers() {
+    let mut stops = TriggerBook::new();
+    stops.insert(limit(1, 0, 100, 0, 1), 10500);
+    stops.insert(limit(2, 1, 100, 0, 1), 9500);
+
+    // A buy stop fires once the reference reaches its trigger
+    assert!(!stops.is_triggered(0, 10499));
+    assert!(stops.is_triggered(0, 10500));
+    assert!(stops.is_triggered(0, 11000));
+    // A sell stop once the reference falls to it
+    assert!(!stops.is_triggered(1, 9501));
+    assert!(stops.is_triggered(1, 9500));
+    // Nothing fires without a reference price
+    assert!(!stops.is_triggered(1, 0));
+}
+
+fn bracket_exit(leg: Order, trigger_price: u64, armed: bool, done: bool) -> (TriggerBook, OrderBook, u8) {
+    let mut stops = TriggerBook::new();
+    stops.insert(grouped(leg, 9, GROUP_ROLE_EXIT), trigger_price);
+    let mut live = OrderBook::new();
+    if armed {
+        live.mark_group_armed(9);
+    }
+    if done {
+        live.mark_group_done(9);
+    }
+    trigger_pass(stops, live, 8900, BATCH_TIME)
+}
+
+#[test]
+fn bracket_exits_follow_their_entry() {
+    let stop_loss = limit(3, 1, 500, 0, 0);
+    let take_profit = limit(4, 1, 500, 11000, 0);
+
+    // Exits wait for the entry
+    let (armed, _, moved) = bracket_exit(stop_loss, 9000, false, false);
+    assert_eq!((armed.count, moved), (1, 0));
+
+    // then a stop loss fires and finishes the group
+    let (armed, live, moved) = bracket_exit(stop_loss, 9000, true, false);
+    assert_eq!((armed.count, moved, live.sell_count), (0, 1, 1));
+    assert!(live.group_done(9));
+
+    // and a take profit without a trigger rests as a one-cancels-other leg
+    let (_, live, moved) = bracket_exit(take_profit, 0, true, false);
+    assert_eq!(moved, 1);
+    assert_eq!(live.sell_orders[0].group_role, GROUP_ROLE_OCO);
+
+    // Exits of a finished group are dropped
+    let (armed, _, moved) = bracket_exit(stop_loss, 9000, true, true);
+    assert_eq!((armed.count, moved), (0, 0));
+}
+
+#[test]
+fn post_only_orders() {
+    // Best bid 99.00, best ask 101.00
+    let touch = book(&[limit(1, 0, 100, 9900, 1)], &[limit(2, 1, 100, 10100, 2)]);
+    assert_eq!(post_only_price(&touch, 0, 10000, POST_ONLY_REJECT, 1), (10000, true));
+    assert!(!post_only_price(&touch, 0, 10200, POST_ONLY_REJECT, 1).1);
+    assert_eq!(post_only_price(&touch, 0, 10200, POST_ONLY_SLIDE, 1), (10099, true));
+    assert_eq!(post_only_price(&touch, 1, 9800, POST_ONLY_SLIDE, 5), (9905, true));
+    assert_eq!(post_only_price(&touch, 1, 9800, POST_ONLY_NONE, 1), (9800, true));
+    let no_bids = book(&[], &[limit(2, 1, 100, 10100, 2)]);
+    assert_eq!(post_only_price(&no_bids, 1, 9800, POST_ONLY_REJECT, 1), (9800, true));
+
+    // A resting post-only bid never trades against an older ask
+    let post_only_bid = Order { post_only: true, ..limit(2, 0, 100, 10000, 2) };
+    let (_, result, _) = run(&[post_only_bid], &[limit(1, 1, 100, 10000, 1)], MIDPOINT);
+    assert_eq!(result.num_matches, 0);
+    let (_, result, _) = run(&[post_only_bid], &[limit(3, 1, 100, 10000, 3)], MIDPOINT);
+    assert_eq!(result.num_matches, 1);
+}
+
+fn ledger(available: u64) -> Balances {
+    Balances {
+        base_total: available,
+        base_available: available,
+        quote_total: available,
+        quote_available: available,
+    }
+}
+
+fn no_lock() -> OrderLock {
+    OrderLock { amount: 0, price: 0, quote_budget: 0, locked: 0 }
+}
+
+#[derive(Clone, Copy)]
+struct EntryRules {
+    band_reference: u64,
+    band_bps: u64,
+    tick_size: u64,
+    lot_size: u64,
+    min_notional: u64,
+}
+
+const NO_ENTRY_RULES: EntryRules = EntryRules {
+    band_reference: 0,
+    band_bps: 0,
+    tick_size: 1,
+    lot_size: 1,
+    min_notional: 0,
+};
+
+// Reject reason and status of an ungrouped order
+fn entry(order_type: u8, order_kind: u8, amount: u64, price: u64, quote_budget: u64, available: u64, rules: EntryRules) -> (u8, u8) {
+    let sensitive = UserSensitiveData {
+        amount,
+        price,
+        quote_budget,
+        min_fill_qty: 0,
+        display_size: 0,
+    };
+    let (possible, _, status, reason, _) = entry_check(
+        sensitive,
+        ledger(available),
+        no_lock(),
+        order_type,
+        order_kind,
+        0,
+        0,
+        GROUP_ROLE_NONE,
+        rules.band_reference,
+        rules.band_bps,
+        rules.tick_size,
+        rules.lot_size,
+        rules.min_notional,
+        SCALE,
+    );
+    assert_eq!(possible, reason == REJECT_REASON_NONE);
+    (reason, status.status)
+}
+
+#[test]
+fn entry_band() {
+    let band = EntryRules { band_reference: 10000, band_bps: 1000, ..NO_ENTRY_RULES };
+
+    // A limit price 100x off the feed is turned away before the balance is checked
+    assert_eq!(entry(0, ORDER_KIND_LIMIT, 100, 1_000_000, 0, 0, band), (REJECT_REASON_PRICE_BAND, 6));
+    assert_eq!(entry(0, ORDER_KIND_LIMIT, 100, 10500, 0, 0, band), (REJECT_REASON_INSUFFICIENT_BALANCE, 5));
+    assert_eq!(entry(0, ORDER_KIND_LIMIT, 100, 10500, 0, 10500, band), (REJECT_REASON_NONE, 1));
+
+    // Unpriced orders and markets without a band are left alone
+    assert_eq!(entry(0, ORDER_KIND_MARKET, 100, 0, 5000, 5000, band).0, REJECT_REASON_NONE);
+    assert_eq!(entry(1, ORDER_KIND_LIMIT, 100, 1_000_000, 0, 100, NO_ENTRY_RULES).0, REJECT_REASON_NONE);
+}
+
+#[test]
+fn entry_size_rules() {
+    // Prices on a 0.05 grid, sizes in whole units, at least 10.00 of quote
+    let rules = EntryRules { tick_size: 5, lot_size: 100, min_notional: 1000, ..NO_ENTRY_RULES };
+
+    assert_eq!(entry(1, ORDER_KIND_LIMIT, 500, 10003, 0, 500, rules), (REJECT_REASON_TICK_SIZE, 7));
+    assert_eq!(entry(1, ORDER_KIND_LIMIT, 550, 10000, 0, 550, rules), (REJECT_REASON_LOT_SIZE, 8));
+    // 1.00 at 5.00 is worth 5.00 of quote
+    assert_eq!(entry(1, ORDER_KIND_LIMIT, 100, 500, 0, 100, rules), (REJECT_REASON_MIN_NOTIONAL, 9));
+    assert_eq!(entry(0, ORDER_KIND_MARKET, 0, 0, 900, 900, rules).0, REJECT_REASON_MIN_NOTIONAL);
+    assert_eq!(entry(1, ORDER_KIND_LIMIT, 100, 1000, 0, 100, rules).0, REJECT_REASON_NONE);
+}
+
+fn twap(remaining: u64, slice_size: u64, locked: u64) -> TwapParent {
+    TwapParent { remaining, price: 10000, slice_size, locked }
+}
+
+fn slice(parent: TwapParent, live: OrderBook, order_type: u8, expired: u8) -> (TwapParent, OrderBook, Balances, OrderLock, bool, bool, bool) {
+    twap_step(parent, live, ledger(0), 1, order_type, 1, expired, SCALE, BATCH_TIME)
+}
+
+#[test]
+fn twap_releases_one_slice_per_run() {
+    // 10.00 base at 100.00 locked 1000.00 of quote
+    let (parent, live, _, lock, released, done, _) = slice(twap(1000, 400, 100_000), OrderBook::new(), 0, 0);
+    assert!(released && !done);
+    assert_eq!((parent.remaining, parent.locked, lock.locked), (600, 60_000, 40_000));
+    // The slice's own lock record covers just the slice
+    assert_eq!((lock.amount, lock.price), (400, 10000));
+    assert_eq!(live.buy_orders[0].amount, 400);
+
+    // The last slice only takes what is left, with the rest of the lock
+    let (parent, live, _, lock, released, done, _) = slice(twap(200, 400, 20_001), OrderBook::new(), 0, 0);
+    assert!(released && done);
+    assert_eq!((parent.locked, lock.locked), (0, 20_001));
+    assert_eq!(live.buy_orders[0].amount, 200);
+
+    // No slice size releases everything at once
+    let (_, live, _, _, _, done, _) = slice(twap(1000, 0, 1000), OrderBook::new(), 1, 0);
+    assert!(done);
+    assert_eq!(live.sell_orders[0].amount, 1000);
+}
+
+#[test]
+fn twap_holds_the_slice_while_its_side_is_full() {
+    let (parent, _, _, lock, released, done, _) = slice(twap(1000, 400, 100_000), full_bid_side(), 0, 0);
+    assert!(!released && !done);
+    assert_eq!((parent.remaining, lock.locked), (1000, 0));
+}
+
+#[test]
+fn twap_refunds_the_unreleased_lock_once_expired() {
+    // Buy locks come out of base_available in submit_order_check
+    let (parent, _, balances, _, released, done, overflow) = slice(twap(600, 400, 60_000), OrderBook::new(), 0, 1);
+    assert!(!released && done && !overflow);
+    assert_eq!((parent.remaining, parent.locked), (0, 0));
+    assert_eq!(balances.base_available, 60_000);
+
+    let (_, _, balances, _, _, _, _) = slice(twap(600, 400, 600), OrderBook::new(), 1, 1);
+    assert_eq!(balances.quote_available, 600);
+
+    // A refund the ledger can't take leaves the parent open
+    let (parent, _, balances, _, _, done, overflow) = twap_step(
+        twap(600, 400, 60_000),
+        OrderBook::new(),
+        ledger(u64::MAX),
+        1,
+        0,
+        1,
+        1,
+        SCALE,
+        BATCH_TIME,
+    );
+    assert!(overflow && !done);
+    assert_eq!((parent.locked, balances.base_available), (60_000, u64::MAX));
+}
+
+#[test]
+fn recurring_children_are_funded_until_the_ledger_runs_out() {
+    let schedule = RecurringOrder { quote_amount: 10_000, max_price: 0 };
+    let mut live = OrderBook::new();
+    let mut balances = ledger(25_000);
+    let mut placed = Vec::new();
+    for child_id in 1..=3 {
+        let (next, rest, lock, funded, child_placed) =
+            recurring_child(schedule, live, balances, child_id, 1, SCALE, child_id);
+        assert_eq!(funded, child_placed);
+        assert_eq!(lock.locked, if child_placed { 10_000 } else { 0 });
+        live = next;
+        balances = rest;
+        placed.push(child_placed);
+    }
+    assert_eq!(placed, vec![true, true, false]);
+    assert_eq!(balances.base_available, 5_000);
+
+    // A funded child that finds the bid side full keeps the balance
+    let (_, rest, lock, funded, child_placed) =
+        recurring_child(schedule, full_bid_side(), ledger(25_000), 9, 1, SCALE, 1);
+    assert!(funded && !child_placed);
+    assert_eq!((rest.base_available, lock.locked), (25_000, 0));
+}
+
+#[test]
+fn settlement_fees() {
+    // 10 bps of 15000 quote units
+    assert_eq!(settlement_fee(15_000, 10), 15);
+    // Rounded up so dust fills still pay
+    assert_eq!(settlement_fee(1, 1), 1);
+    assert_eq!(settlement_fee(10_001, 1), 2);
+    assert_eq!(settlement_fee(15_000, 0), 0);
+    // Never more than the receiver gets
+    assert_eq!(settlement_fee(999, 10_000), 999);
+
+    // The referrer's cut comes out of the fee, dust stays with the protocol
+    assert_eq!(referral_cut(15, 2000), 3);
+    assert_eq!(referral_cut(1, 5000), 0);
+    assert_eq!(referral_cut(3, 5000), 1);
+    assert_eq!(referral_cut(15, 0), 0);
+}
+
+#[test]
+fn fills_come_off_the_lock() {
+    let lock = OrderLock { amount: 2, price: 10_000, quote_budget: 0, locked: 20_000 };
+    let (balances, rest, overflow) = take_fill_from_lock(ledger(0), lock, 1, 15_000);
+    assert!(!overflow);
+    assert_eq!((balances.quote_available, balances.base_available, rest.locked), (15_000, 0, 5_000));
+
+    // Only what the lock still holds is handed back
+    let (balances, rest, _) = take_fill_from_lock(ledger(0), rest, 1, 15_000);
+    assert_eq!((balances.quote_available, rest.locked), (5_000, 0));
+    let (balances, rest, _) = take_fill_from_lock(ledger(0), rest, 0, 15_000);
+    assert_eq!((balances.base_available, rest.locked), (0, 0));
+
+    let (_, _, overflow) = take_fill_from_lock(ledger(u64::MAX), lock, 0, 1);
+    assert!(overflow);
+}
+
+#[test]
+fn volume_fee_tiers() {
+    let thresholds = [100_000, 1_000_000, 0];
+    let tier_fees = [8, 5, 0];
+    assert_eq!(tier_fee_bps(99_999, 10, thresholds, tier_fees), 10);
+    assert_eq!(tier_fee_bps(100_000, 10, thresholds, tier_fees), 8);
+    assert_eq!(tier_fee_bps(5_000_000, 10, thresholds, tier_fees), 5);
+}
+
+#[test]
+fn volume_rolls_one_window_at_a_time() {
+    let volume = TradingVolume { current: 500, previous: 300 };
+    let rolled = |windows: u8| {
+        let v = roll_volume(volume, windows);
+        (v.current, v.previous)
+    };
+    assert_eq!(rolled(0), (500, 300));
+    assert_eq!(rolled(1), (0, 500));
+    assert_eq!(rolled(2), (0, 0));
+
+    // The previous window fades out over the current one
+    let volume = TradingVolume { current: 1000, previous: 2000 };
+    assert_eq!(rolling_volume(volume, 10_000), 3000);
+    assert_eq!(rolling_volume(volume, 5_000), 2000);
+    assert_eq!(rolling_volume(volume, 0), 1000);
+}
+
+#[test]
+fn fixed_point_rounds_the_protocols_way() {
+    // 0.5 of a 6 decimal base at 150.000001 is worth 75.0000005
+    let scale = 1_000_000;
+    let price = 150_000_001;
+    assert_eq!(quote_owed(500_000, price, scale), 75_000_001);
+    assert_eq!(quote_due(500_000, price, scale), 75_000_000);
+    assert_eq!(quote_owed(1_000_000, price, scale), quote_due(1_000_000, price, scale));
+
+    // A budget never buys more than it paid for
+    let budget = 75_000_000;
+    let quantity = base_for_quote(budget, price, scale);
+    assert!(quote_owed(quantity, price, scale) <= budget);
+    assert!(quote_owed(quantity + 1, price, scale) > budget);
+
+    assert_eq!(quote_owed(u64::MAX, u64::MAX, 1), u64::MAX);
+    assert_eq!(checked_add(u64::MAX, 1), (u64::MAX, true));
+    assert_eq!(checked_sub(1, 2), (1, true));
+}
+
+const VALUES: [u64; 10] = [0, 1, 2, 99, 100, 101, 12_345, 1_000_000_007, u32::MAX as u64, u64::MAX];
+const SCALES: [u64; 4] = [1, 100, 1_000_000, 1_000_000_000];
+
+// The program prices locks and refunds with its own fixed point helpers, they
+// have to land on the same raw units as the circuits
+#[test]
+fn program_fixed_point_agrees_with_the_circuits() {
+    for &quantity in &VALUES {
+        for &price in &VALUES {
+            for &scale in &SCALES {
+                let owed = quote_owed(quantity, price, scale);
+                let due = quote_due(quantity, price, scale);
+                // The circuits can't fail, they saturate where the program refuses
+                match program::quote_owed(quantity, price, scale) {
+                    Ok(value) => assert_eq!(value, owed, "{} at {} / {}", quantity, price, scale),
+                    Err(_) => assert_eq!(owed, u64::MAX),
+                }
+                match program::quote_due(quantity, price, scale) {
+                    Ok(value) => assert_eq!(value, due, "{} at {} / {}", quantity, price, scale),
+                    Err(_) => assert_eq!(due, u64::MAX),
+                }
+            }
+        }
+    }
+}
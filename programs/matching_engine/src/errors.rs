@@ -20,4 +20,114 @@ pub enum ErrorCode {
     UnauthorizedSettlement,
     #[msg("Not approved")]
     NotApproved,
+    #[msg("Invalid order kind")]
+    InvalidOrderKind,
+    #[msg("Invalid order type")]
+    InvalidOrderType,
+    #[msg("Invalid price feed")]
+    InvalidPriceFeed,
+    #[msg("Reference price unavailable")]
+    PriceUnavailable,
+    #[msg("Unauthorized authority")]
+    UnauthorizedAuthority,
+    #[msg("Invalid pricing rule")]
+    InvalidPricingRule,
+    #[msg("Invalid market mode")]
+    InvalidMarketMode,
+    #[msg("Instruction not available in the current market mode")]
+    WrongMarketMode,
+    #[msg("Invalid self trade prevention policy")]
+    InvalidStpPolicy,
+    #[msg("Invalid allocation mode")]
+    InvalidAllocationMode,
+    #[msg("Invalid order group type")]
+    InvalidGroupType,
+    #[msg("Order group has no room for another leg")]
+    OrderGroupFull,
+    #[msg("Only the group owner can add legs to it")]
+    UnauthorizedGroup,
+    #[msg("Bracket exit legs must be submitted through submit_stop_order")]
+    InvalidGroupLeg,
+    #[msg("The group's first leg has not locked collateral")]
+    GroupLockNotReady,
+    #[msg("Invalid post-only mode")]
+    InvalidPostOnly,
+    #[msg("Invalid tick size")]
+    InvalidTickSize,
+    #[msg("Invalid lot size")]
+    InvalidLotSize,
+    #[msg("Invalid TWAP order")]
+    InvalidTwapOrder,
+    #[msg("TWAP order has already been fully released or expired")]
+    TwapOrderCompleted,
+    #[msg("Invalid recurring order schedule")]
+    InvalidRecurringOrder,
+    #[msg("Recurring order is no longer active")]
+    RecurringOrderInactive,
+    #[msg("Recurring order period has not elapsed yet")]
+    RecurringOrderNotDue,
+    #[msg("Invalid trading session schedule")]
+    InvalidSessionSchedule,
+    #[msg("Market is closed, orders are not accepted in the current session")]
+    MarketClosed,
+    #[msg("Instruction not available in the current trading session")]
+    WrongSession,
+    #[msg("This session's call auction has already crossed")]
+    CallAuctionAlreadyRun,
+    #[msg("Invalid price band")]
+    InvalidPriceBand,
+    #[msg("Market is halted by the price band circuit breaker")]
+    MarketHalted,
+    #[msg("Deposit would overflow the ledger balance")]
+    DepositOverflow,
+    #[msg("Withdrawal would underflow the ledger total")]
+    WithdrawUnderflow,
+    #[msg("Settlement would overflow or underflow a ledger balance")]
+    SettlementOverflow,
+    #[msg("Lock release would overflow the ledger balance")]
+    LockReleaseOverflow,
+    #[msg("Fee rate above the maximum")]
+    InvalidFeeRate,
+    #[msg("Fee ledger does not belong to the market authority")]
+    InvalidFeeLedger,
+    #[msg("Fee tier thresholds must rise and unused tiers come last")]
+    InvalidFeeTiers,
+    #[msg("Invalid referrer")]
+    InvalidReferrer,
+    #[msg("Referral share above 100%")]
+    InvalidReferralShare,
+    #[msg("Mint is neither the market's base nor quote mint")]
+    InvalidMint,
+    #[msg("No fees accrued for this mint")]
+    NoFeesAccrued,
+    #[msg("Order has no bond left to release")]
+    NoOrderBond,
+    #[msg("Invalid order limits")]
+    InvalidOrderLimits,
+    #[msg("Too many open orders")]
+    TooManyOpenOrders,
+    #[msg("Order submission rate limit exceeded")]
+    SubmissionRateExceeded,
+    #[msg("Order is not open")]
+    OrderNotOpen,
+    #[msg("Order was not accepted by submit_order_check")]
+    OrderNotChecked,
+    #[msg("Order has already been submitted")]
+    OrderAlreadySubmitted,
+    #[msg("Order side or kind differs from the checked order")]
+    OrderCheckMismatch,
+    #[msg("Reference price is too old")]
+    StalePrice,
+    #[msg("Reference price confidence interval is too wide")]
+    PriceTooUncertain,
+    #[msg("Token account does not belong to the ledger owner")]
+    InvalidTokenAccountOwner,
+    #[msg("Order is still open")]
+    OrderStillOpen,
+    #[msg("Instruction not available for the market's pricing rule")]
+    WrongPricingRule,
+    #[msg("Bond of an order that passed the check can't be slashed")]
+    CheckedOrderBond,
+    #[msg("A group's first leg needs a non-zero order id")]
+    InvalidGroupOrderId,
 }
@@ -0,0 +1,49 @@
+use crate::errors::ErrorCode;
+use crate::states::*;
+use anchor_lang::prelude::*;
+
+// public key for the settlement/cranker bot
+const CRANKER_BOT_PUBKEY: Pubkey = pubkey!("8wJE7H7svhpz1Jnzbne3YErWFVeWNWGRbAkDQ8xeixoY");
+
+// Frees an open order slot once the backend sees the order filled,
+// cancelled or expired. Rejected orders are closed by the check callback.
+pub fn close_open_order(ctx: Context<CloseOpenOrder>) -> Result<()> {
+    require!(ctx.accounts.order_account.open == 1, ErrorCode::OrderNotOpen);
+    ctx.accounts.order_account.open = 0;
+
+    let user_trading_state = &mut ctx.accounts.user_trading_state;
+    user_trading_state.record_closed();
+
+    emit!(OpenOrderClosedEvent {
+        order_id: ctx.accounts.order_account.order_id,
+        user: user_trading_state.user,
+        open_orders: user_trading_state.open_orders,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CloseOpenOrder<'info> {
+    #[account(address = CRANKER_BOT_PUBKEY)]
+    pub payer: Signer<'info>,
+
+    #[account(mut)]
+    pub order_account: Box<Account<'info, OrderAccount>>,
+
+    #[account(
+        mut,
+        seeds = [USER_TRADING_STATE_SEED, order_account.user.as_ref()],
+        bump = user_trading_state.bump,
+    )]
+    pub user_trading_state: Account<'info, UserTradingState>,
+}
+
+#[event]
+pub struct OpenOrderClosedEvent {
+    pub order_id: u64,
+    pub user: Pubkey,
+    pub open_orders: u16,
+    pub timestamp: i64,
+}
@@ -0,0 +1,97 @@
+use crate::errors::ErrorCode;
+use crate::states::*;
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+
+// Plaintext deposit and withdraw fees are parked here, one vault per mint,
+// owned by the same vault_authority PDA as the trading vaults
+pub const FEE_VAULT_SEED: &[u8] = b"fee_vault";
+
+// Sweeps everything accrued in one mint's fee vault to the treasury
+pub fn collect_fees(ctx: Context<CollectFees>) -> Result<()> {
+    let mint = ctx.accounts.mint.key();
+    let amount = {
+        let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+        if mint == orderbook_state.base_mint {
+            let accrued = orderbook_state.accrued_base_fees;
+            orderbook_state.accrued_base_fees = 0;
+            accrued
+        } else {
+            require_keys_eq!(mint, orderbook_state.quote_mint, ErrorCode::InvalidMint);
+            let accrued = orderbook_state.accrued_quote_fees;
+            orderbook_state.accrued_quote_fees = 0;
+            accrued
+        }
+    };
+    require!(amount > 0, ErrorCode::NoFeesAccrued);
+
+    let cpi_accounts = Transfer {
+        from: ctx.accounts.fee_vault.to_account_info(),
+        to: ctx.accounts.treasury_token_account.to_account_info(),
+        authority: ctx.accounts.vault_authority.to_account_info(),
+    };
+
+    let signer_seeds: &[&[&[u8]]] = &[&[b"vault_authority", &[ctx.bumps.vault_authority]]];
+
+    let cpi_context = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        cpi_accounts,
+        signer_seeds,
+    );
+
+    token::transfer(cpi_context, amount)?;
+
+    emit!(FeesCollectedEvent {
+        mint,
+        treasury: ctx.accounts.treasury_token_account.key(),
+        amount,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CollectFees<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = orderbook_state.load()?.authority == authority.key() @ ErrorCode::UnauthorizedAuthority,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump,
+    )]
+    pub orderbook_state: AccountLoader<'info, OrderBookState>,
+
+    /// CHECK: PDA authority for vault
+    #[account(
+        seeds = [b"vault_authority"],
+        bump,
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    pub mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [FEE_VAULT_SEED, mint.key().as_ref()],
+        bump,
+    )]
+    pub fee_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+    )]
+    pub treasury_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[event]
+pub struct FeesCollectedEvent {
+    pub mint: Pubkey,
+    pub treasury: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
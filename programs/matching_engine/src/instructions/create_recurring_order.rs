@@ -0,0 +1,152 @@
+use crate::errors::ErrorCode;
+use crate::states::*;
+use crate::utils::require_accepting_orders;
+use crate::CreateRecurringOrderCallback;
+use crate::SignerAccount;
+use crate::COMP_DEF_OFFSET_CREATE_RECURRING_ORDER;
+use anchor_lang::prelude::*;
+use arcium_anchor::prelude::*;
+use arcium_client::idl::arcium::types::CallbackAccount;
+
+use crate::ID;
+use crate::ID_CONST;
+
+// Sets up a recurring buy of a fixed quote amount every period for
+// total_periods periods. The order account reserves the schedule's id, each
+// child opens its own under the ids that follow it.
+pub fn create_recurring_order(
+    ctx: Context<CreateRecurringOrder>,
+    quote_amount: [u8; 32],
+    max_price: [u8; 32],
+    user_pubkey: [u8; 32], // x25519 public key
+    period: i64,
+    total_periods: u64,
+    computation_offset: u64,
+    order_id: u64,
+    order_nonce: u128,
+    schedule_nonce: u128,
+) -> Result<()> {
+    // A child per matching interval at most, so they never stack up in the book
+    require!(period >= 15, ErrorCode::InvalidRecurringOrder);
+    require!(total_periods > 0, ErrorCode::InvalidRecurringOrder);
+
+    let current_time = Clock::get()?.unix_timestamp;
+    require_accepting_orders(&*ctx.accounts.orderbook_state.load()?, current_time)?;
+
+    let order_account = &mut ctx.accounts.order_account;
+    order_account.order_id = order_id;
+    order_account.user = ctx.accounts.user.key();
+    order_account.user_enc_pubkey = user_pubkey;
+    order_account.order_nonce = order_nonce;
+    order_account.timestamp = current_time;
+    order_account.bump = ctx.bumps.order_account;
+
+    let mut recurring_order = ctx.accounts.recurring_order_state.load_init()?;
+    recurring_order.orderbook = ctx.accounts.orderbook_state.key();
+    recurring_order.user = ctx.accounts.user.key();
+    recurring_order.user_enc_pubkey = user_pubkey;
+    recurring_order.order_id = order_id;
+    recurring_order.period = period;
+    recurring_order.total_periods = total_periods;
+    recurring_order.next_execution_time = current_time + period;
+    recurring_order.active = 1;
+    recurring_order.bump = ctx.bumps.recurring_order_state;
+
+    let args = vec![
+        // Enc<Shared, RecurringOrderData>
+        Argument::ArcisPubkey(user_pubkey),
+        Argument::PlaintextU128(order_nonce),
+        Argument::EncryptedU64(quote_amount),
+        Argument::EncryptedU64(max_price), // 0 = no worst price
+
+        // Mxe
+        Argument::PlaintextU128(schedule_nonce),
+    ];
+
+    ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+    queue_computation(
+        ctx.accounts,
+        computation_offset,
+        args,
+        None,
+        vec![CreateRecurringOrderCallback::callback_ix(&[CallbackAccount {
+            pubkey: ctx.accounts.recurring_order_state.key(),
+            is_writable: true,
+        }])],
+    )?;
+
+    Ok(())
+}
+
+#[queue_computation_accounts("create_recurring_order", user)]
+#[derive(Accounts)]
+#[instruction(
+    quote_amount: [u8; 32],
+    max_price: [u8; 32],
+    user_pubkey: [u8; 32],
+    period: i64,
+    total_periods: u64,
+    computation_offset: u64,
+    order_id: u64,
+    order_nonce: u128,
+    schedule_nonce: u128,
+)]
+pub struct CreateRecurringOrder<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = user,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, SignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_CREATE_RECURRING_ORDER))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Account<'info, ClockAccount>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+
+    #[account(
+        init,
+        payer = user,
+        space = 8 + OrderAccount::INIT_SPACE,
+        seeds = [
+            b"order",
+            order_id.to_le_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub order_account: Box<Account<'info, OrderAccount>>,
+
+    #[account(seeds = [ORDER_BOOK_STATE_SEED], bump)]
+    pub orderbook_state: AccountLoader<'info, OrderBookState>,
+
+    #[account(
+        init,
+        payer = user,
+        space = 8 + RecurringOrderState::INIT_SPACE,
+        seeds = [RECURRING_ORDER_SEED, order_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub recurring_order_state: AccountLoader<'info, RecurringOrderState>,
+}
@@ -1,5 +1,7 @@
 use crate::errors::ErrorCode;
 use crate::states::*;
+use crate::utils::*;
+use crate::instructions::FEE_VAULT_SEED;
 use crate::SignerAccount;
 use anchor_lang::prelude::*;
 use anchor_spl::token::Mint;
@@ -22,7 +24,35 @@ pub fn deposit_to_ledger(
     is_base_token: bool,
     computation_offset: u64,
 ) -> Result<()> {
-    // 1. Transfer SPL tokens to vault
+    // 1. Take the deposit fee, only what is left gets credited to the ledger
+    let fee = {
+        let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+        // Fees are booked per mint, they sit in that mint's fee vault
+        let is_base = ctx.accounts.mint.key() == orderbook_state.base_mint;
+        let fee = fee_for(amount, orderbook_state.deposit_fee_bps)?;
+        orderbook_state.accrue_fee(is_base, fee)?;
+        fee
+    };
+    let credited = amount - fee;
+
+    if fee > 0 {
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.user_token_account.to_account_info(),
+            to: ctx.accounts.fee_vault.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token::transfer(cpi_ctx, fee)?;
+
+        emit!(FeeChargedEvent {
+            user: ctx.accounts.user.key(),
+            mint: ctx.accounts.mint.key(),
+            fee,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+    }
+
+    // 2. Transfer SPL tokens to vault
     let cpi_accounts = Transfer {
         from: ctx.accounts.user_token_account.to_account_info(),
         to: ctx.accounts.vault.to_account_info(),
@@ -32,11 +62,11 @@ pub fn deposit_to_ledger(
     let cpi_program = ctx.accounts.token_program.to_account_info();
     let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
     
-    token::transfer(cpi_ctx, amount)?;
+    token::transfer(cpi_ctx, credited)?;
     
-    msg!("Deposited {} tokens to vault", amount);
+    msg!("Deposited {} tokens to vault", credited);
     
-    // 2. Queue MPC computation to update encrypted balances
+    // 3. Queue MPC computation to update encrypted balances
     ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
 
     let user_ledger = ctx.accounts.user_ledger.load_mut()?;
@@ -52,21 +82,49 @@ pub fn deposit_to_ledger(
         ),
         
         // Deposit info
-        Argument::PlaintextU64(amount),
+        Argument::PlaintextU64(credited),
+        Argument::PlaintextU64(fee),
         Argument::PlaintextU8(if is_base_token { 0 } else { 1 }),
     ];
-    
+
+    // The callback hands the tokens back if the ledger can't take them
+    let callback_accounts = [
+        CallbackAccount {
+            pubkey: ctx.accounts.user_ledger.key(),
+            is_writable: true,
+        },
+        CallbackAccount {
+            pubkey: ctx.accounts.orderbook_state.key(),
+            is_writable: true,
+        },
+        CallbackAccount {
+            pubkey: ctx.accounts.vault_authority.key(),
+            is_writable: false,
+        },
+        CallbackAccount {
+            pubkey: ctx.accounts.vault.key(),
+            is_writable: true,
+        },
+        CallbackAccount {
+            pubkey: ctx.accounts.fee_vault.key(),
+            is_writable: true,
+        },
+        CallbackAccount {
+            pubkey: ctx.accounts.user_token_account.key(),
+            is_writable: true,
+        },
+        CallbackAccount {
+            pubkey: ctx.accounts.token_program.key(),
+            is_writable: false,
+        },
+    ];
+
     queue_computation(
         ctx.accounts,
         computation_offset,
         args,
         None,
-        vec![UpdateLedgerDepositCallback::callback_ix(&[
-            CallbackAccount {
-                pubkey: ctx.accounts.user_ledger.key(),
-                is_writable: true,
-            },
-        ])],
+        vec![UpdateLedgerDepositCallback::callback_ix(&callback_accounts)],
     )?;
     
     Ok(())
@@ -145,8 +203,32 @@ pub struct DepositToLedger<'info> {
     
     #[account(mut,)]
     pub user_ledger: AccountLoader<'info, UserPrivateLedger>,
+
+    #[account(
+        mut,
+        constraint = orderbook_state.load()?.base_mint == mint.key()
+            || orderbook_state.load()?.quote_mint == mint.key() @ ErrorCode::InvalidMint,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump,
+    )]
+    pub orderbook_state: AccountLoader<'info, OrderBookState>,
+
+    #[account(
+        mut,
+        seeds = [FEE_VAULT_SEED, mint.key().as_ref()],
+        bump,
+    )]
+    pub fee_vault: Account<'info, TokenAccount>,
     
     
     pub token_program: Program<'info, Token>,
     pub associated_token_program: Program<'info, AssociatedToken>,
-}
\ No newline at end of file
+}
+
+#[event]
+pub struct FeeChargedEvent {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub fee: u64,
+    pub timestamp: i64,
+}
@@ -0,0 +1,133 @@
+use crate::errors::ErrorCode;
+use crate::states::*;
+use crate::utils::*;
+use crate::EvaluateTriggersCallback;
+use crate::SignerAccount;
+use crate::COMP_DEF_OFFSET_EVALUATE_TRIGGERS;
+use anchor_lang::prelude::*;
+use arcium_anchor::prelude::*;
+use arcium_client::idl::arcium::types::CallbackAccount;
+
+use crate::ID;
+use crate::ID_CONST;
+
+// Crank that checks every armed stop against the market's reference price
+// and moves the triggered ones into the live order book
+pub fn evaluate_triggers(ctx: Context<EvaluateTriggers>, computation_offset: u64) -> Result<()> {
+    let orderbook_state = ctx.accounts.orderbook_state.load()?;
+    let mut trigger_book = ctx.accounts.trigger_book_state.load_mut()?;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    // Triggered stops join the book, which only takes orders while open
+    require_accepting_orders(&orderbook_state, current_time)?;
+    // Same cadence as matching, triggers can't fire faster than they can trade
+    require!(
+        current_time >= trigger_book.last_evaluation_timestamp + 15,
+        ErrorCode::MatchingTooFrequent
+    );
+    trigger_book.last_evaluation_timestamp = current_time;
+
+    require_keys_eq!(
+        ctx.accounts.price_feed.key(),
+        orderbook_state.price_feed,
+        ErrorCode::InvalidPriceFeed
+    );
+    let reference_price =
+        load_reference_price(&ctx.accounts.price_feed, orderbook_state.quote_decimals)?;
+
+    let args = vec![
+        // Enc<Mxe, TriggerBookFlat>
+        Argument::PlaintextU128(trigger_book.trigger_nonce),
+        Argument::Account(
+            ctx.accounts.trigger_book_state.key(),
+            8 + 32,
+            (TRIGGER_BOOK_CHUNKS * 32) as u32,
+        ),
+
+        // Enc<Mxe, OrderBookFlat>
+        Argument::PlaintextU128(orderbook_state.orderbook_nonce),
+        Argument::Account(
+            ctx.accounts.orderbook_state.key(),
+            8 + 32,
+            (ORDER_BOOK_CHUNKS * 32) as u32,
+        ),
+
+        Argument::PlaintextU64(reference_price),
+        Argument::PlaintextU64(current_time as u64),
+    ];
+
+    ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+    queue_computation(
+        ctx.accounts,
+        computation_offset,
+        args,
+        None,
+        vec![EvaluateTriggersCallback::callback_ix(&[
+            CallbackAccount {
+                pubkey: ctx.accounts.trigger_book_state.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.orderbook_state.key(),
+                is_writable: true,
+            },
+        ])],
+    )?;
+
+    msg!("Trigger evaluation queued at reference price {}", reference_price);
+
+    Ok(())
+}
+
+#[queue_computation_accounts("evaluate_triggers", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct EvaluateTriggers<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, SignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_EVALUATE_TRIGGERS))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Account<'info, ClockAccount>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        mut,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump,
+    )]
+    pub orderbook_state: AccountLoader<'info, OrderBookState>,
+    #[account(
+        mut,
+        seeds = [TRIGGER_BOOK_SEED, orderbook_state.key().as_ref()],
+        bump = trigger_book_state.load()?.bump,
+    )]
+    pub trigger_book_state: AccountLoader<'info, TriggerBookState>,
+    /// CHECK: Pyth price account, must match orderbook_state.price_feed.
+    pub price_feed: UncheckedAccount<'info>,
+}
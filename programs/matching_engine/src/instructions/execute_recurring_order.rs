@@ -0,0 +1,220 @@
+use crate::errors::ErrorCode;
+use crate::states::*;
+use crate::utils::{price_scale, require_accepting_orders};
+use crate::ExecuteRecurringOrderCallback;
+use crate::SignerAccount;
+use crate::COMP_DEF_OFFSET_EXECUTE_RECURRING_ORDER;
+use crate::ORDER_KIND_MARKET;
+use anchor_lang::prelude::*;
+use arcium_anchor::prelude::*;
+use arcium_client::idl::arcium::types::CallbackAccount;
+
+use crate::ID;
+use crate::ID_CONST;
+
+// Permissionless crank that places the next child of a recurring buy once
+// its period has elapsed. Each child gets an order account of its own, with
+// the lock the circuit records for it, so its fills and releases never touch
+// another child's.
+pub fn execute_recurring_order(
+    ctx: Context<ExecuteRecurringOrder>,
+    computation_offset: u64,
+    child_order_id: u64,
+    lock_nonce: u128,
+) -> Result<()> {
+    let orderbook_state = ctx.accounts.orderbook_state.load()?;
+    let mut recurring_order = ctx.accounts.recurring_order_state.load_mut()?;
+    let user_ledger = ctx.accounts.user_ledger.load()?;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    require!(recurring_order.active == 1, ErrorCode::RecurringOrderInactive);
+    require!(
+        current_time >= recurring_order.next_execution_time,
+        ErrorCode::RecurringOrderNotDue
+    );
+    require_accepting_orders(&orderbook_state, current_time)?;
+    // A child that couldn't be placed is retried a period later
+    recurring_order.next_execution_time = current_time + recurring_order.period;
+
+    let expected_child_order_id = recurring_order
+        .next_child_order_id()
+        .ok_or(ErrorCode::InvalidRecurringOrder)?;
+    require!(child_order_id == expected_child_order_id, ErrorCode::InvalidRecurringOrder);
+    recurring_order.children_created += 1;
+
+    // Every child is an order of the owner's and counts against their caps
+    // like one sent through submit_order_check
+    let user_trading_state = &mut ctx.accounts.user_trading_state;
+    if user_trading_state.user == Pubkey::default() {
+        user_trading_state.user = recurring_order.user;
+        user_trading_state.bump = ctx.bumps.user_trading_state;
+    }
+    user_trading_state.record_submission(
+        current_time,
+        orderbook_state.max_open_orders,
+        orderbook_state.max_submissions_per_window,
+        orderbook_state.submission_window,
+    )?;
+
+    let child_order = &mut ctx.accounts.child_order_account;
+    child_order.order_id = child_order_id;
+    child_order.user = recurring_order.user;
+    child_order.user_enc_pubkey = recurring_order.user_enc_pubkey;
+    child_order.order_type = 0;
+    child_order.order_kind = ORDER_KIND_MARKET;
+    child_order.timestamp = current_time;
+    // Funded and placed by the circuit itself, there is no separate check
+    child_order.checked = 1;
+    child_order.submitted = 1;
+    child_order.open = 1;
+    child_order.bump = ctx.bumps.child_order_account;
+
+    let order_id = child_order_id;
+
+    let args = vec![
+        // Enc<Mxe, RecurringOrder>
+        Argument::PlaintextU128(recurring_order.schedule_nonce),
+        Argument::Account(
+            ctx.accounts.recurring_order_state.key(),
+            8 + 32, // Offset: discriminator(8) + orderbook(32)
+            (RECURRING_ORDER_CHUNKS * 32) as u32,
+        ),
+
+        // Enc<Mxe, OrderBookFlat>
+        Argument::PlaintextU128(orderbook_state.orderbook_nonce),
+        Argument::Account(
+            ctx.accounts.orderbook_state.key(),
+            8 + 32,
+            (ORDER_BOOK_CHUNKS * 32) as u32,
+        ),
+
+        // Enc<Shared, Balances>
+        Argument::ArcisPubkey(recurring_order.user_enc_pubkey),
+        Argument::PlaintextU128(user_ledger.balance_nonce),
+        Argument::Account(
+            ctx.accounts.user_ledger.key(),
+            8 + 32,          // Offset: discriminator + owner
+            4 * 32,          // Size: 4 chunks
+        ),
+
+        Argument::PlaintextU64(order_id),
+
+        // Enc<Mxe, u64>
+        Argument::PlaintextU128(user_ledger.owner_nonce),
+        Argument::Account(ctx.accounts.user_ledger.key(), LEDGER_OWNER_OFFSET, 32),
+
+        Argument::PlaintextU64(price_scale(orderbook_state.base_decimals)?),
+        Argument::PlaintextU64(current_time as u64),
+
+        // Mxe, the child's lock record
+        Argument::PlaintextU128(lock_nonce),
+    ];
+
+    ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+    queue_computation(
+        ctx.accounts,
+        computation_offset,
+        args,
+        None,
+        vec![ExecuteRecurringOrderCallback::callback_ix(&[
+            CallbackAccount {
+                pubkey: ctx.accounts.recurring_order_state.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.orderbook_state.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.user_ledger.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.child_order_account.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.user_trading_state.key(),
+                is_writable: true,
+            },
+        ])],
+    )?;
+
+    msg!("Recurring order child {} queued", order_id);
+
+    Ok(())
+}
+
+#[queue_computation_accounts("execute_recurring_order", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64, child_order_id: u64)]
+pub struct ExecuteRecurringOrder<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, SignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_EXECUTE_RECURRING_ORDER))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Account<'info, ClockAccount>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        mut,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump,
+    )]
+    pub orderbook_state: AccountLoader<'info, OrderBookState>,
+    #[account(
+        mut,
+        constraint = recurring_order_state.load()?.orderbook == orderbook_state.key() @ ErrorCode::InvalidRecurringOrder,
+    )]
+    pub recurring_order_state: AccountLoader<'info, RecurringOrderState>,
+    #[account(
+        mut,
+        constraint = user_ledger.load()?.owner == recurring_order_state.load()?.user @ ErrorCode::UnauthorizedSettlement,
+    )]
+    pub user_ledger: AccountLoader<'info, UserPrivateLedger>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + OrderAccount::INIT_SPACE,
+        seeds = [b"order", child_order_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub child_order_account: Box<Account<'info, OrderAccount>>,
+    /// CHECK: Owner of the recurring order, only keys their trading state
+    #[account(address = recurring_order_state.load()?.user)]
+    pub user: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + UserTradingState::INIT_SPACE,
+        seeds = [USER_TRADING_STATE_SEED, user.key().as_ref()],
+        bump,
+    )]
+    pub user_trading_state: Box<Account<'info, UserTradingState>>,
+}
@@ -1,5 +1,6 @@
 use crate::errors::ErrorCode;
 use crate::states::*;
+use crate::utils::*;
 use crate::SignerAccount;
 use crate::ExecuteSettlementCallback;
 use crate::COMP_DEF_OFFSET_EXECUTE_SETTLEMENT;
@@ -19,13 +20,86 @@ pub fn execute_settlement(
     ctx: Context<ExecuteSettlement>,
     user1_enc_pubkey: [u8; 32],
     user2_enc_pubkey: [u8; 32],
+    fee_enc_pubkey: [u8; 32],
+    referrer_enc_pubkey: [u8; 32],
     execution_price: u64,
     is_base: bool,
+    receiver_is_maker: bool,
     computation_offset: u64,
 ) -> Result<()> {
+    // The receiving side pays the fee for its role in the match, the circuit
+    // swaps in a tier rate when its volume qualifies
+    let (fee_bps, tier_thresholds, tier_fees_bps, market_referral_share_bps) = {
+        let orderbook_state = ctx.accounts.orderbook_state.load()?;
+        let tier_fees_bps = if receiver_is_maker {
+            orderbook_state.maker_tier_fee_bps
+        } else {
+            orderbook_state.taker_tier_fee_bps
+        };
+        let fee_bps = if receiver_is_maker {
+            orderbook_state.maker_fee_bps
+        } else {
+            orderbook_state.taker_fee_bps
+        };
+        (
+            fee_bps,
+            orderbook_state.fee_tier_thresholds,
+            tier_fees_bps,
+            orderbook_state.referral_share_bps,
+        )
+    };
+
+    // A referred receiver shares its fee with the referrer. The link is looked
+    // up at the seller ledger's PDA so it can't be left out, while it holds no
+    // data the fee ledger stands in for the referrer and gets a zero share.
+    let referral_link = if ctx.accounts.referral_link.data_is_empty() {
+        None
+    } else {
+        let data = ctx.accounts.referral_link.try_borrow_data()?;
+        Some(ReferralLink::try_deserialize(&mut &data[..])?)
+    };
+    let referral_share_bps = match &referral_link {
+        Some(referral_link) => {
+            require_keys_eq!(
+                referral_link.referee_ledger,
+                ctx.accounts.seller_ledger.key(),
+                ErrorCode::InvalidReferrer
+            );
+            require_keys_eq!(
+                referral_link.referrer_ledger,
+                ctx.accounts.referrer_ledger.key(),
+                ErrorCode::InvalidReferrer
+            );
+            // Each ledger gets one output, a referrer already in the match
+            // is settled without the link
+            require!(
+                referral_link.referrer_ledger != ctx.accounts.buyer_ledger.key()
+                    && referral_link.referrer_ledger != ctx.accounts.fee_ledger.key(),
+                ErrorCode::InvalidReferrer
+            );
+            market_referral_share_bps
+        }
+        None => {
+            require_keys_eq!(
+                ctx.accounts.referrer_ledger.key(),
+                ctx.accounts.fee_ledger.key(),
+                ErrorCode::InvalidReferrer
+            );
+            0
+        }
+    };
+
+    let now = Clock::get()?.unix_timestamp;
+    let current_window = volume_window(now);
 
     let buyer_ledger = ctx.accounts.buyer_ledger.load_mut()?;
     let seller_ledger = ctx.accounts.seller_ledger.load_mut()?;
+    let fee_ledger = ctx.accounts.fee_ledger.load_mut()?;
+    let referrer_nonce = if referral_link.is_some() {
+        ctx.accounts.referrer_ledger.load()?.balance_nonce
+    } else {
+        fee_ledger.balance_nonce
+    };
     let args = vec![
         Argument::ArcisPubkey(user1_enc_pubkey),
         Argument::PlaintextU128(buyer_ledger.balance_nonce),
@@ -35,8 +109,46 @@ pub fn execute_settlement(
         Argument::PlaintextU128(seller_ledger.balance_nonce),
         Argument::Account(ctx.accounts.seller_ledger.key(), 8 + 32, 4 * 32),
 
+        Argument::ArcisPubkey(fee_enc_pubkey),
+        Argument::PlaintextU128(fee_ledger.balance_nonce),
+        Argument::Account(ctx.accounts.fee_ledger.key(), 8 + 32, 4 * 32),
+
+        Argument::ArcisPubkey(referrer_enc_pubkey),
+        Argument::PlaintextU128(referrer_nonce),
+        Argument::Account(ctx.accounts.referrer_ledger.key(), 8 + 32, 4 * 32),
+
+        // Volume counters sit after owner, balances, balance_nonce and last_update
+        Argument::ArcisPubkey(user1_enc_pubkey),
+        Argument::PlaintextU128(buyer_ledger.volume_nonce),
+        Argument::Account(ctx.accounts.buyer_ledger.key(), 8 + 32 + 4 * 32 + 16 + 8, 2 * 32),
+
+        Argument::ArcisPubkey(user2_enc_pubkey),
+        Argument::PlaintextU128(seller_ledger.volume_nonce),
+        Argument::Account(ctx.accounts.seller_ledger.key(), 8 + 32 + 4 * 32 + 16 + 8, 2 * 32),
+
+        // The payer's order pays for its fill out of what it still has locked
+        Argument::PlaintextU128(ctx.accounts.payer_order.lock_nonce),
+        Argument::Account(
+            ctx.accounts.payer_order.key(),
+            ORDER_LOCK_OFFSET,
+            (ORDER_LOCK_CHUNKS * 32) as u32,
+        ),
+
         Argument::PlaintextU64(execution_price),
         Argument::PlaintextU8(is_base as u8),
+        Argument::PlaintextU8(ctx.accounts.payer_order.order_type),
+        Argument::PlaintextU64(fee_bps as u64),
+        Argument::PlaintextU64(referral_share_bps as u64),
+        Argument::PlaintextU64(tier_thresholds[0]),
+        Argument::PlaintextU64(tier_thresholds[1]),
+        Argument::PlaintextU64(tier_thresholds[2]),
+        Argument::PlaintextU64(tier_fees_bps[0] as u64),
+        Argument::PlaintextU64(tier_fees_bps[1] as u64),
+        Argument::PlaintextU64(tier_fees_bps[2] as u64),
+        Argument::PlaintextU8(windows_elapsed(buyer_ledger.volume_window, current_window)),
+        Argument::PlaintextU8(windows_elapsed(seller_ledger.volume_window, current_window)),
+        Argument::PlaintextU64(previous_window_weight_bps(now)),
+        Argument::PlaintextU64(current_window),
     ];
 
     ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
@@ -55,6 +167,18 @@ pub fn execute_settlement(
                 pubkey: ctx.accounts.seller_ledger.key(),
                 is_writable: true,
             },
+            CallbackAccount {
+                pubkey: ctx.accounts.fee_ledger.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.referrer_ledger.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.payer_order.key(),
+                is_writable: true,
+            },
         ])],
     )?;
 
@@ -66,8 +190,11 @@ pub fn execute_settlement(
 #[instruction(
     user1_enc_pubkey: [u8; 32],
     user2_enc_pubkey: [u8; 32],
+    fee_enc_pubkey: [u8; 32],
+    referrer_enc_pubkey: [u8; 32],
     execution_price: u64,
     is_base: bool,
+    receiver_is_maker: bool,
     computation_offset: u64,
 )]
 pub struct ExecuteSettlement<'info> {
@@ -112,4 +239,25 @@ pub struct ExecuteSettlement<'info> {
     #[account(mut)]
     pub seller_ledger: AccountLoader<'info, UserPrivateLedger>,
 
+    #[account(seeds = [ORDER_BOOK_STATE_SEED], bump)]
+    pub orderbook_state: AccountLoader<'info, OrderBookState>,
+    // Protocol fees accrue in the market authority's own ledger
+    #[account(
+        mut,
+        constraint = fee_ledger.load()?.owner == orderbook_state.load()?.authority @ ErrorCode::InvalidFeeLedger,
+    )]
+    pub fee_ledger: AccountLoader<'info, UserPrivateLedger>,
+    /// CHECK: The seller ledger's referral link, empty unless it was referred
+    #[account(seeds = [REFERRAL_LINK_SEED, seller_ledger.key().as_ref()], bump)]
+    pub referral_link: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub referrer_ledger: AccountLoader<'info, UserPrivateLedger>,
+    // The buyer ledger's order whose lock covers this leg, a grouped leg
+    // pays out of the lock its group's first leg holds
+    #[account(
+        mut,
+        constraint = payer_order.user == buyer_ledger.load()?.owner @ ErrorCode::UnauthorizedSettlement,
+        constraint = payer_order.checked == 1 @ ErrorCode::OrderNotChecked,
+    )]
+    pub payer_order: Box<Account<'info, OrderAccount>>,
 }
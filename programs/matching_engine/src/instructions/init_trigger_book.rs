@@ -0,0 +1,95 @@
+use crate::errors::ErrorCode;
+use crate::states::*;
+use crate::InitTriggerBookCallback;
+use crate::SignerAccount;
+use crate::COMP_DEF_OFFSET_INIT_TRIGGER_BOOK;
+use anchor_lang::prelude::*;
+use arcium_anchor::prelude::*;
+use arcium_client::idl::arcium::types::CallbackAccount;
+
+use crate::ID;
+use crate::ID_CONST;
+
+// Creates the market's trigger book and has the MXE encrypt an empty one into it
+pub fn init_trigger_book(
+    ctx: Context<InitTriggerBook>,
+    computation_offset: u64,
+    nonce: u128,
+) -> Result<()> {
+    let mut trigger_book = ctx.accounts.trigger_book_state.load_init()?;
+    trigger_book.orderbook = ctx.accounts.orderbook_state.key();
+    trigger_book.bump = ctx.bumps.trigger_book_state;
+
+    let args = vec![
+        Argument::PlaintextU128(nonce), // Initial nonce
+    ];
+
+    ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+    queue_computation(
+        ctx.accounts,
+        computation_offset,
+        args,
+        None,
+        vec![InitTriggerBookCallback::callback_ix(&[CallbackAccount {
+            pubkey: ctx.accounts.trigger_book_state.key(),
+            is_writable: true,
+        }])],
+    )?;
+
+    Ok(())
+}
+
+#[queue_computation_accounts("init_trigger_book", authority)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct InitTriggerBook<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = authority,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, SignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_INIT_TRIGGER_BOOK))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Account<'info, ClockAccount>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+
+    #[account(
+        constraint = orderbook_state.load()?.authority == authority.key() @ ErrorCode::UnauthorizedAuthority,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump,
+    )]
+    pub orderbook_state: AccountLoader<'info, OrderBookState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + TriggerBookState::INIT_SPACE,
+        seeds = [TRIGGER_BOOK_SEED, orderbook_state.key().as_ref()],
+        bump,
+    )]
+    pub trigger_book_state: AccountLoader<'info, TriggerBookState>,
+}
@@ -1,9 +1,9 @@
 use anchor_lang::{prelude::*, system_program};
-const ORDER_BOOK_STATE_SEED: &[u8] = b"order_book_state";
-use crate::{states::OrderBookState};
+use crate::states::{OrderBookState, ORDER_BOOK_STATE_SEED};
 use anchor_spl::token::{Token, TokenAccount, Mint};
 use crate::utils::*;
 use crate::errors::ErrorCode;
+use crate::instructions::FEE_VAULT_SEED;
 
 const VAULT_SEED: &[u8] = b"vault";
 
@@ -22,6 +22,9 @@ pub fn initialize(ctx: Context<Initialize>, backend_pubkey: [u8; 32], base_mint:
     order_book_state.backend_pubkey = backend_pubkey;
     order_book_state.base_mint = base_mint;
     order_book_state.quote_mint = quote_mint;
+    // Order amounts and prices are fixed point in the mints' own units
+    order_book_state.base_decimals = ctx.accounts.base_mint.decimals;
+    order_book_state.quote_decimals = ctx.accounts.quote_mint.decimals;
     order_book_state.total_orders_processed = 0;
     order_book_state.total_matches = 0;
     Ok(())
@@ -73,6 +76,26 @@ pub struct Initialize<'info> {
     )]
     pub quote_vault: Box<Account<'info, TokenAccount>>,
 
+    #[account(
+        init,
+        payer = authority,
+        seeds = [FEE_VAULT_SEED, base_mint.key().as_ref()],
+        bump,
+        token::mint = base_mint,
+        token::authority = vault_authority,
+    )]
+    pub base_fee_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [FEE_VAULT_SEED, quote_mint.key().as_ref()],
+        bump,
+        token::mint = quote_mint,
+        token::authority = vault_authority,
+    )]
+    pub quote_fee_vault: Box<Account<'info, TokenAccount>>,
+
     pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
 }
@@ -91,9 +114,8 @@ pub fn create_orderbook_state<'info>(
         &crate::id(),
     );
 
-    if orderbook_state.key() != expect_pda_address {
-        require_eq!(orderbook_state.is_signer, true);
-    }
+    // Every instruction finds the market at this PDA
+    require_keys_eq!(orderbook_state.key(), expect_pda_address, ErrorCode::NotApproved);
 
     token::create_or_allocate_account(
         &crate::id(),
@@ -18,6 +18,7 @@ pub fn initialize_user_ledger(
     ctx: Context<InitializeUserLedger>,
     user_pubkey: [u8; 32],
     user_nonce: u128,
+    owner_nonce: u128,
     computation_offset: u64,
 ) -> Result<()> {
     let user_ledger_loader = create_user_ledger(
@@ -37,6 +38,8 @@ pub fn initialize_user_ledger(
     let args = vec![
         Argument::ArcisPubkey(user_pubkey),
         Argument::PlaintextU128(user_nonce),
+        Argument::PlaintextU128(owner_nonce),
+        Argument::PlaintextU64(owner_tag(&ctx.accounts.user.key())),
     ];
 
     queue_computation(
@@ -57,7 +60,7 @@ pub fn initialize_user_ledger(
 
 #[queue_computation_accounts("init_user_ledger", user)]
 #[derive(Accounts)]
-#[instruction(user_pubkey: [u8; 32], user_nonce: u128, computation_offset: u64)]
+#[instruction(user_pubkey: [u8; 32], user_nonce: u128, owner_nonce: u128, computation_offset: u64)]
 pub struct InitializeUserLedger<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
@@ -104,6 +107,12 @@ pub struct InitializeUserLedger<'info> {
     pub user_ledger: UncheckedAccount<'info>,
 }
 
+// Self trade prevention only compares owners inside the circuits, a short
+// fingerprint of the ledger owner's pubkey is enough to seal for that
+pub fn owner_tag(user: &Pubkey) -> u64 {
+    u64::from_le_bytes(user.to_bytes()[0..8].try_into().unwrap())
+}
+
 pub fn create_user_ledger<'info>(
     payer: &AccountInfo<'info>,
     user: &AccountInfo<'info>,
@@ -0,0 +1,538 @@
+use crate::errors::ErrorCode;
+use crate::states::*;
+use crate::utils::*;
+use crate::{
+    ALLOCATION_PRO_RATA, MARKET_MODE_BATCH_AUCTION, MAX_FEE_BPS,
+    PRICE_BAND_SOURCE_ORACLE, PRICING_RULE_UNIFORM, STP_DECREMENT_AND_CANCEL,
+};
+use anchor_lang::prelude::*;
+
+// Points the market at the reference price account peg orders follow.
+// Passing the default pubkey removes the feed and parks every peg order.
+pub fn set_price_feed(ctx: Context<SetPriceFeed>) -> Result<()> {
+    let price_feed = match &ctx.accounts.price_feed {
+        Some(price_feed) => {
+            // Reject anything that doesn't parse as a price account up front
+            parse_pyth_price(&price_feed.try_borrow_data()?, Clock::get()?.unix_timestamp)?;
+            price_feed.key()
+        }
+        None => Pubkey::default(),
+    };
+
+    let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+    orderbook_state.price_feed = price_feed;
+
+    emit!(PriceFeedUpdatedEvent {
+        price_feed,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+// Chooses how match_orders prices a crossing pair, see PRICING_RULE_* in the circuits.
+// Uniform price markets match through trigger_uniform_matching instead
+pub fn set_pricing_rule(ctx: Context<UpdateMarketConfig>, pricing_rule: u8) -> Result<()> {
+    require!(pricing_rule <= PRICING_RULE_UNIFORM, ErrorCode::InvalidPricingRule);
+
+    let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+    orderbook_state.pricing_rule = pricing_rule;
+
+    emit!(PricingRuleUpdatedEvent {
+        pricing_rule,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+// Switches between continuous matching and frequent batch auctions. The
+// auction splits its margin by the same ALLOCATION_* modes as match_orders.
+pub fn set_market_mode(
+    ctx: Context<UpdateMarketConfig>,
+    market_mode: u8,
+    auction_allocation: u8,
+) -> Result<()> {
+    require!(market_mode <= MARKET_MODE_BATCH_AUCTION, ErrorCode::InvalidMarketMode);
+    require!(auction_allocation <= ALLOCATION_PRO_RATA, ErrorCode::InvalidAllocationMode);
+
+    let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+    orderbook_state.market_mode = market_mode;
+    orderbook_state.auction_allocation = auction_allocation;
+
+    emit!(MarketModeUpdatedEvent {
+        market_mode,
+        auction_allocation,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+// What match_orders does when both sides of a cross belong to the same owner
+pub fn set_stp_policy(ctx: Context<UpdateMarketConfig>, stp_policy: u8) -> Result<()> {
+    require!(stp_policy <= STP_DECREMENT_AND_CANCEL, ErrorCode::InvalidStpPolicy);
+
+    let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+    orderbook_state.stp_policy = stp_policy;
+
+    emit!(StpPolicyUpdatedEvent {
+        stp_policy,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+// Chooses how match_orders splits a price level, see ALLOCATION_* in the circuits.
+// top_priority_pct of each pro rata fill goes to the earliest order first.
+pub fn set_allocation_mode(
+    ctx: Context<UpdateMarketConfig>,
+    allocation_mode: u8,
+    top_priority_pct: u8,
+) -> Result<()> {
+    require!(allocation_mode <= ALLOCATION_PRO_RATA, ErrorCode::InvalidAllocationMode);
+    require!(top_priority_pct <= 100, ErrorCode::InvalidAllocationMode);
+
+    let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+    orderbook_state.allocation_mode = allocation_mode;
+    orderbook_state.top_priority_pct = top_priority_pct;
+
+    emit!(AllocationModeUpdatedEvent {
+        allocation_mode,
+        top_priority_pct,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+// Price grid limit orders must sit on, also the step post-only orders slide
+// by when they would cross
+pub fn set_tick_size(ctx: Context<UpdateMarketConfig>, tick_size: u64) -> Result<()> {
+    require!(tick_size > 0, ErrorCode::InvalidTickSize);
+
+    let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+    orderbook_state.tick_size = tick_size;
+
+    emit!(TickSizeUpdatedEvent {
+        tick_size,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+// Size grid and dust floor submit_order_check holds new orders to
+pub fn set_order_size_limits(
+    ctx: Context<UpdateMarketConfig>,
+    lot_size: u64,
+    min_notional: u64,
+) -> Result<()> {
+    require!(lot_size > 0, ErrorCode::InvalidLotSize);
+
+    let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+    orderbook_state.lot_size = lot_size;
+    orderbook_state.min_notional = min_notional;
+
+    emit!(OrderSizeLimitsUpdatedEvent {
+        lot_size,
+        min_notional,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+// Circuit breaker for match_orders: matches priced more than price_band_bps
+// away from the last traded or auction clearing price, or from the price
+// feed, are skipped, and a batch skipping band_halt_threshold crossings
+// halts matching
+pub fn set_price_band(
+    ctx: Context<UpdateMarketConfig>,
+    price_band_bps: u16,
+    price_band_source: u8,
+    band_halt_threshold: u8,
+) -> Result<()> {
+    require!(price_band_bps <= 10_000, ErrorCode::InvalidPriceBand);
+    require!(price_band_source <= PRICE_BAND_SOURCE_ORACLE, ErrorCode::InvalidPriceBand);
+
+    let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+    orderbook_state.price_band_bps = price_band_bps;
+    orderbook_state.price_band_source = price_band_source;
+    orderbook_state.band_halt_threshold = band_halt_threshold;
+
+    emit!(PriceBandUpdatedEvent {
+        price_band_bps,
+        price_band_source,
+        band_halt_threshold,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+// Order entry guard: new limit orders priced more than entry_band_bps away
+// from the price feed are rejected by submit_order_check
+pub fn set_entry_band(ctx: Context<UpdateMarketConfig>, entry_band_bps: u16) -> Result<()> {
+    require!(entry_band_bps <= 10_000, ErrorCode::InvalidPriceBand);
+
+    let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+    // The band is measured against the feed, a market without one can't use it
+    require!(
+        entry_band_bps == 0 || orderbook_state.price_feed != Pubkey::default(),
+        ErrorCode::InvalidPriceFeed
+    );
+    orderbook_state.entry_band_bps = entry_band_bps;
+
+    emit!(EntryBandUpdatedEvent {
+        entry_band_bps,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+// Settlement fees, taken from what each side receives and credited to the
+// authority's ledger
+pub fn set_fee_rates(
+    ctx: Context<UpdateMarketConfig>,
+    maker_fee_bps: u16,
+    taker_fee_bps: u16,
+) -> Result<()> {
+    require!(
+        maker_fee_bps <= MAX_FEE_BPS && taker_fee_bps <= MAX_FEE_BPS,
+        ErrorCode::InvalidFeeRate
+    );
+
+    let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+    orderbook_state.maker_fee_bps = maker_fee_bps;
+    orderbook_state.taker_fee_bps = taker_fee_bps;
+
+    emit!(FeeRatesUpdatedEvent {
+        maker_fee_bps,
+        taker_fee_bps,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+// Volume discounts: a side whose trailing 30 day quote volume reaches
+// thresholds[i] pays the tier's rate instead of the base one. The comparison
+// happens in the settlement circuit, nobody learns which tier applied.
+pub fn set_fee_tiers(
+    ctx: Context<UpdateMarketConfig>,
+    thresholds: [u64; FEE_TIERS],
+    maker_tier_fee_bps: [u16; FEE_TIERS],
+    taker_tier_fee_bps: [u16; FEE_TIERS],
+) -> Result<()> {
+    for i in 0..FEE_TIERS {
+        require!(
+            maker_tier_fee_bps[i] <= MAX_FEE_BPS && taker_tier_fee_bps[i] <= MAX_FEE_BPS,
+            ErrorCode::InvalidFeeRate
+        );
+        if i > 0 {
+            require!(
+                thresholds[i] == 0 || (thresholds[i - 1] > 0 && thresholds[i] > thresholds[i - 1]),
+                ErrorCode::InvalidFeeTiers
+            );
+        }
+    }
+
+    let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+    orderbook_state.fee_tier_thresholds = thresholds;
+    orderbook_state.maker_tier_fee_bps = maker_tier_fee_bps;
+    orderbook_state.taker_tier_fee_bps = taker_tier_fee_bps;
+
+    emit!(FeeTiersUpdatedEvent {
+        thresholds,
+        maker_tier_fee_bps,
+        taker_tier_fee_bps,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+// Share of a referred side's settlement fee paid to its referrer
+pub fn set_referral_share(ctx: Context<UpdateMarketConfig>, referral_share_bps: u16) -> Result<()> {
+    require!(referral_share_bps <= 10_000, ErrorCode::InvalidReferralShare);
+
+    let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+    orderbook_state.referral_share_bps = referral_share_bps;
+
+    emit!(ReferralShareUpdatedEvent {
+        referral_share_bps,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+// Plaintext fees kept from deposits and withdrawals, swept by collect_fees
+pub fn set_transfer_fees(
+    ctx: Context<UpdateMarketConfig>,
+    deposit_fee_bps: u16,
+    withdraw_fee_bps: u16,
+) -> Result<()> {
+    require!(
+        deposit_fee_bps <= MAX_FEE_BPS && withdraw_fee_bps <= MAX_FEE_BPS,
+        ErrorCode::InvalidFeeRate
+    );
+
+    let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+    orderbook_state.deposit_fee_bps = deposit_fee_bps;
+    orderbook_state.withdraw_fee_bps = withdraw_fee_bps;
+
+    emit!(TransferFeesUpdatedEvent {
+        deposit_fee_bps,
+        withdraw_fee_bps,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+// Lamports every new order escrows, refunded once it is done trading and
+// slashed when it was rejected for insufficient balance
+pub fn set_order_bond(ctx: Context<UpdateMarketConfig>, order_bond_lamports: u64) -> Result<()> {
+    let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+    orderbook_state.order_bond_lamports = order_bond_lamports;
+
+    emit!(OrderBondUpdatedEvent {
+        order_bond_lamports,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+// Per wallet order entry caps: open orders at once, and submissions per
+// submission_window seconds
+pub fn set_order_limits(
+    ctx: Context<UpdateMarketConfig>,
+    max_open_orders: u16,
+    max_submissions_per_window: u16,
+    submission_window: u32,
+) -> Result<()> {
+    require!(
+        max_submissions_per_window == 0 || submission_window > 0,
+        ErrorCode::InvalidOrderLimits
+    );
+
+    let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+    orderbook_state.max_open_orders = max_open_orders;
+    orderbook_state.max_submissions_per_window = max_submissions_per_window;
+    orderbook_state.submission_window = submission_window;
+
+    emit!(OrderLimitsUpdatedEvent {
+        max_open_orders,
+        max_submissions_per_window,
+        submission_window,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+// Lifts a circuit breaker halt
+pub fn resume_market(ctx: Context<UpdateMarketConfig>) -> Result<()> {
+    let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+    orderbook_state.halted = 0;
+
+    emit!(MarketResumedEvent {
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+// Daily trading sessions, in seconds after UTC midnight. Each boundary starts
+// the next phase: pre-open, opening auction, continuous, closing auction,
+// and closed from close_time until the next pre-open. Equal boundaries skip
+// a phase, e.g. no opening auction when it starts with continuous trading.
+pub fn set_trading_sessions(
+    ctx: Context<UpdateMarketConfig>,
+    session_enabled: bool,
+    pre_open_time: u32,
+    opening_auction_time: u32,
+    continuous_time: u32,
+    closing_auction_time: u32,
+    close_time: u32,
+) -> Result<()> {
+    require!(
+        pre_open_time <= opening_auction_time
+            && opening_auction_time <= continuous_time
+            && continuous_time <= closing_auction_time
+            && closing_auction_time <= close_time
+            && close_time <= 86_400,
+        ErrorCode::InvalidSessionSchedule
+    );
+
+    let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+    orderbook_state.session_enabled = session_enabled as u8;
+    orderbook_state.pre_open_time = pre_open_time;
+    orderbook_state.opening_auction_time = opening_auction_time;
+    orderbook_state.continuous_time = continuous_time;
+    orderbook_state.closing_auction_time = closing_auction_time;
+    orderbook_state.close_time = close_time;
+
+    emit!(TradingSessionsUpdatedEvent {
+        session_enabled,
+        pre_open_time,
+        opening_auction_time,
+        continuous_time,
+        closing_auction_time,
+        close_time,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct UpdateMarketConfig<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = orderbook_state.load()?.authority == authority.key() @ ErrorCode::UnauthorizedAuthority,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump,
+    )]
+    pub orderbook_state: AccountLoader<'info, OrderBookState>,
+}
+
+#[derive(Accounts)]
+pub struct SetPriceFeed<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = orderbook_state.load()?.authority == authority.key() @ ErrorCode::UnauthorizedAuthority,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump,
+    )]
+    pub orderbook_state: AccountLoader<'info, OrderBookState>,
+
+    /// CHECK: Pyth price account, layout is checked in the handler.
+    pub price_feed: Option<UncheckedAccount<'info>>,
+}
+
+#[event]
+pub struct PriceFeedUpdatedEvent {
+    pub price_feed: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PricingRuleUpdatedEvent {
+    pub pricing_rule: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MarketModeUpdatedEvent {
+    pub market_mode: u8,
+    pub auction_allocation: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct StpPolicyUpdatedEvent {
+    pub stp_policy: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AllocationModeUpdatedEvent {
+    pub allocation_mode: u8,
+    pub top_priority_pct: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TickSizeUpdatedEvent {
+    pub tick_size: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OrderSizeLimitsUpdatedEvent {
+    pub lot_size: u64,
+    pub min_notional: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TradingSessionsUpdatedEvent {
+    pub session_enabled: bool,
+    pub pre_open_time: u32,
+    pub opening_auction_time: u32,
+    pub continuous_time: u32,
+    pub closing_auction_time: u32,
+    pub close_time: u32,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PriceBandUpdatedEvent {
+    pub price_band_bps: u16,
+    pub price_band_source: u8,
+    pub band_halt_threshold: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EntryBandUpdatedEvent {
+    pub entry_band_bps: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FeeRatesUpdatedEvent {
+    pub maker_fee_bps: u16,
+    pub taker_fee_bps: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FeeTiersUpdatedEvent {
+    pub thresholds: [u64; FEE_TIERS],
+    pub maker_tier_fee_bps: [u16; FEE_TIERS],
+    pub taker_tier_fee_bps: [u16; FEE_TIERS],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ReferralShareUpdatedEvent {
+    pub referral_share_bps: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TransferFeesUpdatedEvent {
+    pub deposit_fee_bps: u16,
+    pub withdraw_fee_bps: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OrderBondUpdatedEvent {
+    pub order_bond_lamports: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OrderLimitsUpdatedEvent {
+    pub max_open_orders: u16,
+    pub max_submissions_per_window: u16,
+    pub submission_window: u32,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MarketResumedEvent {
+    pub timestamp: i64,
+}
@@ -4,6 +4,9 @@ pub use initialize::*;
 pub mod trigger_matching;
 pub use trigger_matching::*;
 
+pub mod trigger_uniform_matching;
+pub use trigger_uniform_matching::*;
+
 pub mod arcium;
 pub use arcium::*;
 
@@ -22,8 +25,56 @@ pub use deposit_to_vault::*;
 pub mod withdraw_from_ledger_verify;
 pub use withdraw_from_ledger_verify::*;
 
+pub mod withdraw_protocol_fees;
+pub use withdraw_protocol_fees::*;
+
 pub mod withdraw_from_vault;
 pub use withdraw_from_vault::*;
 
 pub mod submit_order_check;
-pub use submit_order_check::*;
\ No newline at end of file
+pub use submit_order_check::*;
+
+pub mod release_order_lock;
+pub use release_order_lock::*;
+
+pub mod market_config;
+pub use market_config::*;
+
+pub mod trigger_batch_auction;
+pub use trigger_batch_auction::*;
+
+pub mod init_trigger_book;
+pub use init_trigger_book::*;
+
+pub mod submit_stop_order;
+pub use submit_stop_order::*;
+
+pub mod evaluate_triggers;
+pub use evaluate_triggers::*;
+
+pub mod order_group;
+pub use order_group::*;
+
+pub mod submit_twap_order;
+pub use submit_twap_order::*;
+
+pub mod release_twap_slice;
+pub use release_twap_slice::*;
+
+pub mod create_recurring_order;
+pub use create_recurring_order::*;
+
+pub mod execute_recurring_order;
+pub use execute_recurring_order::*;
+
+pub mod referral;
+pub use referral::*;
+
+pub mod collect_fees;
+pub use collect_fees::*;
+
+pub mod order_bond;
+pub use order_bond::*;
+
+pub mod close_open_order;
+pub use close_open_order::*;
@@ -0,0 +1,76 @@
+use crate::errors::ErrorCode;
+use crate::states::*;
+use anchor_lang::prelude::*;
+
+// public key for the settlement/cranker bot
+const CRANKER_BOT_PUBKEY: Pubkey = pubkey!("8wJE7H7svhpz1Jnzbne3YErWFVeWNWGRbAkDQ8xeixoY");
+
+// Pays out an order's anti-spam bond once the backend sees it filled,
+// cancelled or expired. Orders the check rejected for insufficient balance
+// are slashed instead, their bond goes to the market authority, an order the
+// check accepted is never slashed. Either way the order has to be out of the
+// book first: closed by close_open_order or by the check callback that
+// rejected it.
+pub fn release_order_bond(ctx: Context<ReleaseOrderBond>, slash: bool) -> Result<()> {
+    let bond = ctx.accounts.order_account.bond;
+    require!(bond > 0, ErrorCode::NoOrderBond);
+    require!(ctx.accounts.order_account.open == 0, ErrorCode::OrderStillOpen);
+    require!(
+        !slash || ctx.accounts.order_account.checked == 0,
+        ErrorCode::CheckedOrderBond
+    );
+    ctx.accounts.order_account.bond = 0;
+
+    let recipient = if slash {
+        ctx.accounts.protocol_treasury.to_account_info()
+    } else {
+        ctx.accounts.user.to_account_info()
+    };
+
+    // The order account is owned by this program, lamports move directly
+    **ctx
+        .accounts
+        .order_account
+        .to_account_info()
+        .try_borrow_mut_lamports()? -= bond;
+    **recipient.try_borrow_mut_lamports()? += bond;
+
+    emit!(OrderBondReleasedEvent {
+        order_id: ctx.accounts.order_account.order_id,
+        user: ctx.accounts.order_account.user,
+        amount: bond,
+        slashed: slash,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct ReleaseOrderBond<'info> {
+    #[account(address = CRANKER_BOT_PUBKEY)]
+    pub payer: Signer<'info>,
+
+    #[account(mut)]
+    pub order_account: Box<Account<'info, OrderAccount>>,
+
+    /// CHECK: Owner of the order, receives a refunded bond
+    #[account(mut, address = order_account.user)]
+    pub user: UncheckedAccount<'info>,
+
+    #[account(seeds = [ORDER_BOOK_STATE_SEED], bump)]
+    pub orderbook_state: AccountLoader<'info, OrderBookState>,
+
+    /// CHECK: Market authority, receives a slashed bond
+    #[account(mut, address = orderbook_state.load()?.authority)]
+    pub protocol_treasury: UncheckedAccount<'info>,
+}
+
+#[event]
+pub struct OrderBondReleasedEvent {
+    pub order_id: u64,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub slashed: bool,
+    pub timestamp: i64,
+}
@@ -0,0 +1,96 @@
+use crate::errors::ErrorCode;
+use crate::states::*;
+use crate::{GROUP_ROLE_NONE, GROUP_TYPE_BRACKET};
+use anchor_lang::prelude::*;
+
+// Opens an empty order group. Legs join it when they are submitted through
+// submit_order_check with the group account attached.
+pub fn create_order_group(
+    ctx: Context<CreateOrderGroup>,
+    group_id: u64,
+    group_type: u8,
+) -> Result<()> {
+    // Group id 0 marks ungrouped orders inside the circuits
+    require!(group_id > 0, ErrorCode::InvalidGroupType);
+    require!(group_type <= GROUP_TYPE_BRACKET, ErrorCode::InvalidGroupType);
+
+    let order_group = &mut ctx.accounts.order_group;
+    order_group.group_id = group_id;
+    order_group.owner = ctx.accounts.user.key();
+    order_group.group_type = group_type;
+    order_group.leg_order_ids = [0u64; MAX_GROUP_LEGS];
+    order_group.leg_count = 0;
+    order_group.created_at = Clock::get()?.unix_timestamp;
+    order_group.bump = ctx.bumps.order_group;
+    order_group.lock_status = GROUP_LOCK_PENDING;
+    order_group.lock_order_type = 0;
+    order_group.lock_nonce = 0;
+    order_group.encrypted_lock = [[0u8; 32]; ORDER_LOCK_CHUNKS];
+
+    emit!(OrderGroupCreatedEvent {
+        group_id,
+        owner: order_group.owner,
+        group_type,
+        timestamp: order_group.created_at,
+    });
+
+    Ok(())
+}
+
+// Adds the order being checked to its group and works out the role the
+// circuits give it. Returns (group_id, group_role, lock_shared). Later legs
+// are only taken once the first leg's check has locked its collateral.
+// Group ids are only unique per owner, so the circuits know a group by its
+// first leg's order id instead, which is unique across the market.
+pub fn join_order_group(
+    order_group: Option<&mut Account<OrderGroup>>,
+    user: &Pubkey,
+    order_id: u64,
+) -> Result<(u64, u8, bool)> {
+    match order_group {
+        Some(group) => {
+            require_keys_eq!(group.owner, *user, ErrorCode::UnauthorizedGroup);
+            require!((group.leg_count as usize) < MAX_GROUP_LEGS, ErrorCode::OrderGroupFull);
+
+            let role = group.next_leg_role();
+            let lock_shared = group.leg_count > 0;
+            // Order id 0 would read as ungrouped inside the circuits
+            require!(lock_shared || order_id > 0, ErrorCode::InvalidGroupOrderId);
+            // A later leg leans on the first leg's lock, so that lock has to exist
+            if lock_shared {
+                require!(group.lock_status == GROUP_LOCK_READY, ErrorCode::GroupLockNotReady);
+            }
+            group.leg_order_ids[group.leg_count as usize] = order_id;
+            group.leg_count += 1;
+
+            Ok((group.leg_order_ids[0], role, lock_shared))
+        }
+        None => Ok((0, GROUP_ROLE_NONE, false)),
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(group_id: u64)]
+pub struct CreateOrderGroup<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        init,
+        payer = user,
+        space = 8 + OrderGroup::INIT_SPACE,
+        seeds = [ORDER_GROUP_SEED, user.key().as_ref(), group_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub order_group: Account<'info, OrderGroup>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event]
+pub struct OrderGroupCreatedEvent {
+    pub group_id: u64,
+    pub owner: Pubkey,
+    pub group_type: u8,
+    pub timestamp: i64,
+}
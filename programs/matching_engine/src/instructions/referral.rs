@@ -0,0 +1,55 @@
+use crate::errors::ErrorCode;
+use crate::states::*;
+use anchor_lang::prelude::*;
+
+// Links the caller's ledger to a referrer's. A ledger can be referred once,
+// the link can't be changed afterwards.
+pub fn register_referral(ctx: Context<RegisterReferral>) -> Result<()> {
+    let referee = ctx.accounts.user.key();
+    let referrer = ctx.accounts.referrer_ledger.load()?.owner;
+    require_keys_neq!(referrer, referee, ErrorCode::InvalidReferrer);
+    require_keys_eq!(ctx.accounts.user_ledger.load()?.owner, referee, ErrorCode::InvalidReferrer);
+
+    let referral_link = &mut ctx.accounts.referral_link;
+    referral_link.referee = referee;
+    referral_link.referee_ledger = ctx.accounts.user_ledger.key();
+    referral_link.referrer = referrer;
+    referral_link.referrer_ledger = ctx.accounts.referrer_ledger.key();
+    referral_link.created_at = Clock::get()?.unix_timestamp;
+    referral_link.bump = ctx.bumps.referral_link;
+
+    emit!(ReferralRegisteredEvent {
+        referee,
+        referrer,
+        timestamp: referral_link.created_at,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct RegisterReferral<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub user_ledger: AccountLoader<'info, UserPrivateLedger>,
+    pub referrer_ledger: AccountLoader<'info, UserPrivateLedger>,
+
+    #[account(
+        init,
+        payer = user,
+        space = 8 + ReferralLink::INIT_SPACE,
+        seeds = [REFERRAL_LINK_SEED, user_ledger.key().as_ref()],
+        bump,
+    )]
+    pub referral_link: Account<'info, ReferralLink>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event]
+pub struct ReferralRegisteredEvent {
+    pub referee: Pubkey,
+    pub referrer: Pubkey,
+    pub timestamp: i64,
+}
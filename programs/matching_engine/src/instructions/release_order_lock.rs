@@ -0,0 +1,133 @@
+use crate::errors::ErrorCode;
+use crate::states::*;
+use crate::SignerAccount;
+use crate::ReleaseOrderLockCallback;
+use crate::COMP_DEF_OFFSET_RELEASE_ORDER_LOCK;
+use anchor_lang::prelude::*;
+use arcium_anchor::prelude::*;
+use arcium_client::idl::arcium::types::CallbackAccount;
+
+use crate::ID;
+use crate::ID_CONST;
+
+// public key for the settlement/cranker bot
+const CRANKER_BOT_PUBKEY: Pubkey = pubkey!("8wJE7H7svhpz1Jnzbne3YErWFVeWNWGRbAkDQ8xeixoY");
+
+// Hands a cancelled order's remaining lock back to the user's available balance.
+// The amount comes from the releases the backend decrypts out of a match result
+// and is capped in the circuit by what the order's lock record still holds,
+// which settlement shrinks with every fill and each release with what it returns.
+pub fn release_order_lock(
+    ctx: Context<ReleaseOrderLock>,
+    user_enc_pubkey: [u8; 32],
+    order_id: u64,
+    amount: u64,
+    order_type: u8,
+    computation_offset: u64,
+) -> Result<()> {
+    require!(order_type <= 1, ErrorCode::InvalidOrderType);
+
+    let order_account = &ctx.accounts.order_account;
+    require!(order_account.checked == 1, ErrorCode::OrderNotChecked);
+    require!(order_account.order_type == order_type, ErrorCode::OrderCheckMismatch);
+
+    let user_ledger = ctx.accounts.user_ledger.load_mut()?;
+    let args = vec![
+        Argument::ArcisPubkey(user_enc_pubkey),
+        Argument::PlaintextU128(user_ledger.balance_nonce),
+        Argument::Account(ctx.accounts.user_ledger.key(), 8 + 32, 4 * 32),
+
+        Argument::PlaintextU128(ctx.accounts.order_account.lock_nonce),
+        Argument::Account(
+            ctx.accounts.order_account.key(),
+            ORDER_LOCK_OFFSET,
+            (ORDER_LOCK_CHUNKS * 32) as u32,
+        ),
+
+        Argument::PlaintextU64(amount),
+        Argument::PlaintextU8(order_type),
+    ];
+
+    ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+    queue_computation(
+        ctx.accounts,
+        computation_offset,
+        args,
+        None,
+        vec![ReleaseOrderLockCallback::callback_ix(&[
+            CallbackAccount {
+                pubkey: ctx.accounts.user_ledger.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.order_account.key(),
+                is_writable: true,
+            },
+        ])],
+    )?;
+
+    msg!("Releasing lock of order {}", order_id);
+
+    Ok(())
+}
+
+#[queue_computation_accounts("release_order_lock", user)]
+#[derive(Accounts)]
+#[instruction(
+    user_enc_pubkey: [u8; 32],
+    order_id: u64,
+    amount: u64,
+    order_type: u8,
+    computation_offset: u64,
+)]
+pub struct ReleaseOrderLock<'info> {
+    #[account(
+        mut,
+        address = CRANKER_BOT_PUBKEY,
+    )]
+    pub user: Signer<'info>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = user,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, SignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_RELEASE_ORDER_LOCK))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Account<'info, ClockAccount>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+
+    #[account(
+        mut,
+        seeds = [b"order", order_id.to_le_bytes().as_ref()],
+        bump = order_account.bump,
+    )]
+    pub order_account: Box<Account<'info, OrderAccount>>,
+
+    #[account(
+        mut,
+        constraint = user_ledger.load()?.owner == order_account.user @ ErrorCode::UnauthorizedSettlement,
+    )]
+    pub user_ledger: AccountLoader<'info, UserPrivateLedger>,
+}
@@ -0,0 +1,218 @@
+use crate::errors::ErrorCode;
+use crate::states::*;
+use crate::utils::{price_scale, require_accepting_orders};
+use crate::ReleaseTwapSliceCallback;
+use crate::SignerAccount;
+use crate::COMP_DEF_OFFSET_RELEASE_TWAP_SLICE;
+use crate::ORDER_KIND_LIMIT;
+use anchor_lang::prelude::*;
+use arcium_anchor::prelude::*;
+use arcium_client::idl::arcium::types::CallbackAccount;
+
+use crate::ID;
+use crate::ID_CONST;
+
+// Crank that moves the next slice of a TWAP parent into the order book, at
+// most once per matching interval. Past the parent's end time it releases
+// nothing and unlocks whatever is left instead. Each slice gets an order
+// account of its own, with its share of the parent's lock, so its fills and
+// releases never touch another slice's.
+pub fn release_twap_slice(
+    ctx: Context<ReleaseTwapSlice>,
+    computation_offset: u64,
+    slice_order_id: u64,
+    lock_nonce: u128,
+) -> Result<()> {
+    let orderbook_state = ctx.accounts.orderbook_state.load()?;
+    let mut twap_order = ctx.accounts.twap_order_state.load_mut()?;
+    let user_ledger = ctx.accounts.user_ledger.load()?;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    require!(twap_order.completed == 0, ErrorCode::TwapOrderCompleted);
+    // Same cadence as matching, so every batch sees at most one slice
+    require!(
+        current_time >= twap_order.last_release_timestamp + 15,
+        ErrorCode::MatchingTooFrequent
+    );
+    twap_order.last_release_timestamp = current_time;
+
+    let expected_slice_order_id = twap_order
+        .next_slice_order_id()
+        .ok_or(ErrorCode::InvalidTwapOrder)?;
+    require!(slice_order_id == expected_slice_order_id, ErrorCode::InvalidTwapOrder);
+    twap_order.slices_created += 1;
+
+    let expired = current_time >= twap_order.end_time;
+    // An expired parent still unlocks its remainder while the market is closed
+    // and the owner is at their caps. Otherwise the slice is an order of the
+    // owner's and counts against their caps like any other.
+    if !expired {
+        require_accepting_orders(&orderbook_state, current_time)?;
+        ctx.accounts.user_trading_state.record_submission(
+            current_time,
+            orderbook_state.max_open_orders,
+            orderbook_state.max_submissions_per_window,
+            orderbook_state.submission_window,
+        )?;
+    }
+
+    let slice_order = &mut ctx.accounts.slice_order_account;
+    slice_order.order_id = slice_order_id;
+    slice_order.user = twap_order.user;
+    slice_order.user_enc_pubkey = twap_order.user_enc_pubkey;
+    slice_order.order_type = twap_order.order_type;
+    slice_order.order_kind = ORDER_KIND_LIMIT;
+    slice_order.timestamp = current_time;
+    // Placed by the circuit from the parent's lock, there is no separate check
+    slice_order.checked = 1;
+    slice_order.submitted = 1;
+    slice_order.open = if expired { 0 } else { 1 };
+    slice_order.bump = ctx.bumps.slice_order_account;
+
+    let order_id = slice_order_id;
+
+    let args = vec![
+        // Enc<Mxe, TwapParent>
+        Argument::PlaintextU128(twap_order.twap_nonce),
+        Argument::Account(
+            ctx.accounts.twap_order_state.key(),
+            8 + 32, // Offset: discriminator(8) + orderbook(32)
+            (TWAP_PARENT_CHUNKS * 32) as u32,
+        ),
+
+        // Enc<Mxe, OrderBookFlat>
+        Argument::PlaintextU128(orderbook_state.orderbook_nonce),
+        Argument::Account(
+            ctx.accounts.orderbook_state.key(),
+            8 + 32,
+            (ORDER_BOOK_CHUNKS * 32) as u32,
+        ),
+
+        // Enc<Shared, Balances>
+        Argument::ArcisPubkey(twap_order.user_enc_pubkey),
+        Argument::PlaintextU128(user_ledger.balance_nonce),
+        Argument::Account(
+            ctx.accounts.user_ledger.key(),
+            8 + 32,          // Offset: discriminator + owner
+            4 * 32,          // Size: 4 chunks
+        ),
+
+        Argument::PlaintextU64(order_id),
+        Argument::PlaintextU8(twap_order.order_type),
+
+        // Enc<Mxe, u64>
+        Argument::PlaintextU128(user_ledger.owner_nonce),
+        Argument::Account(ctx.accounts.user_ledger.key(), LEDGER_OWNER_OFFSET, 32),
+
+        Argument::PlaintextU8(expired as u8),
+        Argument::PlaintextU64(price_scale(orderbook_state.base_decimals)?),
+        Argument::PlaintextU64(current_time as u64),
+
+        // Mxe, the slice's lock record
+        Argument::PlaintextU128(lock_nonce),
+    ];
+
+    ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+    queue_computation(
+        ctx.accounts,
+        computation_offset,
+        args,
+        None,
+        vec![ReleaseTwapSliceCallback::callback_ix(&[
+            CallbackAccount {
+                pubkey: ctx.accounts.twap_order_state.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.orderbook_state.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.user_ledger.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.slice_order_account.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.user_trading_state.key(),
+                is_writable: true,
+            },
+        ])],
+    )?;
+
+    msg!("TWAP slice {} release queued", order_id);
+
+    Ok(())
+}
+
+#[queue_computation_accounts("release_twap_slice", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64, slice_order_id: u64)]
+pub struct ReleaseTwapSlice<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, SignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_RELEASE_TWAP_SLICE))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Account<'info, ClockAccount>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        mut,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump,
+    )]
+    pub orderbook_state: AccountLoader<'info, OrderBookState>,
+    #[account(
+        mut,
+        constraint = twap_order_state.load()?.orderbook == orderbook_state.key() @ ErrorCode::InvalidTwapOrder,
+    )]
+    pub twap_order_state: AccountLoader<'info, TwapOrderState>,
+    #[account(
+        mut,
+        constraint = user_ledger.load()?.owner == twap_order_state.load()?.user @ ErrorCode::UnauthorizedSettlement,
+    )]
+    pub user_ledger: AccountLoader<'info, UserPrivateLedger>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + OrderAccount::INIT_SPACE,
+        seeds = [b"order", slice_order_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub slice_order_account: Box<Account<'info, OrderAccount>>,
+    // Opened by the parent's submit_order_check
+    #[account(
+        mut,
+        seeds = [USER_TRADING_STATE_SEED, twap_order_state.load()?.user.as_ref()],
+        bump = user_trading_state.bump,
+    )]
+    pub user_trading_state: Box<Account<'info, UserTradingState>>,
+}
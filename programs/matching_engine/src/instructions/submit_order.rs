@@ -1,8 +1,10 @@
 use crate::errors::ErrorCode;
 use crate::states::*;
+use crate::utils::{price_scale, require_accepting_orders};
 use crate::SignerAccount;
 use crate::SubmitOrderCallback;
 use crate::COMP_DEF_OFFSET_SUBMIT_ORDER;
+use crate::{GROUP_ROLE_EXIT, ORDER_KIND_LIMIT, ORDER_KIND_PEG, POST_ONLY_NONE, POST_ONLY_SLIDE};
 use anchor_lang::prelude::*;
 use anchor_spl::token::TokenAccount;
 use anchor_spl::token_interface::Mint;
@@ -19,15 +21,38 @@ pub fn submit_order(
     ctx: Context<SubmitOrder>,
     amount: [u8; 32],
     price: [u8; 32],
+    quote_budget: [u8; 32],
+    min_fill_qty: [u8; 32],
+    display_size: [u8; 32],
     user_pubkey: [u8; 32], // x25519 public key
     order_type: u8, // 0 = buy, 1 = sell
+    order_kind: u8, // 0 = limit, 1 = market, 2 = peg
+    post_only: u8, // 0 = off, 1 = reject if crossing, 2 = slide one tick if crossing
     computation_offset: u64,
     order_id: u64,
     order_nonce: u128,
 ) -> Result<()> {
+    // Stop kinds go through submit_stop_order instead
+    require!(order_kind <= ORDER_KIND_PEG, ErrorCode::InvalidOrderKind);
+    // Bracket exits wait in the trigger book until their entry fills
+    let order_account = &mut ctx.accounts.order_account;
+    require!(order_account.group_role != GROUP_ROLE_EXIT, ErrorCode::InvalidGroupLeg);
+    // Each checked order goes into the book once, as the side and kind it was checked as
+    order_account.take_for_submission(order_type, order_kind)?;
+    let order_account = &ctx.accounts.order_account;
+    // Only limit orders rest at a price of their own. Grouped legs share one
+    // lock, so there is nothing of their own to refund on a reject.
+    require!(post_only <= POST_ONLY_SLIDE, ErrorCode::InvalidPostOnly);
+    require!(
+        post_only == POST_ONLY_NONE || (order_kind == ORDER_KIND_LIMIT && order_account.group_id == 0),
+        ErrorCode::InvalidPostOnly
+    );
 
     ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
     let orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+    // The session may have closed since submit_order_check
+    require_accepting_orders(&orderbook_state, Clock::get()?.unix_timestamp)?;
+    let user_ledger = ctx.accounts.user_ledger.load()?;
     
     let args = vec![        
 
@@ -36,17 +61,50 @@ pub fn submit_order(
         Argument::PlaintextU128(order_nonce),
         Argument::EncryptedU64(amount), // Client encrypts this
         Argument::EncryptedU64(price),  // Client encrypts this
+        Argument::EncryptedU64(quote_budget), // Client encrypts this (0 unless market or peg buy)
+        Argument::EncryptedU64(min_fill_qty), // Client encrypts this (0 = any fill size)
+        Argument::EncryptedU64(display_size), // Client encrypts this (iceberg clip, 0 = fully displayed)
 
         // Enc<Mxe, OrderBook>
         Argument::PlaintextU128(orderbook_state.orderbook_nonce),
         Argument::Account(
             ctx.accounts.orderbook_state.key(),
             8 + 32,      // Offset: discriminator(8) + authority(32) = 40
-            18 * 32,     // Size: 18 chunks × 32 bytes = 576 bytes
+            (ORDER_BOOK_CHUNKS * 32) as u32, // Size: ORDER_BOOK_CHUNKS chunks × 32 bytes
+        ),
+
+        // Enc<Shared, Balances>
+        Argument::ArcisPubkey(user_pubkey),
+        Argument::PlaintextU128(user_ledger.balance_nonce),
+        Argument::Account(
+            ctx.accounts.user_ledger.key(),
+            8 + 32,          // Offset: discriminator + owner
+            4 * 32,          // Size: 4 chunks
+        ),
+
+        // Enc<Mxe, OrderLock>
+        Argument::PlaintextU128(order_account.lock_nonce),
+        Argument::Account(
+            order_account.key(),
+            ORDER_LOCK_OFFSET,
+            (ORDER_LOCK_CHUNKS * 32) as u32,
         ),
 
         Argument::PlaintextU64(order_id),
         Argument::PlaintextU8(order_type),
+        Argument::PlaintextU8(order_kind),
+
+        // Enc<Mxe, u64>
+        Argument::PlaintextU128(user_ledger.owner_nonce),
+        Argument::Account(ctx.accounts.user_ledger.key(), LEDGER_OWNER_OFFSET, 32),
+
+        Argument::PlaintextU64(order_account.group_id),
+        Argument::PlaintextU8(order_account.group_role),
+        Argument::PlaintextU8(post_only),
+        Argument::PlaintextU64(orderbook_state.tick_size.max(1)),
+        Argument::PlaintextU64(orderbook_state.lot_size.max(1)),
+        Argument::PlaintextU64(orderbook_state.min_notional),
+        Argument::PlaintextU64(price_scale(orderbook_state.base_decimals)?),
         Argument::PlaintextU64(Clock::get()?.unix_timestamp as u64),
     ];
 
@@ -60,6 +118,14 @@ pub fn submit_order(
                 pubkey: ctx.accounts.orderbook_state.key(),
                 is_writable: true,
             },
+            CallbackAccount {
+                pubkey: ctx.accounts.user_ledger.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.order_account.key(),
+                is_writable: true,
+            },
         ])],
     )?;
 
@@ -72,8 +138,13 @@ pub fn submit_order(
 #[instruction(
     amount: [u8; 32],
     price: [u8; 32],
+    quote_budget: [u8; 32],
+    min_fill_qty: [u8; 32],
+    display_size: [u8; 32],
     user_pubkey: [u8; 32],
     order_type: u8,
+    order_kind: u8,
+    post_only: u8,
     computation_offset: u64,
     order_id: u64,
     order_nonce: u128,
@@ -122,6 +193,24 @@ pub struct SubmitOrder<'info> {
     )]
     pub vault: Account<'info, TokenAccount>,
 
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump,
+    )]
     pub orderbook_state: AccountLoader<'info, OrderBookState>,
+
+    #[account(
+        mut,
+        seeds = [b"order", order_id.to_le_bytes().as_ref()],
+        bump = order_account.bump,
+        constraint = order_account.user == user.key() @ ErrorCode::UnauthorizedSettlement,
+    )]
+    pub order_account: Box<Account<'info, OrderAccount>>,
+
+    #[account(
+        mut,
+        constraint = user_ledger.load()?.owner == user.key() @ ErrorCode::UnauthorizedSettlement,
+    )]
+    pub user_ledger: AccountLoader<'info, UserPrivateLedger>,
 }
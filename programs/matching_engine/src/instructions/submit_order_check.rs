@@ -3,7 +3,10 @@ use crate::SubmitOrderCheckCallback;
 use crate::errors::ErrorCode;
 use crate::states::*;
 use crate::SignerAccount;
-use anchor_lang::prelude::*;
+use crate::ORDER_KIND_STOP_LIMIT;
+use crate::join_order_group;
+use crate::utils::{load_reference_price, price_scale, require_accepting_orders};
+use anchor_lang::{prelude::*, system_program};
 use anchor_spl::token::TokenAccount;
 use anchor_spl::token_interface::Mint;
 use arcium_anchor::prelude::*;
@@ -19,12 +22,75 @@ pub fn submit_order_check(
     ctx: Context<SubmitOrderCheck>,
     amount: [u8; 32],
     price: [u8; 32],
+    quote_budget: [u8; 32],
+    min_fill_qty: [u8; 32],
+    display_size: [u8; 32],
     user_pubkey: [u8; 32], // x25519 public key
     order_type: u8, // 0 = buy, 1 = sell
+    order_kind: u8, // 0 = limit, 1 = market, 2 = peg
     computation_offset: u64,
     order_id: u64,
     order_nonce: u128,
+    lock_nonce: u128,
 ) -> Result<()> {
+    require!(order_kind <= ORDER_KIND_STOP_LIMIT, ErrorCode::InvalidOrderKind);
+    // With an entry band on, the feed has to come along so the band can't be skipped
+    let (band_reference, entry_band_bps, tick_size, lot_size, min_notional, price_scale, bond, order_limits) = {
+        let orderbook_state = ctx.accounts.orderbook_state.load()?;
+        require_accepting_orders(&orderbook_state, Clock::get()?.unix_timestamp)?;
+
+        let entry_band_bps = orderbook_state.entry_band_bps;
+        let band_reference = if entry_band_bps > 0 {
+            let price_feed = ctx
+                .accounts
+                .price_feed
+                .as_ref()
+                .ok_or(ErrorCode::InvalidPriceFeed)?;
+            require_keys_eq!(price_feed.key(), orderbook_state.price_feed, ErrorCode::InvalidPriceFeed);
+            load_reference_price(price_feed, orderbook_state.quote_decimals)?
+        } else {
+            0
+        };
+        (
+            band_reference,
+            entry_band_bps,
+            orderbook_state.tick_size.max(1),
+            orderbook_state.lot_size.max(1),
+            orderbook_state.min_notional,
+            price_scale(orderbook_state.base_decimals)?,
+            orderbook_state.order_bond_lamports,
+            (
+                orderbook_state.max_open_orders,
+                orderbook_state.max_submissions_per_window,
+                orderbook_state.submission_window,
+            ),
+        )
+    };
+
+    // Per wallet caps, the order counts as open until it is rejected or the
+    // cranker closes it out with close_open_order
+    let user_trading_state = &mut ctx.accounts.user_trading_state;
+    if user_trading_state.user == Pubkey::default() {
+        user_trading_state.user = ctx.accounts.user.key();
+        user_trading_state.bump = ctx.bumps.user_trading_state;
+    }
+    let (max_open_orders, max_submissions_per_window, submission_window) = order_limits;
+    user_trading_state.record_submission(
+        Clock::get()?.unix_timestamp,
+        max_open_orders,
+        max_submissions_per_window,
+        submission_window,
+    )?;
+
+    // The anti-spam bond waits in the order account until release_order_bond
+    if bond > 0 {
+        let cpi_accounts = system_program::Transfer {
+            from: ctx.accounts.user.to_account_info(),
+            to: ctx.accounts.order_account.to_account_info(),
+        };
+        let cpi_context = CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
+        system_program::transfer(cpi_context, bond)?;
+    }
 
     //initialize the order account
     let order_account = &mut ctx.accounts.order_account;
@@ -32,11 +98,37 @@ pub fn submit_order_check(
     order_account.user = ctx.accounts.user.key();
     order_account.user_enc_pubkey = user_pubkey;
     order_account.order_nonce = order_nonce;
+    order_account.order_type = order_type;
+    order_account.order_kind = order_kind;
     order_account.timestamp = Clock::get()?.unix_timestamp;
+    order_account.bond = bond;
+    order_account.open = 1;
     order_account.bump = ctx.bumps.order_account;
 
+    // Grouped legs after the first are backed by the first leg's lock
+    let (group_id, group_role, lock_shared) = join_order_group(
+        ctx.accounts.order_group.as_mut(),
+        &ctx.accounts.user.key(),
+        order_id,
+    )?;
+    let order_account = &mut ctx.accounts.order_account;
+    order_account.group_id = group_id;
+    order_account.group_role = group_role;
+
+    // A shared leg is checked against the lock the first leg recorded on the
+    // group. Other orders have no such lock and point the circuit at their
+    // own balances instead, it only reads the record when lock_shared is set.
+    let group = ctx.accounts.order_group.as_ref();
+    let has_group = group.is_some();
+    let group_callback_account = group.map(|g| g.key()).unwrap_or(ID);
+    let first_lock = group
+        .filter(|_| lock_shared)
+        .map(|g| (g.lock_nonce, g.key(), GROUP_LOCK_OFFSET, g.lock_order_type));
+
     ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
     let user_ledger = ctx.accounts.user_ledger.load_mut()?;
+    let (first_lock_nonce, first_lock_account, first_lock_offset, first_order_type) =
+        first_lock.unwrap_or((user_ledger.balance_nonce, ctx.accounts.user_ledger.key(), 8 + 32, order_type));
     
     let args = vec![        
 
@@ -45,6 +137,9 @@ pub fn submit_order_check(
         Argument::PlaintextU128(order_nonce),
         Argument::EncryptedU64(amount), // Client encrypts this
         Argument::EncryptedU64(price),  // Client encrypts this
+        Argument::EncryptedU64(quote_budget), // Client encrypts this (0 unless market or peg buy)
+        Argument::EncryptedU64(min_fill_qty), // Client encrypts this (0 = any fill size)
+        Argument::EncryptedU64(display_size), // Client encrypts this (iceberg clip, 0 = fully displayed)
 
         // Enc<Shared, Balances>
         Argument::ArcisPubkey(user_pubkey),
@@ -55,9 +150,30 @@ pub fn submit_order_check(
             4 * 32,          // Size: 4 chunks
         ),
 
+        // Enc<Mxe, OrderLock>, the group's first leg
+        Argument::PlaintextU128(first_lock_nonce),
+        Argument::Account(
+            first_lock_account,
+            first_lock_offset,
+            (ORDER_LOCK_CHUNKS * 32) as u32,
+        ),
+
         // Argument::PlaintextU64(order_id),
         Argument::PlaintextU8(order_type),
+        Argument::PlaintextU8(order_kind),
+        Argument::PlaintextU8(lock_shared as u8),
+        Argument::PlaintextU8(first_order_type),
+        Argument::PlaintextU8(group_role),
+        Argument::PlaintextU64(band_reference),
+        Argument::PlaintextU64(entry_band_bps as u64),
+        Argument::PlaintextU64(tick_size),
+        Argument::PlaintextU64(lot_size),
+        Argument::PlaintextU64(min_notional),
+        Argument::PlaintextU64(price_scale),
         Argument::PlaintextU64(Clock::get()?.unix_timestamp as u64),
+
+        // Mxe, the lock record kept on the order account
+        Argument::PlaintextU128(lock_nonce),
     ];
 
     queue_computation(
@@ -74,6 +190,15 @@ pub fn submit_order_check(
                 pubkey: ctx.accounts.order_account.key(),
                 is_writable: true,
             },
+            CallbackAccount {
+                pubkey: ctx.accounts.user_trading_state.key(),
+                is_writable: true,
+            },
+            // The program id stands in for "no group"
+            CallbackAccount {
+                pubkey: group_callback_account,
+                is_writable: has_group,
+            },
         ])],
     )?;
 
@@ -86,11 +211,16 @@ pub fn submit_order_check(
 #[instruction(
     amount: [u8; 32],
     price: [u8; 32],
+    quote_budget: [u8; 32],
+    min_fill_qty: [u8; 32],
+    display_size: [u8; 32],
     user_pubkey: [u8; 32],
     order_type: u8,
+    order_kind: u8,
     computation_offset: u64,
     order_id: u64,
     order_nonce: u128,
+    lock_nonce: u128,
 )]
 pub struct SubmitOrderCheck<'info> {
     #[account(mut)]
@@ -150,4 +280,28 @@ pub struct SubmitOrderCheck<'info> {
 
     #[account(mut)]
     pub user_ledger: AccountLoader<'info, UserPrivateLedger>,
+
+    // Only read for its trading session schedule, order entry rules and bond
+    #[account(seeds = [ORDER_BOOK_STATE_SEED], bump)]
+    pub orderbook_state: AccountLoader<'info, OrderBookState>,
+
+    /// CHECK: Pyth price account, must match orderbook_state.price_feed. Only needed with an entry band.
+    pub price_feed: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + UserTradingState::INIT_SPACE,
+        seeds = [USER_TRADING_STATE_SEED, user.key().as_ref()],
+        bump,
+    )]
+    pub user_trading_state: Box<Account<'info, UserTradingState>>,
+
+    // Only set when the order is a leg of a one-cancels-other or bracket group
+    #[account(
+        mut,
+        seeds = [ORDER_GROUP_SEED, user.key().as_ref(), order_group.group_id.to_le_bytes().as_ref()],
+        bump = order_group.bump,
+    )]
+    pub order_group: Option<Account<'info, OrderGroup>>,
 }
@@ -0,0 +1,194 @@
+use crate::errors::ErrorCode;
+use crate::states::*;
+use crate::utils::require_accepting_orders;
+use crate::SignerAccount;
+use crate::SubmitStopOrderCallback;
+use crate::COMP_DEF_OFFSET_SUBMIT_STOP_ORDER;
+use crate::{ORDER_KIND_STOP, ORDER_KIND_STOP_LIMIT};
+use anchor_lang::prelude::*;
+use arcium_anchor::prelude::*;
+use arcium_client::idl::arcium::types::CallbackAccount;
+
+use crate::ID;
+use crate::ID_CONST;
+
+// Second step for stop orders, in place of submit_order. The order's lock is
+// taken by submit_order_check, this parks it in the encrypted trigger book.
+// Bracket exits always come through here; a stop-limit exit with a trigger
+// price of 0 is a plain take profit that rests as soon as the entry fills.
+pub fn submit_stop_order(
+    ctx: Context<SubmitStopOrder>,
+    amount: [u8; 32],
+    price: [u8; 32],
+    quote_budget: [u8; 32],
+    min_fill_qty: [u8; 32],
+    trigger_price: [u8; 32],
+    user_pubkey: [u8; 32], // x25519 public key
+    order_type: u8, // 0 = buy, 1 = sell
+    order_kind: u8, // 3 = stop, 4 = stop-limit
+    computation_offset: u64,
+    order_id: u64,
+    order_nonce: u128,
+) -> Result<()> {
+    require!(
+        order_kind == ORDER_KIND_STOP || order_kind == ORDER_KIND_STOP_LIMIT,
+        ErrorCode::InvalidOrderKind
+    );
+    require!(order_type <= 1, ErrorCode::InvalidOrderType);
+    ctx.accounts
+        .order_account
+        .take_for_submission(order_type, order_kind)?;
+    require_accepting_orders(
+        &*ctx.accounts.orderbook_state.load()?,
+        Clock::get()?.unix_timestamp,
+    )?;
+
+    ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+    let trigger_book = ctx.accounts.trigger_book_state.load()?;
+    let user_ledger = ctx.accounts.user_ledger.load()?;
+
+    let args = vec![
+        // Enc<Shared, StopOrderData>
+        Argument::ArcisPubkey(user_pubkey),
+        Argument::PlaintextU128(order_nonce),
+        Argument::EncryptedU64(amount),
+        Argument::EncryptedU64(price),
+        Argument::EncryptedU64(quote_budget), // 0 unless stop buy
+        Argument::EncryptedU64(min_fill_qty),
+        Argument::EncryptedU64(trigger_price),
+
+        // Enc<Mxe, TriggerBookFlat>
+        Argument::PlaintextU128(trigger_book.trigger_nonce),
+        Argument::Account(
+            ctx.accounts.trigger_book_state.key(),
+            8 + 32, // Offset: discriminator(8) + orderbook(32)
+            (TRIGGER_BOOK_CHUNKS * 32) as u32,
+        ),
+
+        // Enc<Shared, Balances>, refunded when the trigger book is full
+        Argument::ArcisPubkey(user_pubkey),
+        Argument::PlaintextU128(user_ledger.balance_nonce),
+        Argument::Account(
+            ctx.accounts.user_ledger.key(),
+            8 + 32,          // Offset: discriminator + owner
+            4 * 32,          // Size: 4 chunks
+        ),
+
+        // Enc<Mxe, OrderLock>
+        Argument::PlaintextU128(ctx.accounts.order_account.lock_nonce),
+        Argument::Account(
+            ctx.accounts.order_account.key(),
+            ORDER_LOCK_OFFSET,
+            (ORDER_LOCK_CHUNKS * 32) as u32,
+        ),
+
+        Argument::PlaintextU64(order_id),
+        Argument::PlaintextU8(order_type),
+        Argument::PlaintextU8(order_kind),
+
+        // Enc<Mxe, u64>
+        Argument::PlaintextU128(user_ledger.owner_nonce),
+        Argument::Account(ctx.accounts.user_ledger.key(), LEDGER_OWNER_OFFSET, 32),
+
+        Argument::PlaintextU64(ctx.accounts.order_account.group_id),
+        Argument::PlaintextU8(ctx.accounts.order_account.group_role),
+    ];
+
+    queue_computation(
+        ctx.accounts,
+        computation_offset,
+        args,
+        None,
+        vec![SubmitStopOrderCallback::callback_ix(&[
+            CallbackAccount {
+                pubkey: ctx.accounts.trigger_book_state.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.user_ledger.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.order_account.key(),
+                is_writable: true,
+            },
+        ])],
+    )?;
+
+    Ok(())
+}
+
+#[queue_computation_accounts("submit_stop_order", user)]
+#[derive(Accounts)]
+#[instruction(
+    amount: [u8; 32],
+    price: [u8; 32],
+    quote_budget: [u8; 32],
+    min_fill_qty: [u8; 32],
+    trigger_price: [u8; 32],
+    user_pubkey: [u8; 32],
+    order_type: u8,
+    order_kind: u8,
+    computation_offset: u64,
+    order_id: u64,
+    order_nonce: u128,
+)]
+pub struct SubmitStopOrder<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = user,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, SignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_SUBMIT_STOP_ORDER))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Account<'info, ClockAccount>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+
+    // The lock for this order was taken when its order account was created
+    #[account(
+        mut,
+        seeds = [b"order", order_id.to_le_bytes().as_ref()],
+        bump = order_account.bump,
+        constraint = order_account.user == user.key() @ ErrorCode::UnauthorizedSettlement,
+    )]
+    pub order_account: Box<Account<'info, OrderAccount>>,
+
+    #[account(seeds = [ORDER_BOOK_STATE_SEED], bump)]
+    pub orderbook_state: AccountLoader<'info, OrderBookState>,
+
+    #[account(
+        mut,
+        seeds = [TRIGGER_BOOK_SEED, orderbook_state.key().as_ref()],
+        bump = trigger_book_state.load()?.bump,
+    )]
+    pub trigger_book_state: AccountLoader<'info, TriggerBookState>,
+
+    #[account(
+        mut,
+        constraint = user_ledger.load()?.owner == user.key() @ ErrorCode::UnauthorizedSettlement,
+    )]
+    pub user_ledger: AccountLoader<'info, UserPrivateLedger>,
+}
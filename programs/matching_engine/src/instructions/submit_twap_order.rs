@@ -0,0 +1,156 @@
+use crate::errors::ErrorCode;
+use crate::states::*;
+use crate::utils::require_accepting_orders;
+use crate::SignerAccount;
+use crate::SubmitTwapOrderCallback;
+use crate::COMP_DEF_OFFSET_SUBMIT_TWAP_ORDER;
+use crate::ORDER_KIND_LIMIT;
+use anchor_lang::prelude::*;
+use arcium_anchor::prelude::*;
+use arcium_client::idl::arcium::types::CallbackAccount;
+
+use crate::ID;
+use crate::ID_CONST;
+
+// Second step for TWAP parents, in place of submit_order. submit_order_check
+// locks the whole parent as a limit order, this keeps it out of the book and
+// hands it to release_twap_slice one slice at a time until end_time. Size and
+// price come from the recorded lock, which the parent takes over.
+pub fn submit_twap_order(
+    ctx: Context<SubmitTwapOrder>,
+    slice_size: [u8; 32],
+    user_pubkey: [u8; 32], // x25519 public key
+    order_type: u8, // 0 = buy, 1 = sell
+    end_time: i64,
+    computation_offset: u64,
+    order_id: u64,
+    order_nonce: u128,
+    twap_nonce: u128,
+) -> Result<()> {
+    require!(order_type <= 1, ErrorCode::InvalidOrderType);
+    let current_time = Clock::get()?.unix_timestamp;
+    require!(end_time > current_time, ErrorCode::InvalidTwapOrder);
+    require_accepting_orders(&*ctx.accounts.orderbook_state.load()?, current_time)?;
+    // The parent was checked as one limit order for its whole amount
+    ctx.accounts
+        .order_account
+        .take_for_submission(order_type, ORDER_KIND_LIMIT)?;
+    // Grouped legs share a lock, an expiring parent could refund one it never took
+    require!(ctx.accounts.order_account.group_id == 0, ErrorCode::InvalidTwapOrder);
+
+    let mut twap_order = ctx.accounts.twap_order_state.load_init()?;
+    twap_order.orderbook = ctx.accounts.orderbook_state.key();
+    twap_order.user = ctx.accounts.user.key();
+    twap_order.user_enc_pubkey = user_pubkey;
+    twap_order.order_id = order_id;
+    twap_order.order_type = order_type;
+    twap_order.end_time = end_time;
+    twap_order.bump = ctx.bumps.twap_order_state;
+
+    let args = vec![
+        // Enc<Shared, TwapOrderData>
+        Argument::ArcisPubkey(user_pubkey),
+        Argument::PlaintextU128(order_nonce),
+        Argument::EncryptedU64(slice_size),
+
+        // Enc<Mxe, OrderLock>
+        Argument::PlaintextU128(ctx.accounts.order_account.lock_nonce),
+        Argument::Account(
+            ctx.accounts.order_account.key(),
+            ORDER_LOCK_OFFSET,
+            (ORDER_LOCK_CHUNKS * 32) as u32,
+        ),
+
+        // Mxe
+        Argument::PlaintextU128(twap_nonce),
+    ];
+
+    ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+    queue_computation(
+        ctx.accounts,
+        computation_offset,
+        args,
+        None,
+        vec![SubmitTwapOrderCallback::callback_ix(&[
+            CallbackAccount {
+                pubkey: ctx.accounts.twap_order_state.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.order_account.key(),
+                is_writable: true,
+            },
+        ])],
+    )?;
+
+    Ok(())
+}
+
+#[queue_computation_accounts("submit_twap_order", user)]
+#[derive(Accounts)]
+#[instruction(
+    slice_size: [u8; 32],
+    user_pubkey: [u8; 32],
+    order_type: u8,
+    end_time: i64,
+    computation_offset: u64,
+    order_id: u64,
+    order_nonce: u128,
+    twap_nonce: u128,
+)]
+pub struct SubmitTwapOrder<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = user,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, SignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_SUBMIT_TWAP_ORDER))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Account<'info, ClockAccount>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+
+    // The lock for the whole parent was taken when its order account was created
+    #[account(
+        mut,
+        seeds = [b"order", order_id.to_le_bytes().as_ref()],
+        bump = order_account.bump,
+        constraint = order_account.user == user.key() @ ErrorCode::UnauthorizedSettlement,
+    )]
+    pub order_account: Box<Account<'info, OrderAccount>>,
+
+    #[account(seeds = [ORDER_BOOK_STATE_SEED], bump)]
+    pub orderbook_state: AccountLoader<'info, OrderBookState>,
+
+    #[account(
+        init,
+        payer = user,
+        space = 8 + TwapOrderState::INIT_SPACE,
+        seeds = [TWAP_ORDER_SEED, order_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub twap_order_state: AccountLoader<'info, TwapOrderState>,
+}
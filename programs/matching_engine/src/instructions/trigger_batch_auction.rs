@@ -0,0 +1,139 @@
+use anchor_lang::prelude::*;
+use arcium_anchor::prelude::*;
+use crate::errors::ErrorCode;
+use crate::states::*;
+use crate::COMP_DEF_OFFSET_BATCH_AUCTION;
+use crate::SignerAccount;
+use crate::BatchAuctionCallback;
+use crate::utils::*;
+use crate::{MARKET_MODE_BATCH_AUCTION, SESSION_CLOSING_AUCTION, SESSION_CONTINUOUS, SESSION_OPENING_AUCTION};
+use arcium_client::idl::arcium::types::CallbackAccount;
+
+use crate::ID;
+use crate::ID_CONST;
+
+pub fn trigger_batch_auction(
+    ctx: Context<TriggerBatchAuction>,
+    computation_offset: u64,
+    backend_nonce: u128,
+) -> Result<()> {
+    let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    require!(orderbook_state.halted == 0, ErrorCode::MarketHalted);
+
+    // The opening and closing auctions cross the whole book once, whatever the
+    // market mode. In between, batch auction markets keep auctioning as usual.
+    let phase = session_phase(&orderbook_state, current_time);
+    if phase == SESSION_OPENING_AUCTION || phase == SESSION_CLOSING_AUCTION {
+        require!(
+            orderbook_state.last_call_auction < call_auction_start(&orderbook_state, current_time),
+            ErrorCode::CallAuctionAlreadyRun
+        );
+        orderbook_state.last_call_auction = current_time;
+    } else {
+        require!(phase == SESSION_CONTINUOUS, ErrorCode::WrongSession);
+        require!(
+            orderbook_state.market_mode == MARKET_MODE_BATCH_AUCTION,
+            ErrorCode::WrongMarketMode
+        );
+    }
+
+    // Auctions share the matching interval
+    require!(
+        current_time >= orderbook_state.last_match_timestamp + 15,
+        ErrorCode::MatchingTooFrequent
+    );
+
+    orderbook_state.last_match_timestamp = current_time;
+
+    let reference_price =
+        market_reference_price(
+            &ctx.accounts.price_feed,
+            orderbook_state.price_feed,
+            orderbook_state.quote_decimals,
+        )?;
+
+    let args = vec![
+        // Backend user (Shared) - To receive the encrypted fills
+        Argument::ArcisPubkey(orderbook_state.backend_pubkey),
+        Argument::PlaintextU128(backend_nonce),
+
+        // OrderBook (Enc<Mxe, OrderBook>)
+        Argument::PlaintextU128(orderbook_state.orderbook_nonce),
+        Argument::Account(
+            ctx.accounts.orderbook_state.key(),
+            8 + 32,
+            (ORDER_BOOK_CHUNKS * 32) as u32,
+        ),
+
+        Argument::PlaintextU64(reference_price),
+        Argument::PlaintextU8(orderbook_state.auction_allocation),
+        Argument::PlaintextU8(orderbook_state.stp_policy),
+        Argument::PlaintextU64(price_scale(orderbook_state.base_decimals)?),
+        Argument::PlaintextU64(current_time as u64),
+    ];
+
+    ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+    queue_computation(
+        ctx.accounts,
+        computation_offset,
+        args,
+        None,
+        vec![BatchAuctionCallback::callback_ix(&[CallbackAccount {
+            pubkey: ctx.accounts.orderbook_state.key(),
+            is_writable: true,
+        }])],
+    )?;
+
+    msg!("Batch auction triggered. Computation offset: {}", computation_offset);
+
+    Ok(())
+}
+
+#[queue_computation_accounts("batch_auction", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct TriggerBatchAuction<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, SignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_BATCH_AUCTION))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Account<'info, ClockAccount>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        mut,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump,
+    )]
+    pub orderbook_state: AccountLoader<'info, OrderBookState>,
+    /// CHECK: Pyth price account, must match orderbook_state.price_feed.
+    pub price_feed: Option<UncheckedAccount<'info>>,
+}
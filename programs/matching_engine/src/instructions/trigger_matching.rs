@@ -6,6 +6,11 @@ use crate::instructions::*;
 use crate::COMP_DEF_OFFSET_MATCH_ORDERS;
 use crate::SignerAccount;
 use crate::MatchOrdersCallback;
+use crate::utils::*;
+use crate::{MatchesFoundEvent, MarketHaltedEvent, OrderLocksReleasedEvent, MAX_LOCK_RELEASES};
+use crate::{
+    MARKET_MODE_CONTINUOUS, PRICE_BAND_SOURCE_ORACLE, PRICING_RULE_UNIFORM, SESSION_CONTINUOUS,
+};
 use arcium_client::idl::arcium::types::CallbackAccount;
 
 use crate::ID;
@@ -16,8 +21,64 @@ pub fn trigger_matching(
     computation_offset: u64,
     backend_nonce: u128,
 ) -> Result<()> {
-    let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+    let args = {
+        let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+        // Uniform price markets match through their own computation
+        require!(
+            orderbook_state.pricing_rule != PRICING_RULE_UNIFORM,
+            ErrorCode::WrongPricingRule
+        );
+        matching_computation(
+            &mut orderbook_state,
+            ctx.accounts.orderbook_state.key(),
+            &ctx.accounts.price_feed,
+            backend_nonce,
+        )?
+    };
+
+    ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+    let callback_accounts = vec![
+        CallbackAccount {
+        pubkey: ctx.accounts.orderbook_state.key(),
+        is_writable: true,
+    }
+    ];
+
+    queue_computation(
+        ctx.accounts,
+        computation_offset,
+        args,
+        None,
+        vec![MatchOrdersCallback::callback_ix(&callback_accounts)],
+    )?;
+
+    msg!("Matching triggered. Computation offset: {}", computation_offset);
+
+    Ok(())
+}
+
+// Checks shared by both matching computations, the pricing rule argument is
+// left out for uniform price markets since their computation fixes it
+pub fn matching_computation(
+    orderbook_state: &mut OrderBookState,
+    orderbook_key: Pubkey,
+    price_feed: &Option<UncheckedAccount>,
+    backend_nonce: u128,
+) -> Result<Vec<Argument>> {
     let current_time = Clock::get()?.unix_timestamp;
+
+    require!(
+        orderbook_state.market_mode == MARKET_MODE_CONTINUOUS,
+        ErrorCode::WrongMarketMode
+    );
+    require_accepting_orders(orderbook_state, current_time)?;
+    // Orders pile up without matching until continuous trading opens
+    require!(
+        session_phase(orderbook_state, current_time) == SESSION_CONTINUOUS,
+        ErrorCode::WrongSession
+    );
+    require!(orderbook_state.halted == 0, ErrorCode::MarketHalted);
     
     // Rate limit matching (every 15 seconds) or based on complex logic based on filling of the orderbook
     require!(
@@ -27,7 +88,20 @@ pub fn trigger_matching(
 
     orderbook_state.last_match_timestamp = current_time;
 
-    let args = vec![
+    let reference_price =
+        market_reference_price(
+            price_feed,
+            orderbook_state.price_feed,
+            orderbook_state.quote_decimals,
+        )?;
+    // Without a price to compare against the band stays off for this batch
+    let band_reference = if orderbook_state.price_band_source == PRICE_BAND_SOURCE_ORACLE {
+        reference_price
+    } else {
+        orderbook_state.last_clearing_price
+    };
+
+    let mut args = vec![
         // Backend user (Shared) - To receive encrypted match results
         Argument::ArcisPubkey(orderbook_state.backend_pubkey),
         Argument::PlaintextU128(backend_nonce),  // Fresh nonce for this match batch (generated by MXE)
@@ -35,30 +109,99 @@ pub fn trigger_matching(
         // OrderBook (Enc<Mxe, OrderBook>)
         Argument::PlaintextU128(orderbook_state.orderbook_nonce),
         Argument::Account(
-            ctx.accounts.orderbook_state.key(),
+            orderbook_key,
             8 + 32,  
-            18 * 32, 
+            (ORDER_BOOK_CHUNKS * 32) as u32, 
         ),
+
+        Argument::PlaintextU64(reference_price),
     ];
+    if orderbook_state.pricing_rule != PRICING_RULE_UNIFORM {
+        args.push(Argument::PlaintextU8(orderbook_state.pricing_rule));
+    }
+    args.extend([
+        Argument::PlaintextU8(orderbook_state.stp_policy),
+        Argument::PlaintextU8(orderbook_state.allocation_mode),
+        Argument::PlaintextU8(orderbook_state.top_priority_pct),
+        Argument::PlaintextU64(band_reference),
+        Argument::PlaintextU64(orderbook_state.price_band_bps as u64),
+        Argument::PlaintextU64(price_scale(orderbook_state.base_decimals)?),
+        Argument::PlaintextU64(current_time as u64), // Fresh time priority for refilled iceberg clips
+    ]);
 
-    ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+    Ok(args)
+}
 
-    let callback_accounts = vec![
-        CallbackAccount {
-        pubkey: ctx.accounts.orderbook_state.key(),
-        is_writable: true,
+// Applies a match batch result to the market and emits the matches and
+// releases for the backend, shared by both matching callbacks
+pub fn record_match_batch(
+    orderbook_state: &mut OrderBookState,
+    matches_nonce: u128,
+    matches: &[[u8; 32]],
+    num_matches: u8,
+    num_releases: u8,
+    skipped_count: u8,
+    last_trade_price: u64,
+) -> Result<()> {
+    // A clearing sourced price band follows continuous trades too
+    if num_matches > 0 {
+        orderbook_state.last_clearing_price = last_trade_price;
     }
-    ];
 
-    queue_computation(
-        ctx.accounts,
-        computation_offset,
-        args,
-        None,
-        vec![MatchOrdersCallback::callback_ix(&callback_accounts)],
-    )?;
+    // Too many crossings outside the price band trips the breaker
+    let halt_threshold = orderbook_state.band_halt_threshold;
+    if halt_threshold > 0 && skipped_count >= halt_threshold {
+        orderbook_state.halted = 1;
+        emit!(MarketHaltedEvent {
+            skipped_count,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+    }
 
-    msg!("Matching triggered. Computation offset: {}", computation_offset);
+    if num_matches > 0 {
+        // Create MatchResult accounts for each match
+        // The encrypted matches will be decrypted by backend
+
+        let match1 = matches[0..5].try_into().unwrap();
+        let mut match2 = [[0u8; 32]; 5];
+        let mut match3 = [[0u8; 32]; 5];
+        let mut match4 = [[0u8; 32]; 5];
+        if num_matches > 1 {
+            match2 = matches[5..10].try_into().unwrap();
+        }
+        if num_matches > 2 {
+            match3 = matches[10..15].try_into().unwrap();
+        }
+        if num_matches > 3 {
+            match4 = matches[15..20].try_into().unwrap();
+        }
+        orderbook_state.total_matches += num_matches as u64;
+
+        emit!(MatchesFoundEvent {
+            num_matches,
+            last_trade_price,
+            match1,
+            match2,
+            match3,
+            match4,
+            nonce: matches_nonce,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+    }
+
+    if num_releases > 0 {
+        // Releases follow the 4 matches (5 fields each) and num_matches,
+        // each release is order_id, order_type, amount
+        let releases: [[u8; 32]; 3 * MAX_LOCK_RELEASES] =
+            matches[21..21 + 3 * MAX_LOCK_RELEASES].try_into().unwrap();
+
+        emit!(OrderLocksReleasedEvent {
+            num_releases,
+            releases,
+            nonce: matches_nonce,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+    }
 
     Ok(())
 }
@@ -99,8 +242,14 @@ pub struct TriggerMatching<'info> {
     pub clock_account: Account<'info, ClockAccount>,
     pub system_program: Program<'info, System>,
     pub arcium_program: Program<'info, Arcium>,
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump,
+    )]
     pub orderbook_state: AccountLoader<'info, OrderBookState>,
+    /// CHECK: Pyth price account, must match orderbook_state.price_feed.
+    pub price_feed: Option<UncheckedAccount<'info>>,
 }
 
 #[event]
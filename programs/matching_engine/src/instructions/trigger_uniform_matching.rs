@@ -0,0 +1,100 @@
+use anchor_lang::prelude::*;
+use arcium_anchor::prelude::*;
+use crate::errors::ErrorCode;
+use crate::states::*;
+use crate::instructions::*;
+use crate::COMP_DEF_OFFSET_MATCH_ORDERS_UNIFORM;
+use crate::SignerAccount;
+use crate::MatchOrdersUniformCallback;
+use crate::PRICING_RULE_UNIFORM;
+use arcium_client::idl::arcium::types::CallbackAccount;
+
+use crate::ID;
+use crate::ID_CONST;
+
+pub fn trigger_uniform_matching(
+    ctx: Context<TriggerUniformMatching>,
+    computation_offset: u64,
+    backend_nonce: u128,
+) -> Result<()> {
+    let args = {
+        let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+        require!(
+            orderbook_state.pricing_rule == PRICING_RULE_UNIFORM,
+            ErrorCode::WrongPricingRule
+        );
+        matching_computation(
+            &mut orderbook_state,
+            ctx.accounts.orderbook_state.key(),
+            &ctx.accounts.price_feed,
+            backend_nonce,
+        )?
+    };
+
+    ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+    let callback_accounts = vec![
+        CallbackAccount {
+        pubkey: ctx.accounts.orderbook_state.key(),
+        is_writable: true,
+    }
+    ];
+
+    queue_computation(
+        ctx.accounts,
+        computation_offset,
+        args,
+        None,
+        vec![MatchOrdersUniformCallback::callback_ix(&callback_accounts)],
+    )?;
+
+    msg!("Uniform matching triggered. Computation offset: {}", computation_offset);
+
+    Ok(())
+}
+
+#[queue_computation_accounts("match_orders_uniform", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct TriggerUniformMatching<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, SignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_MATCH_ORDERS_UNIFORM))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Account<'info, ClockAccount>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        mut,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump,
+    )]
+    pub orderbook_state: AccountLoader<'info, OrderBookState>,
+    /// CHECK: Pyth price account, must match orderbook_state.price_feed.
+    pub price_feed: Option<UncheckedAccount<'info>>,
+}
@@ -25,38 +25,52 @@ pub fn withdraw_from_ledger_verify(
     // 1. Queue MPC computation to update encrypted balances
     ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
 
-    let user_ledger = ctx.accounts.user_ledger.load_mut()?;
-    
+    let (args, callback_accounts) =
+        withdraw_verify_computation(user_enc_pubkey, &ctx.accounts.user_ledger, amount, is_base_token)?;
+
+    queue_computation(
+        ctx.accounts,
+        computation_offset,
+        args,
+        None,
+        vec![UpdateLedgerWithdrawVerifyCallback::callback_ix(&callback_accounts)],
+    )?;
+
+    Ok(())
+
+}
+
+// Arguments and callback accounts of update_ledger_withdraw_verify, shared by
+// user withdrawals and withdraw_protocol_fees on the authority's fee ledger
+pub fn withdraw_verify_computation(
+    enc_pubkey: [u8; 32],
+    ledger: &AccountLoader<UserPrivateLedger>,
+    amount: u64,
+    is_base_token: bool,
+) -> Result<(Vec<Argument>, [CallbackAccount; 1])> {
+    let balance_nonce = ledger.load()?.balance_nonce;
+
     let args = vec![
         // Current encrypted balances
-        Argument::ArcisPubkey(user_enc_pubkey),
-        Argument::PlaintextU128(user_ledger.balance_nonce),
+        Argument::ArcisPubkey(enc_pubkey),
+        Argument::PlaintextU128(balance_nonce),
         Argument::Account(
-            ctx.accounts.user_ledger.key(),
+            ledger.key(),
             8 + 32,          // Offset: discriminator + owner
             4 * 32,          // Size: 4 chunks
         ),
-        
-        // Deposit info
+
+        // Withdrawal info
         Argument::PlaintextU64(amount),
         Argument::PlaintextU8(if is_base_token { 0 } else { 1 }),
     ];
-    
-    queue_computation(
-        ctx.accounts,
-        computation_offset,
-        args,
-        None,
-        vec![UpdateLedgerWithdrawVerifyCallback::callback_ix(&[
-            CallbackAccount {
-                pubkey: ctx.accounts.user_ledger.key(),
-                is_writable: true,
-            },
-        ])],
-    )?;
 
-    Ok(())
+    let callback_accounts = [CallbackAccount {
+        pubkey: ledger.key(),
+        is_writable: true,
+    }];
 
+    Ok((args, callback_accounts))
 }
 
 #[queue_computation_accounts("update_ledger_withdraw_verify", user)]
@@ -2,29 +2,63 @@ use anchor_lang::prelude::*;
 use anchor_spl::token::{self, Token, TokenAccount, Transfer};
 use anchor_spl::token::Mint;
 use anchor_spl::associated_token::AssociatedToken;
+use crate::errors::ErrorCode;
+use crate::states::*;
+use crate::utils::*;
+use crate::instructions::{FeeChargedEvent, FEE_VAULT_SEED};
 
 const CRANKER_BOT_PUBKEY: Pubkey = pubkey!("8wJE7H7svhpz1Jnzbne3YErWFVeWNWGRbAkDQ8xeixoY");
 
 pub fn withdraw_from_vault(ctx: Context<WithdrawFromVault>, amount: u64, user: Pubkey) -> Result<()> {
+    // The ledger was debited the full amount, the withdraw fee stays behind
+    let fee = {
+        let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+        let is_base = ctx.accounts.mint.key() == orderbook_state.base_mint;
+        let fee = fee_for(amount, orderbook_state.withdraw_fee_bps)?;
+        orderbook_state.accrue_fee(is_base, fee)?;
+        fee
+    };
+
+    let signer_seeds: &[&[&[u8]]] = &[&[b"vault_authority", &[ctx.bumps.vault_authority]]];
+
+    if fee > 0 {
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.vault.to_account_info(),
+            to: ctx.accounts.fee_vault.to_account_info(),
+            authority: ctx.accounts.vault_authority.to_account_info(),
+        };
+        let cpi_context = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token::transfer(cpi_context, fee)?;
+
+        emit!(FeeChargedEvent {
+            user,
+            mint: ctx.accounts.mint.key(),
+            fee,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+    }
+
     let cpi_accounts = Transfer {
         from: ctx.accounts.vault.to_account_info(),
         to: ctx.accounts.user_token_account.to_account_info(),
         authority: ctx.accounts.vault_authority.to_account_info(),
     };
 
-    let signer_seeds: &[&[&[u8]]] = &[&[b"vault_authority", &[ctx.bumps.vault_authority]]];
-
     let cpi_context = CpiContext::new_with_signer(
         ctx.accounts.token_program.to_account_info(),
         cpi_accounts,
         signer_seeds,
     );
 
-    token::transfer(cpi_context, amount)?;
+    token::transfer(cpi_context, amount - fee)?;
 
     emit!(WithdrawEvent {
         user: user,
-        amount,
+        amount: amount - fee,
     });
     Ok(())
 }
@@ -51,6 +85,23 @@ pub struct WithdrawFromVault<'info> {
     
     #[account(mut)]
     pub user_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = orderbook_state.load()?.base_mint == mint.key()
+            || orderbook_state.load()?.quote_mint == mint.key() @ ErrorCode::InvalidMint,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump,
+    )]
+    pub orderbook_state: AccountLoader<'info, OrderBookState>,
+
+    #[account(
+        mut,
+        seeds = [FEE_VAULT_SEED, mint.key().as_ref()],
+        bump,
+    )]
+    pub fee_vault: Account<'info, TokenAccount>,
+
     pub token_program: Program<'info, Token>,
 }
 
@@ -0,0 +1,96 @@
+use crate::errors::ErrorCode;
+use crate::states::*;
+use crate::SignerAccount;
+use anchor_lang::prelude::*;
+use arcium_anchor::prelude::*;
+use crate::instructions::withdraw_verify_computation;
+use crate::UpdateLedgerWithdrawVerifyCallback;
+use crate::COMP_DEF_OFFSET_UPDATE_LEDGER_WITHDRAW_VERIFY;
+
+use crate::ID;
+use crate::ID_CONST;
+
+// Debits accrued settlement fees from the authority's ledger. Same two step
+// flow as a user withdrawal: the verified event lets the cranker release the
+// tokens with withdraw_from_vault.
+pub fn withdraw_protocol_fees(
+    ctx: Context<WithdrawProtocolFees>,
+    authority_enc_pubkey: [u8; 32],
+    amount: u64,
+    is_base_token: bool,
+    computation_offset: u64,
+) -> Result<()> {
+    ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+    let (args, callback_accounts) = withdraw_verify_computation(
+        authority_enc_pubkey,
+        &ctx.accounts.fee_ledger,
+        amount,
+        is_base_token,
+    )?;
+
+    queue_computation(
+        ctx.accounts,
+        computation_offset,
+        args,
+        None,
+        vec![UpdateLedgerWithdrawVerifyCallback::callback_ix(&callback_accounts)],
+    )?;
+
+    Ok(())
+}
+
+#[queue_computation_accounts("update_ledger_withdraw_verify", authority)]
+#[derive(Accounts)]
+#[instruction(
+    authority_enc_pubkey: [u8; 32],
+    amount: u64,
+    is_base_token: bool,
+    computation_offset: u64,
+)]
+pub struct WithdrawProtocolFees<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = authority,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, SignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_UPDATE_LEDGER_WITHDRAW_VERIFY))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Account<'info, ClockAccount>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+
+    #[account(
+        constraint = orderbook_state.load()?.authority == authority.key() @ ErrorCode::UnauthorizedAuthority,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump,
+    )]
+    pub orderbook_state: AccountLoader<'info, OrderBookState>,
+    #[account(
+        mut,
+        constraint = fee_ledger.load()?.owner == authority.key() @ ErrorCode::InvalidFeeLedger,
+    )]
+    pub fee_ledger: AccountLoader<'info, UserPrivateLedger>,
+}
@@ -1,8 +1,10 @@
 use anchor_lang::prelude::*;
 use arcium_anchor::prelude::*;
 use arcium_client::idl::arcium::types::CallbackAccount;
+use anchor_spl::token::{self, Token, TokenAccount, Transfer};
 
 const COMP_DEF_OFFSET_MATCH_ORDERS: u32 = comp_def_offset("match_orders");
+const COMP_DEF_OFFSET_MATCH_ORDERS_UNIFORM: u32 = comp_def_offset("match_orders_uniform");
 const COMP_DEF_OFFSET_SUBMIT_ORDER: u32 = comp_def_offset("submit_order");
 const COMP_DEF_OFFSET_SUBMIT_ORDER_CHECK: u32 = comp_def_offset("submit_order_check");
 const COMP_DEF_OFFSET_INIT_ORDER_BOOK: u32 = comp_def_offset("init_order_book");
@@ -12,7 +14,46 @@ const COMP_DEF_OFFSET_UPDATE_LEDGER_WITHDRAW_VERIFY: u32 =
 // const COMP_DEF_OFFSET_UPDATE_SETTLEMENT: u32 = comp_def_offset("update_settlement");
 const COMP_DEF_OFFSET_INIT_USER_LEDGER: u32 = comp_def_offset("init_user_ledger");
 const COMP_DEF_OFFSET_EXECUTE_SETTLEMENT: u32 = comp_def_offset("execute_settlement");
+const COMP_DEF_OFFSET_RELEASE_ORDER_LOCK: u32 = comp_def_offset("release_order_lock");
+const COMP_DEF_OFFSET_BATCH_AUCTION: u32 = comp_def_offset("batch_auction");
+const COMP_DEF_OFFSET_INIT_TRIGGER_BOOK: u32 = comp_def_offset("init_trigger_book");
+const COMP_DEF_OFFSET_SUBMIT_STOP_ORDER: u32 = comp_def_offset("submit_stop_order");
+const COMP_DEF_OFFSET_EVALUATE_TRIGGERS: u32 = comp_def_offset("evaluate_triggers");
+const COMP_DEF_OFFSET_SUBMIT_TWAP_ORDER: u32 = comp_def_offset("submit_twap_order");
+const COMP_DEF_OFFSET_RELEASE_TWAP_SLICE: u32 = comp_def_offset("release_twap_slice");
+const COMP_DEF_OFFSET_CREATE_RECURRING_ORDER: u32 = comp_def_offset("create_recurring_order");
+const COMP_DEF_OFFSET_EXECUTE_RECURRING_ORDER: u32 = comp_def_offset("execute_recurring_order");
 const MAX_ORDERS: usize = 4;
+const MAX_LOCK_RELEASES: usize = 2 * MAX_ORDERS;
+const MAX_AUCTION_FILLS: usize = 2 * MAX_ORDERS;
+// fills (buyer_order_id, seller_order_id, quantity) + num_fills + releases + num_releases
+const AUCTION_RESULT_CHUNKS: usize = 3 * MAX_AUCTION_FILLS + 1 + 3 * MAX_LOCK_RELEASES + 1;
+pub const ORDER_KIND_LIMIT: u8 = 0;
+pub const ORDER_KIND_MARKET: u8 = 1;
+pub const ORDER_KIND_PEG: u8 = 2;
+pub const ORDER_KIND_STOP: u8 = 3;
+pub const ORDER_KIND_STOP_LIMIT: u8 = 4;
+pub const PRICING_RULE_UNIFORM: u8 = 3;
+pub const STP_DECREMENT_AND_CANCEL: u8 = 4;
+pub const MARKET_MODE_CONTINUOUS: u8 = 0;
+pub const MARKET_MODE_BATCH_AUCTION: u8 = 1;
+pub const ALLOCATION_PRO_RATA: u8 = 1;
+pub const SESSION_CLOSED: u8 = 0;
+pub const SESSION_PRE_OPEN: u8 = 1;
+pub const SESSION_OPENING_AUCTION: u8 = 2;
+pub const SESSION_CONTINUOUS: u8 = 3;
+pub const SESSION_CLOSING_AUCTION: u8 = 4;
+pub const PRICE_BAND_SOURCE_CLEARING: u8 = 0;
+pub const PRICE_BAND_SOURCE_ORACLE: u8 = 1;
+pub const POST_ONLY_NONE: u8 = 0;
+pub const POST_ONLY_SLIDE: u8 = 2;
+pub const GROUP_TYPE_OCO: u8 = 0;
+pub const GROUP_TYPE_BRACKET: u8 = 1;
+pub const GROUP_ROLE_NONE: u8 = 0;
+pub const GROUP_ROLE_OCO: u8 = 1;
+pub const GROUP_ROLE_ENTRY: u8 = 2;
+pub const GROUP_ROLE_EXIT: u8 = 3;
+pub const MAX_FEE_BPS: u16 = 1_000;
 declare_id!("3MSz7Kkyf6yXC1puWY8gofiPqCHPqYYMdMeZd3KYDi3y");
 
 pub mod instructions;
@@ -90,11 +131,65 @@ pub mod matching_engine {
         Ok(())
     }
 
+    pub fn init_match_orders_uniform_comp_def(
+        ctx: Context<InitMatchOrdersUniformCompDef>,
+    ) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
     pub fn init_order_book_comp_def(ctx: Context<InitOrderBookCompDef>) -> Result<()> {
         init_comp_def(ctx.accounts, true, 0, None, None)?;
         Ok(())
     }
 
+    pub fn init_release_order_lock_comp_def(
+        ctx: Context<InitReleaseOrderLockCompDef>,
+    ) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_batch_auction_comp_def(ctx: Context<InitBatchAuctionCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_trigger_book_comp_def(ctx: Context<InitTriggerBookCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_submit_stop_order_comp_def(ctx: Context<InitSubmitStopOrderCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_evaluate_triggers_comp_def(ctx: Context<InitEvaluateTriggersCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_submit_twap_order_comp_def(ctx: Context<InitSubmitTwapOrderCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_release_twap_slice_comp_def(ctx: Context<InitReleaseTwapSliceCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_create_recurring_order_comp_def(ctx: Context<InitCreateRecurringOrderCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_execute_recurring_order_comp_def(ctx: Context<InitExecuteRecurringOrderCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
     pub fn initialize(
         ctx: Context<Initialize>,
         backend_pubkey: [u8; 32],
@@ -105,6 +200,39 @@ pub mod matching_engine {
         Ok(())
     }
 
+    pub fn set_price_feed(ctx: Context<SetPriceFeed>) -> Result<()> {
+        instructions::set_price_feed(ctx)?;
+        Ok(())
+    }
+
+    pub fn set_pricing_rule(ctx: Context<UpdateMarketConfig>, pricing_rule: u8) -> Result<()> {
+        instructions::set_pricing_rule(ctx, pricing_rule)?;
+        Ok(())
+    }
+
+    pub fn set_market_mode(
+        ctx: Context<UpdateMarketConfig>,
+        market_mode: u8,
+        auction_allocation: u8,
+    ) -> Result<()> {
+        instructions::set_market_mode(ctx, market_mode, auction_allocation)?;
+        Ok(())
+    }
+
+    pub fn set_stp_policy(ctx: Context<UpdateMarketConfig>, stp_policy: u8) -> Result<()> {
+        instructions::set_stp_policy(ctx, stp_policy)?;
+        Ok(())
+    }
+
+    pub fn set_allocation_mode(
+        ctx: Context<UpdateMarketConfig>,
+        allocation_mode: u8,
+        top_priority_pct: u8,
+    ) -> Result<()> {
+        instructions::set_allocation_mode(ctx, allocation_mode, top_priority_pct)?;
+        Ok(())
+    }
+
     pub fn init_encrypted_orderbook(
         ctx: Context<InitEncryptedOrderbook>,
         computation_offset: u64,
@@ -157,25 +285,175 @@ pub mod matching_engine {
     }
 
 
+    pub fn set_tick_size(ctx: Context<UpdateMarketConfig>, tick_size: u64) -> Result<()> {
+        instructions::set_tick_size(ctx, tick_size)?;
+        Ok(())
+    }
+
+    pub fn set_price_band(
+        ctx: Context<UpdateMarketConfig>,
+        price_band_bps: u16,
+        price_band_source: u8,
+        band_halt_threshold: u8,
+    ) -> Result<()> {
+        instructions::set_price_band(ctx, price_band_bps, price_band_source, band_halt_threshold)?;
+        Ok(())
+    }
+
+    pub fn set_entry_band(ctx: Context<UpdateMarketConfig>, entry_band_bps: u16) -> Result<()> {
+        instructions::set_entry_band(ctx, entry_band_bps)?;
+        Ok(())
+    }
+
+    pub fn set_fee_rates(
+        ctx: Context<UpdateMarketConfig>,
+        maker_fee_bps: u16,
+        taker_fee_bps: u16,
+    ) -> Result<()> {
+        instructions::set_fee_rates(ctx, maker_fee_bps, taker_fee_bps)?;
+        Ok(())
+    }
+
+    pub fn set_fee_tiers(
+        ctx: Context<UpdateMarketConfig>,
+        thresholds: [u64; FEE_TIERS],
+        maker_tier_fee_bps: [u16; FEE_TIERS],
+        taker_tier_fee_bps: [u16; FEE_TIERS],
+    ) -> Result<()> {
+        instructions::set_fee_tiers(ctx, thresholds, maker_tier_fee_bps, taker_tier_fee_bps)?;
+        Ok(())
+    }
+
+    pub fn set_referral_share(
+        ctx: Context<UpdateMarketConfig>,
+        referral_share_bps: u16,
+    ) -> Result<()> {
+        instructions::set_referral_share(ctx, referral_share_bps)?;
+        Ok(())
+    }
+
+    pub fn register_referral(ctx: Context<RegisterReferral>) -> Result<()> {
+        instructions::register_referral(ctx)?;
+        Ok(())
+    }
+
+    pub fn set_transfer_fees(
+        ctx: Context<UpdateMarketConfig>,
+        deposit_fee_bps: u16,
+        withdraw_fee_bps: u16,
+    ) -> Result<()> {
+        instructions::set_transfer_fees(ctx, deposit_fee_bps, withdraw_fee_bps)?;
+        Ok(())
+    }
+
+    pub fn collect_fees(ctx: Context<CollectFees>) -> Result<()> {
+        instructions::collect_fees(ctx)?;
+        Ok(())
+    }
+
+    pub fn set_order_bond(ctx: Context<UpdateMarketConfig>, order_bond_lamports: u64) -> Result<()> {
+        instructions::set_order_bond(ctx, order_bond_lamports)?;
+        Ok(())
+    }
+
+    pub fn release_order_bond(ctx: Context<ReleaseOrderBond>, slash: bool) -> Result<()> {
+        instructions::release_order_bond(ctx, slash)?;
+        Ok(())
+    }
+
+    pub fn set_order_limits(
+        ctx: Context<UpdateMarketConfig>,
+        max_open_orders: u16,
+        max_submissions_per_window: u16,
+        submission_window: u32,
+    ) -> Result<()> {
+        instructions::set_order_limits(
+            ctx,
+            max_open_orders,
+            max_submissions_per_window,
+            submission_window,
+        )?;
+        Ok(())
+    }
+
+    pub fn close_open_order(ctx: Context<CloseOpenOrder>) -> Result<()> {
+        instructions::close_open_order(ctx)?;
+        Ok(())
+    }
+
+    pub fn resume_market(ctx: Context<UpdateMarketConfig>) -> Result<()> {
+        instructions::resume_market(ctx)?;
+        Ok(())
+    }
+
+    pub fn set_order_size_limits(
+        ctx: Context<UpdateMarketConfig>,
+        lot_size: u64,
+        min_notional: u64,
+    ) -> Result<()> {
+        instructions::set_order_size_limits(ctx, lot_size, min_notional)?;
+        Ok(())
+    }
+
+    pub fn set_trading_sessions(
+        ctx: Context<UpdateMarketConfig>,
+        session_enabled: bool,
+        pre_open_time: u32,
+        opening_auction_time: u32,
+        continuous_time: u32,
+        closing_auction_time: u32,
+        close_time: u32,
+    ) -> Result<()> {
+        instructions::set_trading_sessions(
+            ctx,
+            session_enabled,
+            pre_open_time,
+            opening_auction_time,
+            continuous_time,
+            closing_auction_time,
+            close_time,
+        )?;
+        Ok(())
+    }
+
+    pub fn create_order_group(
+        ctx: Context<CreateOrderGroup>,
+        group_id: u64,
+        group_type: u8,
+    ) -> Result<()> {
+        instructions::create_order_group(ctx, group_id, group_type)?;
+        Ok(())
+    }
+
     pub fn submit_order_check(
         ctx: Context<SubmitOrderCheck>,
         amount: [u8; 32],
         price: [u8; 32],
+        quote_budget: [u8; 32],
+        min_fill_qty: [u8; 32],
+        display_size: [u8; 32],
         user_enc_pubkey: [u8; 32],
         order_type: u8,
+        order_kind: u8,
         computation_offset: u64,
         order_id: u64,
         order_nonce: u128,
+        lock_nonce: u128,
     ) -> Result<()> {
         instructions::submit_order_check(
             ctx,
             amount,
             price,
+            quote_budget,
+            min_fill_qty,
+            display_size,
             user_enc_pubkey,
             order_type,
+            order_kind,
             computation_offset,
             order_id,
             order_nonce,
+            lock_nonce,
         )?;
         Ok(())
     }
@@ -184,8 +462,13 @@ pub mod matching_engine {
         ctx: Context<SubmitOrder>,
         amount: [u8; 32],
         price: [u8; 32],
+        quote_budget: [u8; 32],
+        min_fill_qty: [u8; 32],
+        display_size: [u8; 32],
         user_enc_pubkey: [u8; 32],
         order_type: u8,
+        order_kind: u8,
+        post_only: u8,
         computation_offset: u64,
         order_id: u64,
         order_nonce: u128,
@@ -194,8 +477,13 @@ pub mod matching_engine {
             ctx,
             amount,
             price,
+            quote_budget,
+            min_fill_qty,
+            display_size,
             user_enc_pubkey,
             order_type,
+            order_kind,
+            post_only,
             computation_offset,
             order_id,
             order_nonce,
@@ -216,25 +504,590 @@ pub mod matching_engine {
         process_match_orders_result(ctx, output)
     }
 
+    pub fn trigger_uniform_matching(
+        ctx: Context<TriggerUniformMatching>,
+        computation_offset: u64,
+        backend_nonce: u128,
+    ) -> Result<()> {
+        instructions::trigger_uniform_matching(ctx, computation_offset, backend_nonce)?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "match_orders_uniform", network = "localnet")]
+    pub fn match_orders_uniform_callback(
+        ctx: Context<MatchOrdersUniformCallback>,
+        output: ComputationOutputs<MatchOrdersUniformOutput>,
+    ) -> Result<()> {
+        process_match_orders_uniform_result(ctx, output)
+    }
+
+    pub fn trigger_batch_auction(
+        ctx: Context<TriggerBatchAuction>,
+        computation_offset: u64,
+        backend_nonce: u128,
+    ) -> Result<()> {
+        instructions::trigger_batch_auction(ctx, computation_offset, backend_nonce)?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "batch_auction", network = "localnet")]
+    pub fn batch_auction_callback(
+        ctx: Context<BatchAuctionCallback>,
+        output: ComputationOutputs<BatchAuctionOutput>,
+    ) -> Result<()> {
+        process_batch_auction_result(ctx, output)
+    }
+
+    #[inline(never)]
+    pub fn process_batch_auction_result(
+        ctx: Context<BatchAuctionCallback>,
+        output: ComputationOutputs<BatchAuctionOutput>,
+    ) -> Result<()> {
+        match &output {
+            ComputationOutputs::Success(BatchAuctionOutput { field_0 }) => {
+                let orderbook_enc = &field_0.field_0;
+                let result_enc = &field_0.field_1;
+                let clearing_price = field_0.field_2;
+                let volume = field_0.field_3;
+
+                let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+                orderbook_state.orderbook_nonce = orderbook_enc.nonce;
+                orderbook_state.orderbook_data = orderbook_enc.ciphertexts;
+                orderbook_state.last_clearing_price = clearing_price;
+                orderbook_state.last_clearing_volume = volume;
+
+                // Fill count and releases stay encrypted, the backend decrypts
+                // them to settle every fill at the clearing price
+                let result: [[u8; 32]; AUCTION_RESULT_CHUNKS] =
+                    result_enc.ciphertexts[0..AUCTION_RESULT_CHUNKS].try_into().unwrap();
+
+                emit!(BatchAuctionClearedEvent {
+                    clearing_price,
+                    volume,
+                    result,
+                    nonce: result_enc.nonce,
+                    timestamp: Clock::get()?.unix_timestamp,
+                });
+
+                Ok(())
+            }
+            _ => Err(ErrorCode::AbortedComputation.into()),
+        }
+    }
+
+    pub fn init_trigger_book(
+        ctx: Context<InitTriggerBook>,
+        computation_offset: u64,
+        nonce: u128,
+    ) -> Result<()> {
+        instructions::init_trigger_book(ctx, computation_offset, nonce)?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "init_trigger_book", network = "localnet")]
+    pub fn init_trigger_book_callback(
+        ctx: Context<InitTriggerBookCallback>,
+        output: ComputationOutputs<InitTriggerBookOutput>,
+    ) -> Result<()> {
+        let trigger_book_enc = match &output {
+            ComputationOutputs::Success(InitTriggerBookOutput { field_0 }) => field_0,
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        let mut trigger_book = ctx.accounts.trigger_book_state.load_mut()?;
+        trigger_book.trigger_nonce = trigger_book_enc.nonce;
+        trigger_book.trigger_data = trigger_book_enc.ciphertexts;
+
+        msg!("Trigger book initialized");
+        Ok(())
+    }
+
+    pub fn submit_stop_order(
+        ctx: Context<SubmitStopOrder>,
+        amount: [u8; 32],
+        price: [u8; 32],
+        quote_budget: [u8; 32],
+        min_fill_qty: [u8; 32],
+        trigger_price: [u8; 32],
+        user_enc_pubkey: [u8; 32],
+        order_type: u8,
+        order_kind: u8,
+        computation_offset: u64,
+        order_id: u64,
+        order_nonce: u128,
+    ) -> Result<()> {
+        instructions::submit_stop_order(
+            ctx,
+            amount,
+            price,
+            quote_budget,
+            min_fill_qty,
+            trigger_price,
+            user_enc_pubkey,
+            order_type,
+            order_kind,
+            computation_offset,
+            order_id,
+            order_nonce,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "submit_stop_order", network = "localnet")]
+    pub fn submit_stop_order_callback(
+        ctx: Context<SubmitStopOrderCallback>,
+        output: ComputationOutputs<SubmitStopOrderOutput>,
+    ) -> Result<()> {
+        match &output {
+            ComputationOutputs::Success(SubmitStopOrderOutput { field_0 }) => {
+                let trigger_book_enc = &field_0.field_0;
+                let ledger_enc = &field_0.field_1;
+                let lock_enc = &field_0.field_2;
+                let success = field_0.field_3;
+                let overflow = field_0.field_4;
+                if overflow {
+                    // The stop wasn't parked and its lock is still held
+                    return report_arithmetic_error(
+                        ErrorCode::LockReleaseOverflow,
+                        ctx.accounts.order_account.user,
+                    );
+                }
+
+                let mut trigger_book = ctx.accounts.trigger_book_state.load_mut()?;
+                trigger_book.trigger_nonce = trigger_book_enc.nonce;
+                trigger_book.trigger_data = trigger_book_enc.ciphertexts;
+                if success {
+                    trigger_book.total_stop_orders += 1;
+                }
+
+                // A stop the trigger book had no room for was refunded
+                let mut user_ledger = ctx.accounts.user_ledger.load_mut()?;
+                user_ledger.balance_nonce = ledger_enc.nonce;
+                user_ledger.encrypted_balances = ledger_enc.ciphertexts;
+                user_ledger.last_update = Clock::get()?.unix_timestamp;
+
+                ctx.accounts.order_account.lock_nonce = lock_enc.nonce;
+                ctx.accounts.order_account.encrypted_lock = lock_enc.ciphertexts;
+
+                // The group's first leg leaves its check result and lock for the
+                // legs that share it
+                let order_id = ctx.accounts.order_account.order_id;
+                let order_type = ctx.accounts.order_account.order_type;
+                if let Some(order_group) = ctx.accounts.order_group.as_mut() {
+                    if order_group.leg_order_ids[0] == order_id {
+                        order_group.lock_status = if success { GROUP_LOCK_READY } else { GROUP_LOCK_REJECTED };
+                        order_group.lock_order_type = order_type;
+                        order_group.lock_nonce = lock_enc.nonce;
+                        order_group.encrypted_lock = lock_enc.ciphertexts;
+                    }
+                }
+
+                emit!(StopOrderSubmittedEvent {
+                    order_id: ctx.accounts.order_account.order_id,
+                    success,
+                    timestamp: Clock::get()?.unix_timestamp,
+                });
+
+                Ok(())
+            }
+            _ => Err(ErrorCode::AbortedComputation.into()),
+        }
+    }
+
+    pub fn evaluate_triggers(ctx: Context<EvaluateTriggers>, computation_offset: u64) -> Result<()> {
+        instructions::evaluate_triggers(ctx, computation_offset)?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "evaluate_triggers", network = "localnet")]
+    pub fn evaluate_triggers_callback(
+        ctx: Context<EvaluateTriggersCallback>,
+        output: ComputationOutputs<EvaluateTriggersOutput>,
+    ) -> Result<()> {
+        process_evaluate_triggers_result(ctx, output)
+    }
+
+    #[inline(never)]
+    pub fn process_evaluate_triggers_result(
+        ctx: Context<EvaluateTriggersCallback>,
+        output: ComputationOutputs<EvaluateTriggersOutput>,
+    ) -> Result<()> {
+        match &output {
+            ComputationOutputs::Success(EvaluateTriggersOutput { field_0 }) => {
+                let trigger_book_enc = &field_0.field_0;
+                let orderbook_enc = &field_0.field_1;
+                let triggered_count = field_0.field_2;
+
+                let mut trigger_book = ctx.accounts.trigger_book_state.load_mut()?;
+                trigger_book.trigger_nonce = trigger_book_enc.nonce;
+                trigger_book.trigger_data = trigger_book_enc.ciphertexts;
+                trigger_book.total_triggered += triggered_count as u64;
+
+                let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+                orderbook_state.orderbook_nonce = orderbook_enc.nonce;
+                orderbook_state.orderbook_data = orderbook_enc.ciphertexts;
+
+                emit!(TriggersEvaluatedEvent {
+                    triggered_count,
+                    timestamp: Clock::get()?.unix_timestamp,
+                });
+
+                Ok(())
+            }
+            _ => Err(ErrorCode::AbortedComputation.into()),
+        }
+    }
+
+    pub fn submit_twap_order(
+        ctx: Context<SubmitTwapOrder>,
+        slice_size: [u8; 32],
+        user_enc_pubkey: [u8; 32],
+        order_type: u8,
+        end_time: i64,
+        computation_offset: u64,
+        order_id: u64,
+        order_nonce: u128,
+        twap_nonce: u128,
+    ) -> Result<()> {
+        instructions::submit_twap_order(
+            ctx,
+            slice_size,
+            user_enc_pubkey,
+            order_type,
+            end_time,
+            computation_offset,
+            order_id,
+            order_nonce,
+            twap_nonce,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "submit_twap_order", network = "localnet")]
+    pub fn submit_twap_order_callback(
+        ctx: Context<SubmitTwapOrderCallback>,
+        output: ComputationOutputs<SubmitTwapOrderOutput>,
+    ) -> Result<()> {
+        let (twap_enc, lock_enc) = match &output {
+            ComputationOutputs::Success(SubmitTwapOrderOutput { field_0 }) => (&field_0.field_0, &field_0.field_1),
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        let mut twap_order = ctx.accounts.twap_order_state.load_mut()?;
+        twap_order.twap_nonce = twap_enc.nonce;
+        twap_order.twap_data = twap_enc.ciphertexts;
+
+        ctx.accounts.order_account.lock_nonce = lock_enc.nonce;
+        ctx.accounts.order_account.encrypted_lock = lock_enc.ciphertexts;
+
+        emit!(TwapOrderSubmittedEvent {
+            order_id: twap_order.order_id,
+            user: twap_order.user,
+            end_time: twap_order.end_time,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn release_twap_slice(
+        ctx: Context<ReleaseTwapSlice>,
+        computation_offset: u64,
+        slice_order_id: u64,
+        lock_nonce: u128,
+    ) -> Result<()> {
+        instructions::release_twap_slice(ctx, computation_offset, slice_order_id, lock_nonce)?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "release_twap_slice", network = "localnet")]
+    pub fn release_twap_slice_callback(
+        ctx: Context<ReleaseTwapSliceCallback>,
+        output: ComputationOutputs<ReleaseTwapSliceOutput>,
+    ) -> Result<()> {
+        process_release_twap_slice_result(ctx, output)
+    }
+
+    #[inline(never)]
+    pub fn process_release_twap_slice_result(
+        ctx: Context<ReleaseTwapSliceCallback>,
+        output: ComputationOutputs<ReleaseTwapSliceOutput>,
+    ) -> Result<()> {
+        match &output {
+            ComputationOutputs::Success(ReleaseTwapSliceOutput { field_0 }) => {
+                let twap_enc = &field_0.field_0;
+                let orderbook_enc = &field_0.field_1;
+                let ledger_enc = &field_0.field_2;
+                let lock_enc = &field_0.field_3;
+                let released = field_0.field_4;
+                let completed = field_0.field_5;
+                let overflow = field_0.field_6;
+
+                let mut twap_order = ctx.accounts.twap_order_state.load_mut()?;
+                twap_order.twap_nonce = twap_enc.nonce;
+                twap_order.twap_data = twap_enc.ciphertexts;
+                if released {
+                    twap_order.slices_released += 1;
+                }
+                if completed {
+                    twap_order.completed = 1;
+                }
+
+                let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+                orderbook_state.orderbook_nonce = orderbook_enc.nonce;
+                orderbook_state.orderbook_data = orderbook_enc.ciphertexts;
+                if released {
+                    orderbook_state.total_orders_processed += 1;
+                }
+
+                // Only changes once an expired parent hands back its unreleased lock
+                let mut user_ledger = ctx.accounts.user_ledger.load_mut()?;
+                user_ledger.balance_nonce = ledger_enc.nonce;
+                user_ledger.encrypted_balances = ledger_enc.ciphertexts;
+                user_ledger.last_update = Clock::get()?.unix_timestamp;
+
+                // A slice that never reached the book has nothing to release
+                // and stops counting as open right away
+                let slice_order = &mut ctx.accounts.slice_order_account;
+                slice_order.lock_nonce = lock_enc.nonce;
+                slice_order.encrypted_lock = lock_enc.ciphertexts;
+                if !released {
+                    if slice_order.open == 1 {
+                        slice_order.open = 0;
+                        ctx.accounts.user_trading_state.record_closed();
+                    }
+                }
+
+                emit!(TwapSliceReleasedEvent {
+                    order_id: twap_order.order_id,
+                    slice_order_id: slice_order.order_id,
+                    released,
+                    completed,
+                    slices_released: twap_order.slices_released,
+                    timestamp: Clock::get()?.unix_timestamp,
+                });
+
+                // The parent stays open with its lock, the next crank retries
+                if overflow {
+                    return report_arithmetic_error(ErrorCode::LockReleaseOverflow, user_ledger.owner);
+                }
+
+                Ok(())
+            }
+            _ => Err(ErrorCode::AbortedComputation.into()),
+        }
+    }
+
+    pub fn create_recurring_order(
+        ctx: Context<CreateRecurringOrder>,
+        quote_amount: [u8; 32],
+        max_price: [u8; 32],
+        user_enc_pubkey: [u8; 32],
+        period: i64,
+        total_periods: u64,
+        computation_offset: u64,
+        order_id: u64,
+        order_nonce: u128,
+        schedule_nonce: u128,
+    ) -> Result<()> {
+        instructions::create_recurring_order(
+            ctx,
+            quote_amount,
+            max_price,
+            user_enc_pubkey,
+            period,
+            total_periods,
+            computation_offset,
+            order_id,
+            order_nonce,
+            schedule_nonce,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "create_recurring_order", network = "localnet")]
+    pub fn create_recurring_order_callback(
+        ctx: Context<CreateRecurringOrderCallback>,
+        output: ComputationOutputs<CreateRecurringOrderOutput>,
+    ) -> Result<()> {
+        let schedule_enc = match &output {
+            ComputationOutputs::Success(CreateRecurringOrderOutput { field_0 }) => field_0,
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        let mut recurring_order = ctx.accounts.recurring_order_state.load_mut()?;
+        recurring_order.schedule_nonce = schedule_enc.nonce;
+        recurring_order.schedule_data = schedule_enc.ciphertexts;
+
+        emit!(RecurringOrderCreatedEvent {
+            order_id: recurring_order.order_id,
+            user: recurring_order.user,
+            period: recurring_order.period,
+            total_periods: recurring_order.total_periods,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn execute_recurring_order(
+        ctx: Context<ExecuteRecurringOrder>,
+        computation_offset: u64,
+        child_order_id: u64,
+        lock_nonce: u128,
+    ) -> Result<()> {
+        instructions::execute_recurring_order(ctx, computation_offset, child_order_id, lock_nonce)?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "execute_recurring_order", network = "localnet")]
+    pub fn execute_recurring_order_callback(
+        ctx: Context<ExecuteRecurringOrderCallback>,
+        output: ComputationOutputs<ExecuteRecurringOrderOutput>,
+    ) -> Result<()> {
+        process_execute_recurring_order_result(ctx, output)
+    }
+
+    #[inline(never)]
+    pub fn process_execute_recurring_order_result(
+        ctx: Context<ExecuteRecurringOrderCallback>,
+        output: ComputationOutputs<ExecuteRecurringOrderOutput>,
+    ) -> Result<()> {
+        match &output {
+            ComputationOutputs::Success(ExecuteRecurringOrderOutput { field_0 }) => {
+                let orderbook_enc = &field_0.field_0;
+                let ledger_enc = &field_0.field_1;
+                let lock_enc = &field_0.field_2;
+                let funded = field_0.field_3;
+                let placed = field_0.field_4;
+
+                let mut recurring_order = ctx.accounts.recurring_order_state.load_mut()?;
+                if placed {
+                    recurring_order.periods_executed += 1;
+                }
+                // The schedule ends with its last period or with the balance
+                if !funded || recurring_order.periods_executed >= recurring_order.total_periods {
+                    recurring_order.active = 0;
+                }
+
+                let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+                orderbook_state.orderbook_nonce = orderbook_enc.nonce;
+                orderbook_state.orderbook_data = orderbook_enc.ciphertexts;
+                if placed {
+                    orderbook_state.total_orders_processed += 1;
+                }
+
+                let mut user_ledger = ctx.accounts.user_ledger.load_mut()?;
+                user_ledger.balance_nonce = ledger_enc.nonce;
+                user_ledger.encrypted_balances = ledger_enc.ciphertexts;
+                user_ledger.last_update = Clock::get()?.unix_timestamp;
+
+                // A child that never reached the book has nothing to release
+                // and stops counting as open right away
+                let child_order = &mut ctx.accounts.child_order_account;
+                child_order.lock_nonce = lock_enc.nonce;
+                child_order.encrypted_lock = lock_enc.ciphertexts;
+                if !placed {
+                    child_order.open = 0;
+                    ctx.accounts.user_trading_state.record_closed();
+                }
+
+                emit!(RecurringOrderExecutedEvent {
+                    order_id: recurring_order.order_id,
+                    child_order_id: child_order.order_id,
+                    funded,
+                    placed,
+                    periods_executed: recurring_order.periods_executed,
+                    active: recurring_order.active == 1,
+                    timestamp: Clock::get()?.unix_timestamp,
+                });
+
+                Ok(())
+            }
+            _ => Err(ErrorCode::AbortedComputation.into()),
+        }
+    }
+
     pub fn execute_settlement(
         ctx: Context<ExecuteSettlement>,
         user1_enc_pubkey: [u8; 32],
         user2_enc_pubkey: [u8; 32],
+        fee_enc_pubkey: [u8; 32],
+        referrer_enc_pubkey: [u8; 32],
         execution_price: u64,
         is_base: bool,
+        receiver_is_maker: bool,
         computation_offset: u64,
     ) -> Result<()> {
         instructions::execute_settlement(
             ctx,
             user1_enc_pubkey,
             user2_enc_pubkey,
+            fee_enc_pubkey,
+            referrer_enc_pubkey,
             execution_price,
             is_base,
+            receiver_is_maker,
             computation_offset,
         )?;
         Ok(())
     }
 
+    pub fn release_order_lock(
+        ctx: Context<ReleaseOrderLock>,
+        user_enc_pubkey: [u8; 32],
+        order_id: u64,
+        amount: u64,
+        order_type: u8,
+        computation_offset: u64,
+    ) -> Result<()> {
+        instructions::release_order_lock(
+            ctx,
+            user_enc_pubkey,
+            order_id,
+            amount,
+            order_type,
+            computation_offset,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "release_order_lock", network = "localnet")]
+    pub fn release_order_lock_callback(
+        ctx: Context<ReleaseOrderLockCallback>,
+        output: ComputationOutputs<ReleaseOrderLockOutput>,
+    ) -> Result<()> {
+        match &output {
+            ComputationOutputs::Success(ReleaseOrderLockOutput { field_0 }) => {
+                let ledger_enc = &field_0.field_0;
+                let lock_enc = &field_0.field_1;
+                let overflow = field_0.field_2;
+                if overflow {
+                    let user = ctx.accounts.user_ledger.load()?.owner;
+                    return report_arithmetic_error(ErrorCode::LockReleaseOverflow, user);
+                }
+
+                let order_account = &mut ctx.accounts.order_account;
+                order_account.lock_nonce = lock_enc.nonce;
+                order_account.encrypted_lock = lock_enc.ciphertexts;
+
+                let ledger = &mut ctx.accounts.user_ledger.load_mut()?;
+                ledger.balance_nonce = ledger_enc.nonce;
+                ledger.encrypted_balances = ledger_enc.ciphertexts;
+                ledger.last_update = Clock::get()?.unix_timestamp;
+
+                emit!(OrderLockReleasedEvent {
+                    user: ledger.owner,
+                    balance_nonce: ledger.balance_nonce,
+                    encrypted_balances: ledger.encrypted_balances,
+                    last_update: ledger.last_update,
+                });
+                Ok(())
+            }
+            _ => Err(ErrorCode::AbortedComputation.into()),
+        }
+    }
+
     #[inline(never)]
     pub fn process_match_orders_result(
         ctx: Context<MatchOrdersCallback>,
@@ -244,44 +1097,46 @@ pub mod matching_engine {
             ComputationOutputs::Success(MatchOrdersOutput { field_0 }) => {
                 let orderbook_enc = &field_0.field_0;
                 let matches_enc = &field_0.field_1;
-                let num_matches = field_0.field_2;
 
-                // Update orderbook
                 let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
                 orderbook_state.orderbook_nonce = orderbook_enc.nonce;
                 orderbook_state.orderbook_data = orderbook_enc.ciphertexts;
+                record_match_batch(
+                    &mut orderbook_state,
+                    matches_enc.nonce,
+                    &matches_enc.ciphertexts,
+                    field_0.field_2,
+                    field_0.field_3,
+                    field_0.field_4,
+                    field_0.field_5,
+                )
+            }
+            _ => Err(ErrorCode::AbortedComputation.into()),
+        }
+    }
 
-                if num_matches > 0 {
-                    // Create MatchResult accounts for each match
-                    // The encrypted matches will be decrypted by backend
-
-                    let match1 = matches_enc.ciphertexts[0..5].try_into().unwrap();
-                    let mut match2 = [[0u8; 32]; 5];
-                    let mut match3 = [[0u8; 32]; 5];
-                    let mut match4 = [[0u8; 32]; 5];
-                    if num_matches > 1 {
-                        match2 = matches_enc.ciphertexts[5..10].try_into().unwrap();
-                    }
-                    if num_matches > 2 {
-                        match3 = matches_enc.ciphertexts[10..15].try_into().unwrap();
-                    }
-                    if num_matches > 3 {
-                        match4 = matches_enc.ciphertexts[15..20].try_into().unwrap();
-                    }
-                    orderbook_state.total_matches += num_matches as u64;
-
-                    emit!(MatchesFoundEvent {
-                        num_matches,
-                        match1,
-                        match2,
-                        match3,
-                        match4,
-                        nonce: matches_enc.nonce,
-                        timestamp: Clock::get()?.unix_timestamp,
-                    });
-                }
+    #[inline(never)]
+    pub fn process_match_orders_uniform_result(
+        ctx: Context<MatchOrdersUniformCallback>,
+        output: ComputationOutputs<MatchOrdersUniformOutput>,
+    ) -> Result<()> {
+        match &output {
+            ComputationOutputs::Success(MatchOrdersUniformOutput { field_0 }) => {
+                let orderbook_enc = &field_0.field_0;
+                let matches_enc = &field_0.field_1;
 
-                Ok(())
+                let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+                orderbook_state.orderbook_nonce = orderbook_enc.nonce;
+                orderbook_state.orderbook_data = orderbook_enc.ciphertexts;
+                record_match_batch(
+                    &mut orderbook_state,
+                    matches_enc.nonce,
+                    &matches_enc.ciphertexts,
+                    field_0.field_2,
+                    field_0.field_3,
+                    field_0.field_4,
+                    field_0.field_5,
+                )
             }
             _ => Err(ErrorCode::AbortedComputation.into()),
         }
@@ -313,6 +1168,8 @@ pub mod matching_engine {
                 let success = field_0.field_0;
                 let ledger_enc = &field_0.field_1;
                 let status_enc = &field_0.field_2;
+                let reason_enc = &field_0.field_3;
+                let lock_enc = &field_0.field_4;
                 
                 // // Update user ledger
                 let mut user_ledger = ctx.accounts.user_ledger.load_mut()?;
@@ -323,9 +1180,26 @@ pub mod matching_engine {
                 // // Update order account
                 ctx.accounts.order_account.order_nonce = status_enc.nonce;
                 ctx.accounts.order_account.encrypted_order = status_enc.ciphertexts;
+                ctx.accounts.order_account.lock_nonce = lock_enc.nonce;
+                ctx.accounts.order_account.encrypted_lock = lock_enc.ciphertexts;
+
+                // The group's first leg leaves its check result and lock for the
+                // legs that share it
+                let order_id = ctx.accounts.order_account.order_id;
+                let order_type = ctx.accounts.order_account.order_type;
+                if let Some(order_group) = ctx.accounts.order_group.as_mut() {
+                    if order_group.leg_order_ids[0] == order_id {
+                        order_group.lock_status = if success { GROUP_LOCK_READY } else { GROUP_LOCK_REJECTED };
+                        order_group.lock_order_type = order_type;
+                        order_group.lock_nonce = lock_enc.nonce;
+                        order_group.encrypted_lock = lock_enc.ciphertexts;
+                    }
+                }
 
                 // emit event showing that this order submission was a success and for the given order
                 if success {
+                    // Only a checked order may go on to submit_order and friends
+                    ctx.accounts.order_account.checked = 1;
                     emit!(OrderSubmittedCheckSuccessEvent {
                         order_id: ctx.accounts.order_account.order_id,
                         user: ctx.accounts.order_account.user,
@@ -334,9 +1208,15 @@ pub mod matching_engine {
                         order_nonce: ctx.accounts.order_account.order_nonce,
                         });
                 } else {
+                    // A rejected order never rests, it stops counting right away
+                    ctx.accounts.order_account.open = 0;
+                    ctx.accounts.user_trading_state.record_closed();
+
                     emit!(OrderSubmittedCheckFailedEvent {
                         order_id: ctx.accounts.order_account.order_id,
                         user: ctx.accounts.order_account.user,
+                        reason_code: reason_enc.ciphertexts[0],
+                        reason_nonce: reason_enc.nonce,
                     });
                 }
 
@@ -353,17 +1233,51 @@ pub mod matching_engine {
             ComputationOutputs::Success(ExecuteSettlementOutput { field_0 }) => {
                 let user1_ledger_enc = &field_0.field_0;
                 let user2_ledger_enc = &field_0.field_1;
+                let fee_ledger_enc = &field_0.field_2;
+                let referrer_ledger_enc = &field_0.field_3;
+                let user1_volume_enc = &field_0.field_4;
+                let user2_volume_enc = &field_0.field_5;
+                let lock_enc = &field_0.field_6;
+                let failed = field_0.field_7;
+                let volume_window = field_0.field_8;
+                if failed {
+                    let user = ctx.accounts.user1_ledger.load()?.owner;
+                    return report_arithmetic_error(ErrorCode::SettlementOverflow, user);
+                }
 
                 let mut user1_ledger = ctx.accounts.user1_ledger.load_mut()?;
                 user1_ledger.balance_nonce = user1_ledger_enc.nonce;
                 user1_ledger.encrypted_balances = user1_ledger_enc.ciphertexts;
+                user1_ledger.volume_nonce = user1_volume_enc.nonce;
+                user1_ledger.encrypted_volume = user1_volume_enc.ciphertexts;
+                user1_ledger.volume_window = volume_window;
                 user1_ledger.last_update = Clock::get()?.unix_timestamp;
 
                 let mut user2_ledger = ctx.accounts.user2_ledger.load_mut()?;
                 user2_ledger.balance_nonce = user2_ledger_enc.nonce;
                 user2_ledger.encrypted_balances = user2_ledger_enc.ciphertexts;
+                user2_ledger.volume_nonce = user2_volume_enc.nonce;
+                user2_ledger.encrypted_volume = user2_volume_enc.ciphertexts;
+                user2_ledger.volume_window = volume_window;
                 user2_ledger.last_update = Clock::get()?.unix_timestamp;
 
+                let mut fee_ledger = ctx.accounts.fee_ledger.load_mut()?;
+                fee_ledger.balance_nonce = fee_ledger_enc.nonce;
+                fee_ledger.encrypted_balances = fee_ledger_enc.ciphertexts;
+                fee_ledger.last_update = Clock::get()?.unix_timestamp;
+
+                // Without a referral the fee ledger was passed as the referrer
+                if ctx.accounts.referrer_ledger.key() != ctx.accounts.fee_ledger.key() {
+                    let mut referrer_ledger = ctx.accounts.referrer_ledger.load_mut()?;
+                    referrer_ledger.balance_nonce = referrer_ledger_enc.nonce;
+                    referrer_ledger.encrypted_balances = referrer_ledger_enc.ciphertexts;
+                    referrer_ledger.last_update = Clock::get()?.unix_timestamp;
+                }
+
+                let payer_order = &mut ctx.accounts.payer_order;
+                payer_order.lock_nonce = lock_enc.nonce;
+                payer_order.encrypted_lock = lock_enc.ciphertexts;
+
                 Ok(())
             }
             _ => Err(ErrorCode::AbortedComputation.into()),
@@ -378,7 +1292,17 @@ pub mod matching_engine {
         match &output {
             ComputationOutputs::Success(SubmitOrderOutput { field_0 }) => {
                 let orderbook_enc = &field_0.field_0;
-                let success = field_0.field_1;
+                let ledger_enc = &field_0.field_1;
+                let lock_enc = &field_0.field_2;
+                let success = field_0.field_3;
+                let overflow = field_0.field_4;
+                if overflow {
+                    // Nothing was placed or refunded, the lock stays for release_order_lock
+                    return report_arithmetic_error(
+                        ErrorCode::LockReleaseOverflow,
+                        ctx.accounts.order_account.user,
+                    );
+                }
 
                 // Update orderbook
                 let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
@@ -386,6 +1310,29 @@ pub mod matching_engine {
                 orderbook_state.orderbook_data = orderbook_enc.ciphertexts;
                 orderbook_state.total_orders_processed += 1;
 
+                // A rejected post-only order was refunded inside the circuit
+                let mut user_ledger = ctx.accounts.user_ledger.load_mut()?;
+                user_ledger.balance_nonce = ledger_enc.nonce;
+                user_ledger.encrypted_balances = ledger_enc.ciphertexts;
+                user_ledger.last_update = Clock::get()?.unix_timestamp;
+
+                // What is left locked after that refund
+                ctx.accounts.order_account.lock_nonce = lock_enc.nonce;
+                ctx.accounts.order_account.encrypted_lock = lock_enc.ciphertexts;
+
+                // The group's first leg leaves its check result and lock for the
+                // legs that share it
+                let order_id = ctx.accounts.order_account.order_id;
+                let order_type = ctx.accounts.order_account.order_type;
+                if let Some(order_group) = ctx.accounts.order_group.as_mut() {
+                    if order_group.leg_order_ids[0] == order_id {
+                        order_group.lock_status = if success { GROUP_LOCK_READY } else { GROUP_LOCK_REJECTED };
+                        order_group.lock_order_type = order_type;
+                        order_group.lock_nonce = lock_enc.nonce;
+                        order_group.encrypted_lock = lock_enc.ciphertexts;
+                    }
+                }
+
 
                 Ok(())
             }
@@ -410,6 +1357,23 @@ pub mod matching_engine {
         Ok(())
     }
 
+    pub fn withdraw_protocol_fees(
+        ctx: Context<WithdrawProtocolFees>,
+        authority_enc_pubkey: [u8; 32],
+        amount: u64,
+        is_base_token: bool,
+        computation_offset: u64,
+    ) -> Result<()> {
+        instructions::withdraw_protocol_fees(
+            ctx,
+            authority_enc_pubkey,
+            amount,
+            is_base_token,
+            computation_offset,
+        )?;
+        Ok(())
+    }
+
     pub fn withdraw_from_vault(
         ctx: Context<WithdrawFromVault>,
         amount: u64,
@@ -423,9 +1387,16 @@ pub mod matching_engine {
         ctx: Context<InitializeUserLedger>,
         user_enc_pubkey: [u8; 32],
         user_nonce: u128,
+        owner_nonce: u128,
         computation_offset: u64,
     ) -> Result<()> {
-        instructions::initialize_user_ledger(ctx, user_enc_pubkey, user_nonce, computation_offset)?;
+        instructions::initialize_user_ledger(
+            ctx,
+            user_enc_pubkey,
+            user_nonce,
+            owner_nonce,
+            computation_offset,
+        )?;
         Ok(())
     }
 
@@ -435,9 +1406,18 @@ pub mod matching_engine {
         output: ComputationOutputs<UpdateLedgerDepositOutput>,
     ) -> Result<()> {
         match &output {
-            ComputationOutputs::Success(UpdateLedgerDepositOutput {
-                field_0: balances_enc,
-            }) => {
+            ComputationOutputs::Success(UpdateLedgerDepositOutput { field_0 }) => {
+                let balances_enc = &field_0.field_0;
+                let overflow = field_0.field_1;
+                let amount = field_0.field_2;
+                let fee = field_0.field_3;
+                if overflow {
+                    // Nothing was credited, the tokens and their fee go back
+                    refund_deposit(&ctx, amount, fee)?;
+                    let user = ctx.accounts.user_ledger.load()?.owner;
+                    return report_arithmetic_error(ErrorCode::DepositOverflow, user);
+                }
+
                 let ledger = &mut ctx.accounts.user_ledger.load_mut()?;
 
                 ledger.balance_nonce = balances_enc.nonce;
@@ -471,12 +1451,14 @@ pub mod matching_engine {
         output: ComputationOutputs<InitUserLedgerOutput>,
     ) -> Result<()> {
         match &output {
-            ComputationOutputs::Success(InitUserLedgerOutput {
-                field_0: ledger_enc,
-            }) => {
+            ComputationOutputs::Success(InitUserLedgerOutput { field_0 }) => {
+                let ledger_enc = &field_0.field_0;
+                let owner_enc = &field_0.field_1;
                 let ledger = &mut ctx.accounts.user_ledger.load_mut()?;
                 ledger.balance_nonce = ledger_enc.nonce;
                 ledger.encrypted_balances = ledger_enc.ciphertexts;
+                ledger.owner_nonce = owner_enc.nonce;
+                ledger.encrypted_owner = owner_enc.ciphertexts[0];
                 ledger.last_update = Clock::get()?.unix_timestamp;
 
                 emit!(UserLedgerInitializedEvent {
@@ -498,6 +1480,11 @@ pub mod matching_engine {
             ComputationOutputs::Success(UpdateLedgerWithdrawVerifyOutput { field_0 }) => {
                 let ledger_enc = &field_0.field_0;
                 let success = &field_0.field_1;
+                let underflow = field_0.field_2;
+                if underflow {
+                    let user = ctx.accounts.user_ledger.load()?.owner;
+                    return report_arithmetic_error(ErrorCode::WithdrawUnderflow, user);
+                }
 
                 if *success {
                     let ledger = &mut ctx.accounts.user_ledger.load_mut()?;
@@ -546,8 +1533,173 @@ pub struct MatchOrdersCallback<'info> {
     #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
     /// CHECK: instructions_sysvar, checked by the account constraint
     pub instructions_sysvar: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump,
+    )]
+    pub orderbook_state: AccountLoader<'info, OrderBookState>,
+}
+
+#[callback_accounts("match_orders_uniform")]
+#[derive(Accounts)]
+pub struct MatchOrdersUniformCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_MATCH_ORDERS_UNIFORM))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump,
+    )]
+    pub orderbook_state: AccountLoader<'info, OrderBookState>,
+}
+
+#[callback_accounts("batch_auction")]
+#[derive(Accounts)]
+pub struct BatchAuctionCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_BATCH_AUCTION))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump,
+    )]
+    pub orderbook_state: AccountLoader<'info, OrderBookState>,
+}
+
+#[callback_accounts("init_trigger_book")]
+#[derive(Accounts)]
+pub struct InitTriggerBookCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_INIT_TRIGGER_BOOK))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub trigger_book_state: AccountLoader<'info, TriggerBookState>,
+}
+
+#[callback_accounts("submit_stop_order")]
+#[derive(Accounts)]
+pub struct SubmitStopOrderCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_SUBMIT_STOP_ORDER))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub trigger_book_state: AccountLoader<'info, TriggerBookState>,
+    #[account(mut)]
+    pub user_ledger: AccountLoader<'info, UserPrivateLedger>,
+    #[account(mut)]
+    pub order_account: Box<Account<'info, OrderAccount>>,
+}
+
+#[callback_accounts("evaluate_triggers")]
+#[derive(Accounts)]
+pub struct EvaluateTriggersCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_EVALUATE_TRIGGERS))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub trigger_book_state: AccountLoader<'info, TriggerBookState>,
+    #[account(
+        mut,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump,
+    )]
+    pub orderbook_state: AccountLoader<'info, OrderBookState>,
+}
+
+#[callback_accounts("submit_twap_order")]
+#[derive(Accounts)]
+pub struct SubmitTwapOrderCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_SUBMIT_TWAP_ORDER))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub twap_order_state: AccountLoader<'info, TwapOrderState>,
+    #[account(mut)]
+    pub order_account: Box<Account<'info, OrderAccount>>,
+}
+
+#[callback_accounts("release_twap_slice")]
+#[derive(Accounts)]
+pub struct ReleaseTwapSliceCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_RELEASE_TWAP_SLICE))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub twap_order_state: AccountLoader<'info, TwapOrderState>,
+    #[account(
+        mut,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump,
+    )]
+    pub orderbook_state: AccountLoader<'info, OrderBookState>,
+    #[account(mut)]
+    pub user_ledger: AccountLoader<'info, UserPrivateLedger>,
+    #[account(mut)]
+    pub slice_order_account: Box<Account<'info, OrderAccount>>,
+    #[account(mut)]
+    pub user_trading_state: Box<Account<'info, UserTradingState>>,
+}
+
+#[callback_accounts("create_recurring_order")]
+#[derive(Accounts)]
+pub struct CreateRecurringOrderCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_CREATE_RECURRING_ORDER))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub recurring_order_state: AccountLoader<'info, RecurringOrderState>,
+}
+
+#[callback_accounts("execute_recurring_order")]
+#[derive(Accounts)]
+pub struct ExecuteRecurringOrderCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_EXECUTE_RECURRING_ORDER))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
     #[account(mut)]
+    pub recurring_order_state: AccountLoader<'info, RecurringOrderState>,
+    #[account(
+        mut,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump,
+    )]
     pub orderbook_state: AccountLoader<'info, OrderBookState>,
+    #[account(mut)]
+    pub user_ledger: AccountLoader<'info, UserPrivateLedger>,
+    #[account(mut)]
+    pub child_order_account: Box<Account<'info, OrderAccount>>,
+    #[account(mut)]
+    pub user_trading_state: Box<Account<'info, UserTradingState>>,
 }
 
 #[callback_accounts("submit_order")]
@@ -560,12 +1712,16 @@ pub struct SubmitOrderCallback<'info> {
     /// CHECK: instructions_sysvar, checked by the account constraint
     pub instructions_sysvar: AccountInfo<'info>,
 
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump,
+    )]
     pub orderbook_state: AccountLoader<'info, OrderBookState>,
-    // #[account(mut)]
-    // pub user_ledger: AccountLoader<'info, UserPrivateLedger>,
-    // #[account(mut)]
-    // pub order_account: Box<Account<'info, OrderAccount>>,
+    #[account(mut)]
+    pub user_ledger: AccountLoader<'info, UserPrivateLedger>,
+    #[account(mut)]
+    pub order_account: Box<Account<'info, OrderAccount>>,
 }
 
 
@@ -582,6 +1738,11 @@ pub struct SubmitOrderCheckCallback<'info> {
     pub user_ledger: AccountLoader<'info, UserPrivateLedger>,
     #[account(mut)]
     pub order_account: Box<Account<'info, OrderAccount>>,
+    #[account(mut)]
+    pub user_trading_state: Box<Account<'info, UserTradingState>>,
+    // Only set for a grouped leg, the first leg records its lock here
+    #[account(mut)]
+    pub order_group: Option<Box<Account<'info, OrderGroup>>>,
 }
 
 #[callback_accounts("init_user_ledger")]
@@ -610,6 +1771,27 @@ pub struct ExecuteSettlementCallback<'info> {
     pub user1_ledger: AccountLoader<'info, UserPrivateLedger>,
     #[account(mut)]
     pub user2_ledger: AccountLoader<'info, UserPrivateLedger>,
+    #[account(mut)]
+    pub fee_ledger: AccountLoader<'info, UserPrivateLedger>,
+    #[account(mut)]
+    pub referrer_ledger: AccountLoader<'info, UserPrivateLedger>,
+    #[account(mut)]
+    pub payer_order: Box<Account<'info, OrderAccount>>,
+}
+
+#[callback_accounts("release_order_lock")]
+#[derive(Accounts)]
+pub struct ReleaseOrderLockCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_RELEASE_ORDER_LOCK))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub user_ledger: AccountLoader<'info, UserPrivateLedger>,
+    #[account(mut)]
+    pub order_account: Box<Account<'info, OrderAccount>>,
 }
 
 #[callback_accounts("update_ledger_withdraw_verify")]
@@ -661,7 +1843,11 @@ pub struct InitEncryptedOrderbook<'info> {
     pub system_program: Program<'info, System>,
     pub arcium_program: Program<'info, Arcium>,
 
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump,
+    )]
     pub orderbook_state: AccountLoader<'info, OrderBookState>,
 }
 
@@ -676,7 +1862,11 @@ pub struct InitOrderBookCallback<'info> {
     #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
     /// CHECK: instructions_sysvar, checked by the account constraint
     pub instructions_sysvar: AccountInfo<'info>,
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump,
+    )]
     pub orderbook_state: AccountLoader<'info, OrderBookState>,
 }
 
@@ -694,6 +1884,72 @@ pub struct UpdateLedgerDepositCallback<'info> {
 
     #[account(mut)]
     pub user_ledger: AccountLoader<'info, UserPrivateLedger>,
+
+    #[account(
+        mut,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump,
+    )]
+    pub orderbook_state: AccountLoader<'info, OrderBookState>,
+
+    /// CHECK: PDA authority for vault
+    #[account(
+        seeds = [b"vault_authority"],
+        bump,
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [FEE_VAULT_SEED, vault.mint.as_ref()],
+        bump,
+    )]
+    pub fee_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user_ledger.load()?.owner @ ErrorCode::InvalidTokenAccountOwner,
+        constraint = user_token_account.mint == vault.mint @ ErrorCode::InvalidMint,
+    )]
+    pub user_token_account: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+// Hands a deposit the ledger couldn't take back to the depositor, along with
+// its fee while that is still in the fee vault
+fn refund_deposit(ctx: &Context<UpdateLedgerDepositCallback>, amount: u64, fee: u64) -> Result<()> {
+    let fee_refund = {
+        let mut orderbook_state = ctx.accounts.orderbook_state.load_mut()?;
+        let is_base = ctx.accounts.vault.mint == orderbook_state.base_mint;
+        orderbook_state.unaccrue_fee(is_base, fee)
+    };
+
+    let signer_seeds: &[&[&[u8]]] = &[&[b"vault_authority", &[ctx.bumps.vault_authority]]];
+    for (from, refund) in [
+        (ctx.accounts.vault.to_account_info(), amount),
+        (ctx.accounts.fee_vault.to_account_info(), fee_refund),
+    ] {
+        if refund == 0 {
+            continue;
+        }
+        let cpi_accounts = Transfer {
+            from,
+            to: ctx.accounts.user_token_account.to_account_info(),
+            authority: ctx.accounts.vault_authority.to_account_info(),
+        };
+        let cpi_context = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token::transfer(cpi_context, refund)?;
+    }
+
+    Ok(())
 }
 
 #[event]
@@ -715,6 +1971,7 @@ pub struct OrderSubmittedEvent {
 #[event]
 pub struct MatchesFoundEvent {
     pub num_matches: u8,
+    pub last_trade_price: u64,
     pub match1: [[u8; 32]; 5],
     pub match2: [[u8; 32]; 5],
     pub match3: [[u8; 32]; 5],
@@ -723,6 +1980,88 @@ pub struct MatchesFoundEvent {
     pub timestamp: i64,
 }
 
+// Matching stays halted until the authority calls resume_market
+#[event]
+pub struct MarketHaltedEvent {
+    pub skipped_count: u8,
+    pub timestamp: i64,
+}
+
+// releases of market order remainders cancelled at the end of a batch
+// each release is 3 chunks: order_id, order_type, amount
+#[event]
+pub struct OrderLocksReleasedEvent {
+    pub num_releases: u8,
+    pub releases: [[u8; 32]; 3 * MAX_LOCK_RELEASES],
+    pub nonce: u128,
+    pub timestamp: i64,
+}
+
+// result of a batch auction, AUCTION_RESULT_CHUNKS encrypted for the backend:
+// fills (buyer_order_id, seller_order_id, quantity), num_fills, releases (order_id, order_type, amount), num_releases
+#[event]
+pub struct BatchAuctionClearedEvent {
+    pub clearing_price: u64,
+    pub volume: u64,
+    pub result: [[u8; 32]; AUCTION_RESULT_CHUNKS],
+    pub nonce: u128,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct StopOrderSubmittedEvent {
+    pub order_id: u64,
+    pub success: bool,
+    pub timestamp: i64,
+}
+
+// Only how many stops fired is public, not which ones or at what trigger
+#[event]
+pub struct TriggersEvaluatedEvent {
+    pub triggered_count: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TwapOrderSubmittedEvent {
+    pub order_id: u64,
+    pub user: Pubkey,
+    pub end_time: i64,
+    pub timestamp: i64,
+}
+
+// Slice sizes and the remaining quantity stay encrypted
+#[event]
+pub struct TwapSliceReleasedEvent {
+    pub order_id: u64,
+    pub slice_order_id: u64,
+    pub released: bool,
+    pub completed: bool,
+    pub slices_released: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RecurringOrderCreatedEvent {
+    pub order_id: u64,
+    pub user: Pubkey,
+    pub period: i64,
+    pub total_periods: u64,
+    pub timestamp: i64,
+}
+
+// The quote spent per child stays encrypted
+#[event]
+pub struct RecurringOrderExecutedEvent {
+    pub order_id: u64,
+    pub child_order_id: u64,
+    pub funded: bool,
+    pub placed: bool,
+    pub periods_executed: u64,
+    pub active: bool,
+    pub timestamp: i64,
+}
+
 //each match is a 5 chunks of 32 bytes each
 // pub match_id: u64,
 // pub buyer_order_id: u64,
@@ -753,6 +2092,14 @@ pub struct UserLedgerWithdrawVerifiedSuccessEvent {
     pub last_update: i64,
 }
 
+#[event]
+pub struct OrderLockReleasedEvent {
+    pub user: Pubkey,
+    pub balance_nonce: u128,
+    pub encrypted_balances: [[u8; 32]; 4],
+    pub last_update: i64,
+}
+
 #[event]
 pub struct UserLedgerWithdrawVerifiedFailedEvent {
     pub user: Pubkey,
@@ -768,8 +2115,17 @@ pub struct OrderSubmittedCheckSuccessEvent {
     pub order_nonce: u128,
 }
 
+#[event]
+pub struct ArithmeticErrorEvent {
+    pub user: Pubkey,
+    pub error_code: u32, // ErrorCode of the operation that was turned into a no-op
+    pub timestamp: i64,
+}
+
 #[event]
 pub struct OrderSubmittedCheckFailedEvent {
     pub order_id: u64,
     pub user: Pubkey,
+    pub reason_code: [u8; 32], // REJECT_REASON_*, encrypted for the user
+    pub reason_nonce: u128,
 }
\ No newline at end of file
@@ -21,3 +21,21 @@ pub use user_private_ledger::*;
 
 pub mod match_result;
 pub use match_result::*;
+
+pub mod trigger_book_state;
+pub use trigger_book_state::*;
+
+pub mod order_group;
+pub use order_group::*;
+
+pub mod twap_order_state;
+pub use twap_order_state::*;
+
+pub mod recurring_order_state;
+pub use recurring_order_state::*;
+
+pub mod referral_link;
+pub use referral_link::*;
+
+pub mod user_trading_state;
+pub use user_trading_state::*;
@@ -1,5 +1,12 @@
 use anchor_lang::prelude::*;
 
+use crate::errors::ErrorCode;
+
+// Number of 32 byte ciphertexts making up the encrypted OrderLock
+pub const ORDER_LOCK_CHUNKS: usize = 4;
+// Where encrypted_lock starts: discriminator, order_id, user, user_enc_pubkey, encrypted_order
+pub const ORDER_LOCK_OFFSET: u32 = 8 + 8 + 32 + 32 + 7 * 32;
+
 #[account]
 #[derive(InitSpace)]
 pub struct OrderAccount {
@@ -16,8 +23,36 @@ pub struct OrderAccount {
     // [5] = filled_amount
     // [6] = execution_price
     pub encrypted_order: [[u8; 32]; 7],     // 224
+
+    // Encrypted for the MXE by submit_order_check: the amount, price and quote
+    // budget it checked and the collateral it locked for them
+    pub encrypted_lock: [[u8; 32]; ORDER_LOCK_CHUNKS], // 128
     pub order_nonce: u128,                  // 16
+    pub lock_nonce: u128,                   // 16
     
     pub timestamp: i64,                     // 8
+    pub group_id: u64,                      // 8 (first order id of the group the order is a leg of, 0 = none)
+    pub group_role: u8,                     // 1 (GROUP_ROLE_*)
+    pub order_type: u8,                     // 1 (0 = buy, 1 = sell, as checked)
+    pub order_kind: u8,                     // 1 (ORDER_KIND_*, as checked)
+    pub checked: u8,                        // 1 (1 once submit_order_check accepted the order and locked for it)
+    pub submitted: u8,                      // 1 (1 once submit_order, submit_stop_order or submit_twap_order took the order)
+    pub bond: u64,                          // 8 (lamports escrowed in this account until release_order_bond, 0 = none or released)
+    pub open: u8,                           // 1 (1 while counted in the owner's UserTradingState.open_orders)
     pub bump: u8,                           // 1
-}
\ No newline at end of file
+}
+
+impl OrderAccount {
+    // Claims a checked order for the step that places it, at most once. It
+    // has to go on as the side and kind submit_order_check locked for.
+    pub fn take_for_submission(&mut self, order_type: u8, order_kind: u8) -> Result<()> {
+        require!(self.checked == 1, ErrorCode::OrderNotChecked);
+        require!(self.submitted == 0, ErrorCode::OrderAlreadySubmitted);
+        require!(
+            self.order_type == order_type && self.order_kind == order_kind,
+            ErrorCode::OrderCheckMismatch
+        );
+        self.submitted = 1;
+        Ok(())
+    }
+}
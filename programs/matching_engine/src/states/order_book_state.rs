@@ -1,32 +1,120 @@
 use anchor_lang::prelude::*;
 
+use crate::errors::ErrorCode;
+
+pub const ORDER_BOOK_STATE_SEED: &[u8] = b"order_book_state";
+// Number of 32 byte ciphertexts making up the encrypted OrderBookFlat
+pub const ORDER_BOOK_CHUNKS: usize = 47;
+// Volume discount tiers on top of the base maker and taker rates
+pub const FEE_TIERS: usize = 3;
+
 #[account(zero_copy(unsafe))]
 #[repr(C, packed)]
 #[derive(Debug)]
 #[derive(InitSpace)]
 pub struct OrderBookState {
     pub authority: Pubkey,              // 32
-    pub orderbook_data: [[u8; 32]; 18], // 1344 bytes
+    pub orderbook_data: [[u8; 32]; ORDER_BOOK_CHUNKS], // 1504 bytes
     pub orderbook_nonce: u128,          // 16
     pub backend_pubkey: [u8; 32],       // 32
     pub base_mint: Pubkey,              // 32
     pub quote_mint: Pubkey,             // 32
+    pub base_decimals: u8,              // 1 (amounts are raw base units, quote values divide by 10^base_decimals)
+    pub quote_decimals: u8,             // 1 (prices are raw quote units per whole base token)
+    pub price_feed: Pubkey,             // 32 (reference price for peg orders, default = none)
+    pub pricing_rule: u8,               // 1 (0 = midpoint, 1 = maker, 2 = reference, 3 = uniform)
+    pub market_mode: u8,                // 1 (0 = continuous, 1 = batch auction)
+    pub auction_allocation: u8,         // 1 (0 = price-time, 1 = pro rata, as allocation_mode)
+    pub last_clearing_price: u64,       // 8
+    pub last_clearing_volume: u64,      // 8
+    pub stp_policy: u8,                 // 1 (0 = off, 1 = cancel newest, 2 = cancel oldest, 3 = cancel both, 4 = decrement and cancel)
+    pub allocation_mode: u8,            // 1 (0 = price-time, 1 = pro rata)
+    pub top_priority_pct: u8,           // 1 (pro rata share given to the earliest order first)
+    pub tick_size: u64,                 // 8 (price grid limit orders sit on and the step a sliding post-only order moves by, 0 = 1)
+    pub lot_size: u64,                  // 8 (size grid every order sits on, 0 = 1)
+    pub min_notional: u64,              // 8 (smallest quote value a new order may carry, 0 = any)
+    pub session_enabled: u8,            // 1 (0 = always continuous, 1 = follow the daily schedule below)
+    pub pre_open_time: u32,             // 4 (session boundaries, seconds after UTC midnight)
+    pub opening_auction_time: u32,      // 4
+    pub continuous_time: u32,           // 4
+    pub closing_auction_time: u32,      // 4
+    pub close_time: u32,                // 4
+    pub last_call_auction: i64,         // 8 (when the latest opening or closing auction crossed)
+    pub price_band_bps: u16,            // 2 (widest distance from the band reference a match may trade at, 0 = off)
+    pub price_band_source: u8,          // 1 (0 = last clearing price, 1 = price feed)
+    pub band_halt_threshold: u8,        // 1 (skipped crossings in one batch that halt the market, 0 = never)
+    pub halted: u8,                     // 1 (1 = matching halted until resume_market)
+    pub entry_band_bps: u16,            // 2 (widest distance from the price feed a new limit order may be priced at, 0 = off)
+    pub maker_fee_bps: u16,             // 2 (taken from what the resting side receives in settlement)
+    pub taker_fee_bps: u16,             // 2 (taken from what the incoming side receives in settlement)
+    pub fee_tier_thresholds: [u64; FEE_TIERS], // 24 (30 day quote volume each tier starts at, 0 = unused)
+    pub maker_tier_fee_bps: [u16; FEE_TIERS],  // 6
+    pub taker_tier_fee_bps: [u16; FEE_TIERS],  // 6
+    pub referral_share_bps: u16,        // 2 (share of a referred side's fee paid to its referrer)
+    pub deposit_fee_bps: u16,           // 2 (plaintext fee kept from each deposit)
+    pub withdraw_fee_bps: u16,          // 2 (plaintext fee kept from each withdrawal)
+    pub accrued_base_fees: u64,         // 8 (sitting in the base fee vault until collect_fees)
+    pub accrued_quote_fees: u64,        // 8 (sitting in the quote fee vault until collect_fees)
+    pub order_bond_lamports: u64,       // 8 (anti-spam bond escrowed with every new order, 0 = off)
+    pub max_open_orders: u16,           // 2 (open orders one wallet may have, 0 = no cap)
+    pub max_submissions_per_window: u16, // 2 (orders one wallet may submit per window, 0 = no limit)
+    pub submission_window: u32,         // 4 (rate limit window in seconds)
     pub last_match_timestamp: i64,      // 8
     pub total_orders_processed: u64,    // 8
     pub total_matches: u64,             // 8
     pub bump: u8,                       // 1
 }
-// Total: 1481 bytes
+// Total: 1867 bytes
 
 impl Default for OrderBookState {
     fn default() -> Self {
         Self {
             authority: Pubkey::default(),
-            orderbook_data: [[0u8; 32]; 18],
+            orderbook_data: [[0u8; 32]; ORDER_BOOK_CHUNKS],
             orderbook_nonce: 0,
             backend_pubkey: [0u8; 32],
             base_mint: Pubkey::default(),
             quote_mint: Pubkey::default(),
+            base_decimals: 0,
+            quote_decimals: 0,
+            price_feed: Pubkey::default(),
+            pricing_rule: 0,
+            market_mode: 0,
+            auction_allocation: 0,
+            last_clearing_price: 0,
+            last_clearing_volume: 0,
+            stp_policy: 0,
+            allocation_mode: 0,
+            top_priority_pct: 0,
+            tick_size: 0,
+            lot_size: 0,
+            min_notional: 0,
+            session_enabled: 0,
+            pre_open_time: 0,
+            opening_auction_time: 0,
+            continuous_time: 0,
+            closing_auction_time: 0,
+            close_time: 0,
+            last_call_auction: 0,
+            price_band_bps: 0,
+            price_band_source: 0,
+            band_halt_threshold: 0,
+            halted: 0,
+            entry_band_bps: 0,
+            maker_fee_bps: 0,
+            taker_fee_bps: 0,
+            fee_tier_thresholds: [0; FEE_TIERS],
+            maker_tier_fee_bps: [0; FEE_TIERS],
+            taker_tier_fee_bps: [0; FEE_TIERS],
+            referral_share_bps: 0,
+            deposit_fee_bps: 0,
+            withdraw_fee_bps: 0,
+            accrued_base_fees: 0,
+            accrued_quote_fees: 0,
+            order_bond_lamports: 0,
+            max_open_orders: 0,
+            max_submissions_per_window: 0,
+            submission_window: 0,
             last_match_timestamp: 0,
             total_orders_processed: 0,
             total_matches: 0,
@@ -34,3 +122,34 @@ impl Default for OrderBookState {
         }
     }
 }
+
+impl OrderBookState {
+    // Books a plaintext fee moved into the fee vault of the given side
+    pub fn accrue_fee(&mut self, is_base: bool, fee: u64) -> Result<()> {
+        // Packed fields can't be borrowed, each side is copied out and back
+        if is_base {
+            let accrued = self.accrued_base_fees;
+            self.accrued_base_fees = accrued.checked_add(fee).ok_or(ErrorCode::Overflow)?;
+        } else {
+            let accrued = self.accrued_quote_fees;
+            self.accrued_quote_fees = accrued.checked_add(fee).ok_or(ErrorCode::Overflow)?;
+        }
+        Ok(())
+    }
+
+    // Takes back a fee that is being refunded, at most what is still accrued
+    // since a collect_fees in between already swept the rest
+    pub fn unaccrue_fee(&mut self, is_base: bool, fee: u64) -> u64 {
+        if is_base {
+            let accrued = self.accrued_base_fees;
+            let refund = fee.min(accrued);
+            self.accrued_base_fees = accrued - refund;
+            refund
+        } else {
+            let accrued = self.accrued_quote_fees;
+            let refund = fee.min(accrued);
+            self.accrued_quote_fees = accrued - refund;
+            refund
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use anchor_lang::prelude::*;
+
+use crate::states::ORDER_LOCK_CHUNKS;
+use crate::{GROUP_ROLE_ENTRY, GROUP_ROLE_EXIT, GROUP_ROLE_OCO, GROUP_TYPE_BRACKET};
+
+pub const ORDER_GROUP_SEED: &[u8] = b"order_group";
+
+// Take profit + stop loss, or a bracket entry with its two exits
+pub const MAX_GROUP_LEGS: usize = 3;
+
+// Where the first leg's check stands, later legs may only join once it locked
+pub const GROUP_LOCK_PENDING: u8 = 0;
+pub const GROUP_LOCK_READY: u8 = 1;
+pub const GROUP_LOCK_REJECTED: u8 = 2;
+
+// Where encrypted_lock starts: discriminator, group_id, owner, group_type,
+// leg_order_ids, leg_count, created_at, bump, lock_status, lock_order_type, lock_nonce
+pub const GROUP_LOCK_OFFSET: u32 = 8 + 8 + 32 + 1 + 8 * MAX_GROUP_LEGS as u32 + 1 + 8 + 1 + 1 + 1 + 16;
+
+// Links the OrderAccounts of a one-cancels-other or bracket group. Only the
+// first leg locks collateral, the later legs are backed by the same lock.
+#[account]
+#[derive(InitSpace)]
+pub struct OrderGroup {
+    pub group_id: u64,                          // 8
+    pub owner: Pubkey,                          // 32
+    pub group_type: u8,                         // 1 (0 = one-cancels-other, 1 = bracket)
+    pub leg_order_ids: [u64; MAX_GROUP_LEGS],   // 24
+    pub leg_count: u8,                          // 1
+    pub created_at: i64,                        // 8
+    pub bump: u8,                               // 1
+    pub lock_status: u8,                        // 1 (GROUP_LOCK_*, set by the first leg's submit_order_check)
+    pub lock_order_type: u8,                    // 1 (side the first leg locked for)
+    pub lock_nonce: u128,                       // 16
+    // The first leg's OrderLock as submit_order_check recorded it, later legs
+    // are checked against it in MPC
+    pub encrypted_lock: [[u8; 32]; ORDER_LOCK_CHUNKS], // 128
+}
+
+impl OrderGroup {
+    // Role the circuits give the next leg added to the group. A bracket's
+    // first leg is its entry, the legs after it are the exits.
+    pub fn next_leg_role(&self) -> u8 {
+        if self.group_type == GROUP_TYPE_BRACKET {
+            if self.leg_count == 0 {
+                GROUP_ROLE_ENTRY
+            } else {
+                GROUP_ROLE_EXIT
+            }
+        } else {
+            GROUP_ROLE_OCO
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use anchor_lang::prelude::*;
+
+pub const RECURRING_ORDER_SEED: &[u8] = b"recurring_order";
+
+// Number of 32 byte ciphertexts making up the encrypted RecurringOrder
+pub const RECURRING_ORDER_CHUNKS: usize = 2;
+
+// A recurring (DCA) buy. The quote spent per period stays encrypted for the
+// MXE, the schedule itself is public so anyone can crank it. Every child is
+// a market buy with an order account of its own, the n-th one under order id
+// order_id + n, so the ids after the schedule's are reserved for its children.
+#[account(zero_copy(unsafe))]
+#[repr(C, packed)]
+#[derive(Debug)]
+#[derive(InitSpace)]
+pub struct RecurringOrderState {
+    pub orderbook: Pubkey,                                       // 32
+    pub schedule_data: [[u8; 32]; RECURRING_ORDER_CHUNKS],       // 64 bytes
+    pub schedule_nonce: u128,                                    // 16
+    pub user: Pubkey,                                            // 32
+    pub user_enc_pubkey: [u8; 32],                               // 32 (x25519 key of the user's ledger)
+    pub order_id: u64,                                           // 8
+    pub period: i64,                                             // 8 (seconds between children)
+    pub total_periods: u64,                                      // 8
+    pub periods_executed: u64,                                   // 8
+    pub next_execution_time: i64,                                // 8
+    pub children_created: u64,                                   // 8 (child order accounts opened so far)
+    pub active: u8,                                              // 1 (0 once done or out of balance)
+    pub bump: u8,                                                // 1
+}
+// Total: 226 bytes
+
+impl Default for RecurringOrderState {
+    fn default() -> Self {
+        Self {
+            orderbook: Pubkey::default(),
+            schedule_data: [[0u8; 32]; RECURRING_ORDER_CHUNKS],
+            schedule_nonce: 0,
+            user: Pubkey::default(),
+            user_enc_pubkey: [0u8; 32],
+            order_id: 0,
+            period: 0,
+            total_periods: 0,
+            periods_executed: 0,
+            next_execution_time: 0,
+            children_created: 0,
+            active: 0,
+            bump: 0,
+        }
+    }
+}
+
+impl RecurringOrderState {
+    // Order id the next child is placed under
+    pub fn next_child_order_id(&self) -> Option<u64> {
+        self.order_id.checked_add(self.children_created.checked_add(1)?)
+    }
+}
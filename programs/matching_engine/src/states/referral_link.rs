@@ -0,0 +1,17 @@
+use anchor_lang::prelude::*;
+
+pub const REFERRAL_LINK_SEED: &[u8] = b"referral_link";
+
+// Ties a referee's ledger to the ledger of whoever referred it. Settlement
+// pays the referrer a share of every fee the referee is charged, the amounts
+// only ever exist encrypted in the two ledgers.
+#[account]
+#[derive(InitSpace)]
+pub struct ReferralLink {
+    pub referee: Pubkey,                // 32
+    pub referee_ledger: Pubkey,         // 32
+    pub referrer: Pubkey,               // 32
+    pub referrer_ledger: Pubkey,        // 32
+    pub created_at: i64,                // 8
+    pub bump: u8,                       // 1
+}
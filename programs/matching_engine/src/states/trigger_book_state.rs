@@ -0,0 +1,35 @@
+use anchor_lang::prelude::*;
+
+pub const TRIGGER_BOOK_SEED: &[u8] = b"trigger_book";
+
+// Number of 32 byte ciphertexts making up the encrypted TriggerBookFlat
+pub const TRIGGER_BOOK_CHUNKS: usize = 21;
+
+#[account(zero_copy(unsafe))]
+#[repr(C, packed)]
+#[derive(Debug)]
+#[derive(InitSpace)]
+pub struct TriggerBookState {
+    pub orderbook: Pubkey,                                 // 32
+    pub trigger_data: [[u8; 32]; TRIGGER_BOOK_CHUNKS],     // 672 bytes
+    pub trigger_nonce: u128,                               // 16
+    pub last_evaluation_timestamp: i64,                    // 8
+    pub total_stop_orders: u64,                            // 8
+    pub total_triggered: u64,                              // 8
+    pub bump: u8,                                          // 1
+}
+// Total: 745 bytes
+
+impl Default for TriggerBookState {
+    fn default() -> Self {
+        Self {
+            orderbook: Pubkey::default(),
+            trigger_data: [[0u8; 32]; TRIGGER_BOOK_CHUNKS],
+            trigger_nonce: 0,
+            last_evaluation_timestamp: 0,
+            total_stop_orders: 0,
+            total_triggered: 0,
+            bump: 0,
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use anchor_lang::prelude::*;
+
+pub const TWAP_ORDER_SEED: &[u8] = b"twap_order";
+
+// Number of 32 byte ciphertexts making up the encrypted TwapParent
+pub const TWAP_PARENT_CHUNKS: usize = 4;
+
+// A TWAP parent order. The remaining quantity, slice size, price and the
+// collateral still held for them stay encrypted for the MXE, release_twap_slice
+// feeds one slice of it into the order book per matching interval until it is
+// done or past its end time.
+#[account(zero_copy(unsafe))]
+#[repr(C, packed)]
+#[derive(Debug)]
+#[derive(InitSpace)]
+pub struct TwapOrderState {
+    pub orderbook: Pubkey,                                 // 32
+    pub twap_data: [[u8; 32]; TWAP_PARENT_CHUNKS],         // 128 bytes
+    pub twap_nonce: u128,                                  // 16
+    pub user: Pubkey,                                      // 32
+    pub user_enc_pubkey: [u8; 32],                         // 32 (x25519 key of the user's ledger)
+    pub order_id: u64,                                     // 8
+    pub end_time: i64,                                     // 8
+    pub last_release_timestamp: i64,                       // 8
+    pub slices_released: u64,                              // 8
+    pub slices_created: u64,                               // 8 (slice order accounts opened so far)
+    pub order_type: u8,                                    // 1
+    pub completed: u8,                                     // 1 (1 once fully released or expired)
+    pub bump: u8,                                          // 1
+}
+// Total: 283 bytes
+
+impl Default for TwapOrderState {
+    fn default() -> Self {
+        Self {
+            orderbook: Pubkey::default(),
+            twap_data: [[0u8; 32]; TWAP_PARENT_CHUNKS],
+            twap_nonce: 0,
+            user: Pubkey::default(),
+            user_enc_pubkey: [0u8; 32],
+            order_id: 0,
+            end_time: 0,
+            last_release_timestamp: 0,
+            slices_released: 0,
+            slices_created: 0,
+            order_type: 0,
+            completed: 0,
+            bump: 0,
+        }
+    }
+}
+
+impl TwapOrderState {
+    // Order id the next slice is placed under
+    pub fn next_slice_order_id(&self) -> Option<u64> {
+        self.order_id.checked_add(self.slices_created.checked_add(1)?)
+    }
+}
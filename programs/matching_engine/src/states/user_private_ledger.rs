@@ -1,5 +1,8 @@
 use anchor_lang::prelude::*;
 
+// Where encrypted_owner starts: discriminator, owner, encrypted_balances,
+// balance_nonce, last_update, encrypted_volume, volume_nonce, volume_window
+pub const LEDGER_OWNER_OFFSET: u32 = 8 + 32 + 4 * 32 + 16 + 8 + 2 * 32 + 16 + 8;
 
 #[account(zero_copy(unsafe))]
 #[repr(C, packed)]
@@ -10,5 +13,13 @@ pub struct UserPrivateLedger {
     pub encrypted_balances: [[u8; 32]; 4],
     pub balance_nonce: u128,
     pub last_update: i64,
+    // Encrypted TradingVolume, quote traded in the current and previous window
+    pub encrypted_volume: [[u8; 32]; 2],
+    pub volume_nonce: u128,
+    pub volume_window: u64, // window the volume was last rolled into, see VOLUME_WINDOW
+    // Owner id sealed for the MXE by init_user_ledger, the circuits compare it
+    // for self trade prevention instead of a tag passed in the clear
+    pub encrypted_owner: [u8; 32],
+    pub owner_nonce: u128,
     pub bump: u8,
-}
\ No newline at end of file
+}
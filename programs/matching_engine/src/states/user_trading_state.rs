@@ -0,0 +1,50 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::ErrorCode;
+
+pub const USER_TRADING_STATE_SEED: &[u8] = b"user_trading_state";
+
+// Plaintext order entry counters for one wallet, checked against the market's
+// open order cap and submission rate limit
+#[account]
+#[derive(InitSpace)]
+pub struct UserTradingState {
+    pub user: Pubkey,                   // 32
+    pub open_orders: u16,               // 2 (submitted and not yet filled, cancelled, expired or rejected)
+    pub window_start: i64,              // 8 (start of the current rate limit window)
+    pub window_submissions: u16,        // 2 (orders submitted since window_start)
+    pub bump: u8,                       // 1
+}
+
+impl UserTradingState {
+    // Counts a new order against the caps, 0 turns a cap off
+    pub fn record_submission(
+        &mut self,
+        now: i64,
+        max_open_orders: u16,
+        max_submissions_per_window: u16,
+        submission_window: u32,
+    ) -> Result<()> {
+        if now >= self.window_start.saturating_add(submission_window as i64) {
+            self.window_start = now;
+            self.window_submissions = 0;
+        }
+        require!(
+            max_open_orders == 0 || self.open_orders < max_open_orders,
+            ErrorCode::TooManyOpenOrders
+        );
+        require!(
+            max_submissions_per_window == 0 || self.window_submissions < max_submissions_per_window,
+            ErrorCode::SubmissionRateExceeded
+        );
+
+        self.open_orders = self.open_orders.checked_add(1).ok_or(ErrorCode::Overflow)?;
+        self.window_submissions = self.window_submissions.saturating_add(1);
+        Ok(())
+    }
+
+    // An order stopped counting against the open order cap
+    pub fn record_closed(&mut self) {
+        self.open_orders = self.open_orders.saturating_sub(1);
+    }
+}
@@ -0,0 +1,18 @@
+use crate::errors::ErrorCode;
+use crate::ArithmeticErrorEvent;
+use anchor_lang::prelude::*;
+
+// The ledger circuits don't wrap: on an overflow or underflow they hand back
+// the balances untouched and reveal a flag, which the callback reports here
+// instead of storing anything
+pub fn report_arithmetic_error(error: ErrorCode, user: Pubkey) -> Result<()> {
+    msg!("Ledger arithmetic error: {}", error);
+
+    emit!(ArithmeticErrorEvent {
+        user,
+        error_code: error.into(),
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
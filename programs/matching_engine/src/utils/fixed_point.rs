@@ -0,0 +1,90 @@
+use crate::errors::ErrorCode;
+use anchor_lang::prelude::*;
+
+// Program side of the fixed point the circuits use (quote_owed, quote_due and
+// base_for_quote in encrypted-ixs). Amounts are raw base units like the
+// ledger credits on deposit, prices raw quote units per whole base token, so
+// a quote value is amount * price / 10^base_decimals. Products are taken in
+// u128 and rounding always goes the protocol's way.
+
+// 10^base_decimals, the divisor turning amount * price into quote units
+pub fn price_scale(base_decimals: u8) -> Result<u64> {
+    10u64
+        .checked_pow(base_decimals as u32)
+        .ok_or(ErrorCode::Overflow.into())
+}
+
+// Quote a buyer locks or pays for quantity at price, rounded up
+pub fn quote_owed(quantity: u64, price: u64, price_scale: u64) -> Result<u64> {
+    let product = quantity as u128 * price as u128;
+    let value = product.div_ceil(price_scale as u128);
+    u64::try_from(value).map_err(|_| ErrorCode::Overflow.into())
+}
+
+// Quote handed back to a buyer for quantity at price, rounded down
+pub fn quote_due(quantity: u64, price: u64, price_scale: u64) -> Result<u64> {
+    let value = quantity as u128 * price as u128 / price_scale as u128;
+    u64::try_from(value).map_err(|_| ErrorCode::Overflow.into())
+}
+
+// Moves a value between decimal scales, rounding down when precision is dropped
+pub fn rescale(value: u64, from_decimals: i32, to_decimals: i32) -> Result<u64> {
+    let shift = to_decimals - from_decimals;
+    let scaled = if shift >= 0 {
+        10u64
+            .checked_pow(shift as u32)
+            .and_then(|scale| value.checked_mul(scale))
+    } else {
+        10u64
+            .checked_pow(shift.unsigned_abs())
+            .map(|scale| value / scale)
+    };
+
+    scaled.ok_or(ErrorCode::Overflow.into())
+}
+
+// Plaintext fee in basis points, rounded up like the settlement fee
+pub fn fee_for(amount: u64, fee_bps: u16) -> Result<u64> {
+    let value = (amount as u128 * fee_bps as u128).div_ceil(10_000);
+    u64::try_from(value).map_err(|_| ErrorCode::Overflow.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn owed_rounds_up_and_due_rounds_down() {
+        // 0.05 base at 0.03 is 0.0015 quote, 1 raw unit owed and 0 due
+        assert_eq!(quote_owed(5, 3, 100).unwrap(), 1);
+        assert_eq!(quote_due(5, 3, 100).unwrap(), 0);
+        assert_eq!(quote_owed(200, 150, 100).unwrap(), 300);
+        assert_eq!(quote_due(200, 150, 100).unwrap(), 300);
+    }
+
+    #[test]
+    fn rescale_moves_between_decimals() {
+        // A feed price of 100.00000000 at expo -8 is 100.00 in a 2 decimal book
+        assert_eq!(rescale(10_000_000_000, 8, 2).unwrap(), 10_000);
+        assert_eq!(rescale(10_000_000_999, 8, 2).unwrap(), 10_000);
+        assert_eq!(rescale(12, 2, 6).unwrap(), 120_000);
+        assert!(rescale(u64::MAX, 0, 1).is_err());
+    }
+
+    #[test]
+    fn fee_for_rounds_up() {
+        // 10 bps of 15000, and a dust amount still pays a unit
+        assert_eq!(fee_for(15_000, 10).unwrap(), 15);
+        assert_eq!(fee_for(1, 1).unwrap(), 1);
+        assert_eq!(fee_for(10_001, 1).unwrap(), 2);
+        assert_eq!(fee_for(15_000, 0).unwrap(), 0);
+        assert_eq!(fee_for(999, 10_000).unwrap(), 999);
+    }
+
+    #[test]
+    fn price_scale_is_a_power_of_ten() {
+        assert_eq!(price_scale(2).unwrap(), 100);
+        assert_eq!(price_scale(0).unwrap(), 1);
+        assert!(price_scale(20).is_err());
+    }
+}
@@ -2,4 +2,19 @@ pub mod account_load;
 pub use account_load::*;
 
 pub mod token;
-pub use token::*;
\ No newline at end of file
+pub use token::*;
+
+pub mod price_feed;
+pub use price_feed::*;
+
+pub mod session;
+pub use session::*;
+
+pub mod fixed_point;
+pub use fixed_point::*;
+
+pub mod arithmetic;
+pub use arithmetic::*;
+
+pub mod volume;
+pub use volume::*;
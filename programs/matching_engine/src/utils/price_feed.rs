@@ -0,0 +1,154 @@
+use crate::errors::ErrorCode;
+use crate::utils::rescale;
+use anchor_lang::prelude::*;
+use arrayref::array_ref;
+
+// Pyth (legacy push oracle) price account layout, only the fields we read
+const PYTH_MAGIC: u32 = 0xa1b2c3d4;
+const PYTH_ACCOUNT_TYPE_PRICE: u32 = 3;
+const PYTH_STATUS_TRADING: u32 = 1;
+const PYTH_MAGIC_OFFSET: usize = 0;
+const PYTH_ACCOUNT_TYPE_OFFSET: usize = 8;
+const PYTH_EXPO_OFFSET: usize = 20;
+const PYTH_TIMESTAMP_OFFSET: usize = 96;
+const PYTH_AGG_PRICE_OFFSET: usize = 208;
+const PYTH_AGG_CONF_OFFSET: usize = 216;
+const PYTH_AGG_STATUS_OFFSET: usize = 224;
+const PYTH_MIN_LEN: usize = 240;
+
+// A price published longer ago than this is not used
+pub const MAX_PRICE_AGE_SECS: i64 = 60;
+// Widest confidence interval accepted, in bps of the price
+pub const MAX_PRICE_CONF_BPS: u64 = 200;
+
+pub struct ReferencePrice {
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+}
+
+pub fn parse_pyth_price(data: &[u8], now: i64) -> Result<ReferencePrice> {
+    require!(data.len() >= PYTH_MIN_LEN, ErrorCode::InvalidPriceFeed);
+
+    let magic = u32::from_le_bytes(*array_ref![data, PYTH_MAGIC_OFFSET, 4]);
+    let account_type = u32::from_le_bytes(*array_ref![data, PYTH_ACCOUNT_TYPE_OFFSET, 4]);
+    require!(
+        magic == PYTH_MAGIC && account_type == PYTH_ACCOUNT_TYPE_PRICE,
+        ErrorCode::InvalidPriceFeed
+    );
+
+    let status = u32::from_le_bytes(*array_ref![data, PYTH_AGG_STATUS_OFFSET, 4]);
+    require!(status == PYTH_STATUS_TRADING, ErrorCode::PriceUnavailable);
+
+    let publish_time = i64::from_le_bytes(*array_ref![data, PYTH_TIMESTAMP_OFFSET, 8]);
+    require!(
+        now.saturating_sub(publish_time) <= MAX_PRICE_AGE_SECS,
+        ErrorCode::StalePrice
+    );
+
+    let price = i64::from_le_bytes(*array_ref![data, PYTH_AGG_PRICE_OFFSET, 8]);
+    let conf = u64::from_le_bytes(*array_ref![data, PYTH_AGG_CONF_OFFSET, 8]);
+    require!(price > 0, ErrorCode::PriceUnavailable);
+    // conf / price <= MAX_PRICE_CONF_BPS / 10_000, in u128 so neither side wraps
+    require!(
+        conf as u128 * 10_000 <= price as u128 * MAX_PRICE_CONF_BPS as u128,
+        ErrorCode::PriceTooUncertain
+    );
+
+    Ok(ReferencePrice {
+        price,
+        conf,
+        expo: i32::from_le_bytes(*array_ref![data, PYTH_EXPO_OFFSET, 4]),
+    })
+}
+
+impl ReferencePrice {
+    // Rescales the feed price to the order book's prices, which carry the
+    // quote mint's decimals
+    pub fn to_book_price(&self, quote_decimals: u8) -> Result<u64> {
+        require!(self.price > 0, ErrorCode::PriceUnavailable);
+
+        rescale(self.price as u64, -self.expo, quote_decimals as i32)
+    }
+}
+
+pub fn load_reference_price(price_feed: &AccountInfo, quote_decimals: u8) -> Result<u64> {
+    let data = price_feed.try_borrow_data()?;
+    parse_pyth_price(&data, Clock::get()?.unix_timestamp)?.to_book_price(quote_decimals)
+}
+
+// Reference price for a matching run. Peg orders stay parked (reference 0)
+// while the market has no feed passed in.
+pub fn market_reference_price(
+    price_feed: &Option<UncheckedAccount>,
+    expected_feed: Pubkey,
+    quote_decimals: u8,
+) -> Result<u64> {
+    match price_feed {
+        Some(price_feed) => {
+            require_keys_eq!(price_feed.key(), expected_feed, ErrorCode::InvalidPriceFeed);
+            load_reference_price(price_feed, quote_decimals)
+        }
+        None => Ok(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    // A trading 100.00 price at expo -8, published at NOW
+    fn price_account(price: i64, conf: u64, status: u32, publish_time: i64) -> Vec<u8> {
+        let mut data = vec![0u8; PYTH_MIN_LEN];
+        data[PYTH_MAGIC_OFFSET..PYTH_MAGIC_OFFSET + 4].copy_from_slice(&PYTH_MAGIC.to_le_bytes());
+        data[PYTH_ACCOUNT_TYPE_OFFSET..PYTH_ACCOUNT_TYPE_OFFSET + 4]
+            .copy_from_slice(&PYTH_ACCOUNT_TYPE_PRICE.to_le_bytes());
+        data[PYTH_EXPO_OFFSET..PYTH_EXPO_OFFSET + 4].copy_from_slice(&(-8i32).to_le_bytes());
+        data[PYTH_TIMESTAMP_OFFSET..PYTH_TIMESTAMP_OFFSET + 8].copy_from_slice(&publish_time.to_le_bytes());
+        data[PYTH_AGG_PRICE_OFFSET..PYTH_AGG_PRICE_OFFSET + 8].copy_from_slice(&price.to_le_bytes());
+        data[PYTH_AGG_CONF_OFFSET..PYTH_AGG_CONF_OFFSET + 8].copy_from_slice(&conf.to_le_bytes());
+        data[PYTH_AGG_STATUS_OFFSET..PYTH_AGG_STATUS_OFFSET + 4].copy_from_slice(&status.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn accepts_a_fresh_trading_price() {
+        let data = price_account(10_000_000_000, 5_000_000, PYTH_STATUS_TRADING, NOW - MAX_PRICE_AGE_SECS);
+        let price = parse_pyth_price(&data, NOW).unwrap();
+        assert_eq!(price.price, 10_000_000_000);
+        assert_eq!(price.to_book_price(2).unwrap(), 10_000);
+    }
+
+    #[test]
+    fn rejects_a_stale_price() {
+        let data = price_account(10_000_000_000, 5_000_000, PYTH_STATUS_TRADING, NOW - MAX_PRICE_AGE_SECS - 1);
+        assert_eq!(
+            parse_pyth_price(&data, NOW).err().unwrap(),
+            ErrorCode::StalePrice.into()
+        );
+    }
+
+    #[test]
+    fn rejects_a_price_that_is_not_trading() {
+        let data = price_account(10_000_000_000, 5_000_000, 0, NOW);
+        assert_eq!(
+            parse_pyth_price(&data, NOW).err().unwrap(),
+            ErrorCode::PriceUnavailable.into()
+        );
+    }
+
+    #[test]
+    fn bounds_the_confidence_interval() {
+        let widest = 10_000_000_000 / 10_000 * MAX_PRICE_CONF_BPS;
+        let data = price_account(10_000_000_000, widest, PYTH_STATUS_TRADING, NOW);
+        assert!(parse_pyth_price(&data, NOW).is_ok());
+
+        let data = price_account(10_000_000_000, widest + 1, PYTH_STATUS_TRADING, NOW);
+        assert_eq!(
+            parse_pyth_price(&data, NOW).err().unwrap(),
+            ErrorCode::PriceTooUncertain.into()
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::ErrorCode;
+use crate::states::OrderBookState;
+use crate::{
+    SESSION_CLOSED, SESSION_CLOSING_AUCTION, SESSION_CONTINUOUS, SESSION_OPENING_AUCTION,
+    SESSION_PRE_OPEN,
+};
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+// Phase of the market's daily session schedule at unix time `now`. A market
+// without a schedule trades continuously around the clock.
+pub fn session_phase(orderbook_state: &OrderBookState, now: i64) -> u8 {
+    if orderbook_state.session_enabled == 0 {
+        return SESSION_CONTINUOUS;
+    }
+
+    // Copied out of the packed account before comparing
+    let pre_open_time = orderbook_state.pre_open_time;
+    let opening_auction_time = orderbook_state.opening_auction_time;
+    let continuous_time = orderbook_state.continuous_time;
+    let closing_auction_time = orderbook_state.closing_auction_time;
+    let close_time = orderbook_state.close_time;
+
+    let time_of_day = now.rem_euclid(SECONDS_PER_DAY) as u32;
+    if time_of_day < pre_open_time || time_of_day >= close_time {
+        SESSION_CLOSED
+    } else if time_of_day < opening_auction_time {
+        SESSION_PRE_OPEN
+    } else if time_of_day < continuous_time {
+        SESSION_OPENING_AUCTION
+    } else if time_of_day < closing_auction_time {
+        SESSION_CONTINUOUS
+    } else {
+        SESSION_CLOSING_AUCTION
+    }
+}
+
+// Unix time the auction phase `now` falls in began, each one crosses once
+pub fn call_auction_start(orderbook_state: &OrderBookState, now: i64) -> i64 {
+    let day_start = now - now.rem_euclid(SECONDS_PER_DAY);
+    let offset = if session_phase(orderbook_state, now) == SESSION_OPENING_AUCTION {
+        orderbook_state.opening_auction_time
+    } else {
+        orderbook_state.closing_auction_time
+    };
+    day_start + offset as i64
+}
+
+// Orders are taken in every phase but closed, matching waits for the auctions
+// or continuous trading
+pub fn require_accepting_orders(orderbook_state: &OrderBookState, now: i64) -> Result<()> {
+    require!(
+        session_phase(orderbook_state, now) != SESSION_CLOSED,
+        ErrorCode::MarketClosed
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 19_000 * SECONDS_PER_DAY;
+
+    // 08:00 pre-open, 09:30 opening auction, 09:35 continuous, 15:50 closing
+    // auction, 16:00 close
+    fn scheduled() -> OrderBookState {
+        OrderBookState {
+            session_enabled: 1,
+            pre_open_time: 28_800,
+            opening_auction_time: 34_200,
+            continuous_time: 34_500,
+            closing_auction_time: 57_000,
+            close_time: 57_600,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn walks_through_a_trading_day() {
+        let state = scheduled();
+        assert_eq!(session_phase(&state, DAY + 3_600), SESSION_CLOSED);
+        assert_eq!(session_phase(&state, DAY + 30_000), SESSION_PRE_OPEN);
+        assert_eq!(session_phase(&state, DAY + 34_200), SESSION_OPENING_AUCTION);
+        assert_eq!(session_phase(&state, DAY + 40_000), SESSION_CONTINUOUS);
+        assert_eq!(session_phase(&state, DAY + 57_000), SESSION_CLOSING_AUCTION);
+        assert_eq!(session_phase(&state, DAY + 57_600), SESSION_CLOSED);
+    }
+
+    #[test]
+    fn skips_an_empty_phase() {
+        let state = OrderBookState {
+            continuous_time: 34_200,
+            ..scheduled()
+        };
+        assert_eq!(session_phase(&state, DAY + 34_200), SESSION_CONTINUOUS);
+    }
+
+    #[test]
+    fn trades_continuously_without_a_schedule() {
+        let state = OrderBookState::default();
+        assert_eq!(session_phase(&state, DAY + 3_600), SESSION_CONTINUOUS);
+        assert!(require_accepting_orders(&state, DAY + 3_600).is_ok());
+        assert!(require_accepting_orders(&scheduled(), DAY + 3_600).is_err());
+    }
+
+    #[test]
+    fn auctions_start_at_their_boundary() {
+        let state = scheduled();
+        assert_eq!(call_auction_start(&state, DAY + 34_300), DAY + 34_200);
+        assert_eq!(call_auction_start(&state, DAY + 57_100), DAY + 57_000);
+    }
+}
@@ -0,0 +1,47 @@
+// Trading volume behind the fee tiers is kept per fixed 30 day window. The
+// settlement circuit rolls a ledger's counter forward and estimates the
+// trailing 30 days from the current window plus a share of the previous one.
+pub const VOLUME_WINDOW: i64 = 30 * 86_400;
+
+// Window `now` falls in
+pub fn volume_window(now: i64) -> u64 {
+    now.div_euclid(VOLUME_WINDOW) as u64
+}
+
+// Share of the previous window still inside the trailing 30 days, in bps
+pub fn previous_window_weight_bps(now: i64) -> u64 {
+    let remaining = VOLUME_WINDOW - now.rem_euclid(VOLUME_WINDOW);
+    (remaining as u64 * 10_000) / VOLUME_WINDOW as u64
+}
+
+// How far a ledger last rolled into `last_window` has to roll, capped at 2
+// since the circuit clears both windows from there. A ledger that never
+// traded is at window 0 and starts from cleared counters.
+pub fn windows_elapsed(last_window: u64, current_window: u64) -> u8 {
+    current_window.saturating_sub(last_window).min(2) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fades_the_previous_window_out() {
+        let start = 10 * VOLUME_WINDOW;
+        assert_eq!(volume_window(start), 10);
+        assert_eq!(volume_window(start - 1), 9);
+        assert_eq!(previous_window_weight_bps(start), 10_000);
+        assert_eq!(previous_window_weight_bps(start + VOLUME_WINDOW / 2), 5_000);
+        assert_eq!(previous_window_weight_bps(start + VOLUME_WINDOW - 1), 0);
+    }
+
+    #[test]
+    fn rolls_at_most_two_windows() {
+        assert_eq!(windows_elapsed(10, 10), 0);
+        assert_eq!(windows_elapsed(10, 11), 1);
+        assert_eq!(windows_elapsed(10, 40), 2);
+        assert_eq!(windows_elapsed(0, 19_000), 2);
+        // A window older than the stored one never rolls backwards
+        assert_eq!(windows_elapsed(11, 10), 0);
+    }
+}
@@ -6,9 +6,21 @@ import { getOrCreateAssociatedTokenAccount, mintTo } from "@solana/spl-token";
 
 const ORDERBOOK_SEED = Buffer.from("order_book_state");
 const VAULT_SEED = Buffer.from("vault");
+const FEE_VAULT_SEED = Buffer.from("fee_vault");
 const VAULT_STATE_SEED = Buffer.from("vault_state");
 const ORDER_SEED = Buffer.from("order");
 const USER_LEDGER_SEED = Buffer.from("user_ledger");
+const USER_TRADING_STATE_SEED = Buffer.from("user_trading_state");
+const TRIGGER_BOOK_SEED = Buffer.from("trigger_book");
+const ORDER_GROUP_SEED = Buffer.from("order_group");
+const TWAP_ORDER_SEED = Buffer.from("twap_order");
+const RECURRING_ORDER_SEED = Buffer.from("recurring_order");
+const REFERRAL_LINK_SEED = Buffer.from("referral_link");
+
+// Stand-in Pyth price account loaded by the test validator (see Anchor.toml)
+export const TEST_PRICE_FEED = new PublicKey(
+  "BELSMCiUmHpo8cVMpvotEZo3egekiFYmrBWcs5LJMvRd"
+);
 
 
 
@@ -57,6 +69,85 @@ export function deriveUserLedgerPDA(
   return PublicKey.findProgramAddressSync([USER_LEDGER_SEED, userPubkey.toBuffer()], programId);
 }
 
+/**
+ * Derive UserTradingState PDA
+ */
+export function deriveUserTradingStatePDA(
+  userPubkey: PublicKey,
+  programId: PublicKey
+): [PublicKey, number] {
+  return PublicKey.findProgramAddressSync(
+    [USER_TRADING_STATE_SEED, userPubkey.toBuffer()],
+    programId
+  );
+}
+
+/**
+ * Derive TriggerBookState PDA for an order book
+ */
+export function deriveTriggerBookPDA(
+  orderbook: PublicKey,
+  programId: PublicKey
+): [PublicKey, number] {
+  return PublicKey.findProgramAddressSync(
+    [TRIGGER_BOOK_SEED, orderbook.toBuffer()],
+    programId
+  );
+}
+
+/**
+ * Derives the PDA of a one-cancels-other or bracket order group
+ */
+export function deriveOrderGroupPDA(
+  owner: PublicKey,
+  groupId: anchor.BN,
+  programId: PublicKey
+): [PublicKey, number] {
+  return PublicKey.findProgramAddressSync(
+    [ORDER_GROUP_SEED, owner.toBuffer(), groupId.toArrayLike(Buffer, "le", 8)],
+    programId
+  );
+}
+
+/**
+ * Derive TwapOrderState PDA for a TWAP parent order
+ */
+export function deriveTwapOrderPDA(
+  orderId: anchor.BN,
+  programId: PublicKey
+): [PublicKey, number] {
+  return PublicKey.findProgramAddressSync(
+    [TWAP_ORDER_SEED, orderId.toArrayLike(Buffer, "le", 8)],
+    programId
+  );
+}
+
+/**
+ * Derive RecurringOrderState PDA for a recurring (DCA) buy
+ */
+export function deriveRecurringOrderPDA(
+  orderId: anchor.BN,
+  programId: PublicKey
+): [PublicKey, number] {
+  return PublicKey.findProgramAddressSync(
+    [RECURRING_ORDER_SEED, orderId.toArrayLike(Buffer, "le", 8)],
+    programId
+  );
+}
+
+/**
+ * Derive the ReferralLink PDA of a referred user's ledger
+ */
+export function deriveReferralLinkPDA(
+  refereeLedger: PublicKey,
+  programId: PublicKey
+): [PublicKey, number] {
+  return PublicKey.findProgramAddressSync(
+    [REFERRAL_LINK_SEED, refereeLedger.toBuffer()],
+    programId
+  );
+}
+
 /**
  * Derive Vault (TokenAccount) PDA
  */
@@ -70,6 +161,19 @@ export function deriveVaultPDA(
   );
 }
 
+/**
+ * Derive the fee vault PDA holding a mint's plaintext fees
+ */
+export function deriveFeeVaultPDA(
+  mint: PublicKey,
+  programId: PublicKey
+): [PublicKey, number] {
+  return PublicKey.findProgramAddressSync(
+    [FEE_VAULT_SEED, mint.toBuffer()],
+    programId
+  );
+}
+
 /**
  * Derive VaultState PDA
  */
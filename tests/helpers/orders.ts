@@ -0,0 +1,763 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import {
+  ASSOCIATED_TOKEN_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
+  getAssociatedTokenAddressSync,
+} from "@solana/spl-token";
+import { randomBytes } from "crypto";
+import {
+  awaitComputationFinalization,
+  getCompDefAccOffset,
+  getCompDefAccAddress,
+  getMXEAccAddress,
+  getMempoolAccAddress,
+  getExecutingPoolAccAddress,
+  getComputationAccAddress,
+  deserializeLE,
+  getArciumProgramId,
+  RescueCipher,
+} from "@arcium-hq/client";
+import { MatchingEngine } from "../../target/types/matching_engine";
+import {
+  accountExists,
+  deriveOrderbookPDA,
+  deriveOrderAccountPDA,
+  deriveUserLedgerPDA,
+  deriveUserTradingStatePDA,
+  deriveVaultPDA,
+  deriveFeeVaultPDA,
+  deriveVaultAuthorityPDA,
+  deriveTwapOrderPDA,
+  deriveRecurringOrderPDA,
+  deriveReferralLinkPDA,
+} from "./accounts";
+
+/**
+ * A wallet together with the x25519 key its ledger and orders are encrypted to
+ */
+export interface Trader {
+  keypair: Keypair;
+  encPublicKey: Uint8Array;
+  cipher: RescueCipher;
+}
+
+export interface LedgerBalances {
+  baseTotal: bigint;
+  baseAvailable: bigint;
+  quoteTotal: bigint;
+  quoteAvailable: bigint;
+}
+
+export interface OrderParams {
+  orderId: number;
+  orderType: number; // 0 = buy, 1 = sell
+  orderKind: number; // ORDER_KIND_*
+  amount: number;
+  price: number; // limit price, worst acceptable price for market and peg orders (0 = none)
+  quoteBudget?: number; // only used by market and peg buys
+  minFillQty?: number;
+  displaySize?: number;
+  postOnly?: number;
+}
+
+/**
+ * An order that went through submit_order_check, ready for the step that places it
+ */
+export interface CheckedOrder {
+  params: OrderParams;
+  orderAccount: PublicKey;
+  ciphertext: number[][];
+  nonce: Uint8Array;
+  success: boolean;
+}
+
+export function newComputationOffset(): anchor.BN {
+  return new anchor.BN(randomBytes(8), "hex");
+}
+
+export function newNonce(): anchor.BN {
+  return new anchor.BN(deserializeLE(randomBytes(16)).toString());
+}
+
+/**
+ * The Arcium accounts every queue_computation instruction takes
+ */
+export function queueAccounts(
+  program: Program<MatchingEngine>,
+  clusterAccount: PublicKey,
+  computationOffset: anchor.BN,
+  circuit: string
+) {
+  return {
+    computationAccount: getComputationAccAddress(
+      program.programId,
+      computationOffset
+    ),
+    clusterAccount: clusterAccount,
+    mxeAccount: getMXEAccAddress(program.programId),
+    mempoolAccount: getMempoolAccAddress(program.programId),
+    executingPool: getExecutingPoolAccAddress(program.programId),
+    compDefAccount: getCompDefAccAddress(
+      program.programId,
+      Buffer.from(getCompDefAccOffset(circuit)).readUInt32LE()
+    ),
+    systemProgram: SystemProgram.programId,
+    arciumProgram: getArciumProgramId(),
+  };
+}
+
+export async function finalize(
+  program: Program<MatchingEngine>,
+  computationOffset: anchor.BN
+): Promise<string> {
+  return awaitComputationFinalization(
+    program.provider as anchor.AnchorProvider,
+    computationOffset,
+    program.programId,
+    "confirmed"
+  );
+}
+
+export function decryptBalances(
+  cipher: RescueCipher,
+  encryptedBalances: number[][],
+  balanceNonce: anchor.BN
+): LedgerBalances {
+  const [baseTotal, baseAvailable, quoteTotal, quoteAvailable] = cipher.decrypt(
+    [...encryptedBalances],
+    Uint8Array.from(balanceNonce.toArray("le", 16))
+  );
+  return { baseTotal, baseAvailable, quoteTotal, quoteAvailable };
+}
+
+/**
+ * Decrypt a trader's ledger as it is stored on chain
+ */
+export async function readLedger(
+  program: Program<MatchingEngine>,
+  trader: Trader
+): Promise<LedgerBalances> {
+  const [userLedgerPDA] = deriveUserLedgerPDA(
+    trader.keypair.publicKey,
+    program.programId
+  );
+  const ledger = await program.account.userPrivateLedger.fetch(userLedgerPDA);
+  return decryptBalances(
+    trader.cipher,
+    ledger.encryptedBalances,
+    ledger.balanceNonce
+  );
+}
+
+/**
+ * Create a trader's encrypted ledger unless it already exists
+ */
+export async function initLedger(
+  program: Program<MatchingEngine>,
+  clusterAccount: PublicKey,
+  trader: Trader
+): Promise<void> {
+  const user = trader.keypair.publicKey;
+  const [userLedgerPDA] = deriveUserLedgerPDA(user, program.programId);
+  if (await accountExists(program.provider as anchor.AnchorProvider, userLedgerPDA)) {
+    return;
+  }
+
+  const offset = newComputationOffset();
+  await program.methods
+    .initializeUserLedger(Array.from(trader.encPublicKey), newNonce(), newNonce(), offset)
+    .accountsPartial({
+      ...queueAccounts(program, clusterAccount, offset, "init_user_ledger"),
+      user,
+      userLedger: userLedgerPDA,
+    })
+    .signers([trader.keypair])
+    .rpc({ commitment: "confirmed" });
+
+  await finalize(program, offset);
+}
+
+/**
+ * Move tokens into the vault and credit them, less the deposit fee, to the
+ * base (isBase) or quote side of the trader's ledger
+ */
+export async function depositToLedger(
+  program: Program<MatchingEngine>,
+  clusterAccount: PublicKey,
+  trader: Trader,
+  mint: PublicKey,
+  amount: number,
+  isBase: boolean
+): Promise<void> {
+  const user = trader.keypair.publicKey;
+  const offset = newComputationOffset();
+  await program.methods
+    .depositToLedger(
+      Array.from(trader.encPublicKey),
+      new anchor.BN(amount),
+      isBase,
+      offset
+    )
+    .accounts({
+      ...queueAccounts(program, clusterAccount, offset, "update_ledger_deposit"),
+      user,
+      userLedger: deriveUserLedgerPDA(user, program.programId)[0],
+      mint,
+      vault: deriveVaultPDA(mint, program.programId)[0],
+      userTokenAccount: getAssociatedTokenAddressSync(mint, user),
+      orderbookState: deriveOrderbookPDA(program.programId)[0],
+      feeVault: deriveFeeVaultPDA(mint, program.programId)[0],
+      tokenProgram: TOKEN_PROGRAM_ID,
+      associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+      vaultAuthority: deriveVaultAuthorityPDA(program.programId)[0],
+    })
+    .signers([trader.keypair])
+    .rpc({ commitment: "confirmed" });
+
+  await finalize(program, offset);
+}
+
+/**
+ * Run submit_order_check for an order and wait for its verdict
+ */
+export async function checkOrder(
+  program: Program<MatchingEngine>,
+  clusterAccount: PublicKey,
+  trader: Trader,
+  mint: PublicKey,
+  params: OrderParams,
+  orderGroup: PublicKey | null = null
+): Promise<CheckedOrder> {
+  const user = trader.keypair.publicKey;
+  const [orderAccount] = deriveOrderAccountPDA(
+    new anchor.BN(params.orderId),
+    program.programId
+  );
+  const nonce = randomBytes(16);
+  // amount, price, quote budget, min fill quantity, iceberg display size
+  const ciphertext = trader.cipher.encrypt(
+    [
+      BigInt(params.amount),
+      BigInt(params.price),
+      BigInt(params.quoteBudget ?? 0),
+      BigInt(params.minFillQty ?? 0),
+      BigInt(params.displaySize ?? 0),
+    ],
+    nonce
+  );
+
+  let verdict: boolean | undefined;
+  const successListener = program.addEventListener(
+    "orderSubmittedCheckSuccessEvent",
+    (event) => {
+      if (event.orderId.toNumber() === params.orderId) verdict = true;
+    }
+  );
+  const failedListener = program.addEventListener(
+    "orderSubmittedCheckFailedEvent",
+    (event) => {
+      if (event.orderId.toNumber() === params.orderId) verdict = false;
+    }
+  );
+
+  const offset = newComputationOffset();
+  await program.methods
+    .submitOrderCheck(
+      Array.from(ciphertext[0]),
+      Array.from(ciphertext[1]),
+      Array.from(ciphertext[2]),
+      Array.from(ciphertext[3]),
+      Array.from(ciphertext[4]),
+      Array.from(trader.encPublicKey),
+      params.orderType,
+      params.orderKind,
+      offset,
+      new anchor.BN(params.orderId),
+      new anchor.BN(deserializeLE(nonce).toString()),
+      newNonce() // lock record nonce
+    )
+    .accountsPartial({
+      ...queueAccounts(program, clusterAccount, offset, "submit_order_check"),
+      user,
+      baseMint: mint,
+      vault: deriveVaultPDA(mint, program.programId)[0],
+      orderAccount,
+      userLedger: deriveUserLedgerPDA(user, program.programId)[0],
+      orderbookState: deriveOrderbookPDA(program.programId)[0],
+      userTradingState: deriveUserTradingStatePDA(user, program.programId)[0],
+      priceFeed: null,
+      orderGroup,
+    })
+    .signers([trader.keypair])
+    .rpc({ commitment: "confirmed" });
+
+  await finalize(program, offset);
+  await new Promise((resolve) => setTimeout(resolve, 2000));
+  await program.removeEventListener(successListener);
+  await program.removeEventListener(failedListener);
+
+  return {
+    params,
+    orderAccount,
+    ciphertext,
+    nonce,
+    success: verdict === true,
+  };
+}
+
+/**
+ * Place a checked order in the book with submit_order
+ */
+export async function placeOrder(
+  program: Program<MatchingEngine>,
+  clusterAccount: PublicKey,
+  trader: Trader,
+  mint: PublicKey,
+  order: CheckedOrder
+): Promise<void> {
+  const user = trader.keypair.publicKey;
+  const { params, ciphertext, nonce } = order;
+
+  const offset = newComputationOffset();
+  await program.methods
+    .submitOrder(
+      Array.from(ciphertext[0]),
+      Array.from(ciphertext[1]),
+      Array.from(ciphertext[2]),
+      Array.from(ciphertext[3]),
+      Array.from(ciphertext[4]),
+      Array.from(trader.encPublicKey),
+      params.orderType,
+      params.orderKind,
+      params.postOnly ?? 0,
+      offset,
+      new anchor.BN(params.orderId),
+      new anchor.BN(deserializeLE(nonce).toString())
+    )
+    .accountsPartial({
+      ...queueAccounts(program, clusterAccount, offset, "submit_order"),
+      user,
+      baseMint: mint,
+      vault: deriveVaultPDA(mint, program.programId)[0],
+      orderbookState: deriveOrderbookPDA(program.programId)[0],
+      orderAccount: order.orderAccount,
+      userLedger: deriveUserLedgerPDA(user, program.programId)[0],
+    })
+    .signers([trader.keypair])
+    .rpc({ commitment: "confirmed" });
+
+  await finalize(program, offset);
+}
+
+/**
+ * Turn a checked limit order into a TWAP parent with submit_twap_order. Size
+ * and price come from the order's recorded lock, only the slice size is sent.
+ */
+export async function submitTwap(
+  program: Program<MatchingEngine>,
+  clusterAccount: PublicKey,
+  trader: Trader,
+  order: CheckedOrder,
+  sliceSize: number,
+  endTime: number
+): Promise<PublicKey> {
+  const user = trader.keypair.publicKey;
+  const orderId = new anchor.BN(order.params.orderId);
+  const [twapOrderState] = deriveTwapOrderPDA(orderId, program.programId);
+  const nonce = randomBytes(16);
+  const [sliceCiphertext] = trader.cipher.encrypt([BigInt(sliceSize)], nonce);
+
+  const offset = newComputationOffset();
+  await program.methods
+    .submitTwapOrder(
+      Array.from(sliceCiphertext),
+      Array.from(trader.encPublicKey),
+      order.params.orderType,
+      new anchor.BN(endTime),
+      offset,
+      orderId,
+      new anchor.BN(deserializeLE(nonce).toString()),
+      newNonce() // TWAP parent nonce
+    )
+    .accountsPartial({
+      ...queueAccounts(program, clusterAccount, offset, "submit_twap_order"),
+      user,
+      orderAccount: order.orderAccount,
+      orderbookState: deriveOrderbookPDA(program.programId)[0],
+      twapOrderState,
+    })
+    .signers([trader.keypair])
+    .rpc({ commitment: "confirmed" });
+
+  await finalize(program, offset);
+  return twapOrderState;
+}
+
+/**
+ * Crank release_twap_slice once and report the slice's order id, whether it
+ * went into the book and whether the parent is done
+ */
+export async function releaseTwapSlice(
+  program: Program<MatchingEngine>,
+  clusterAccount: PublicKey,
+  payer: Keypair,
+  trader: Trader,
+  orderId: number
+): Promise<{ sliceOrderId: number; released: boolean; completed: boolean }> {
+  const [twapOrderState] = deriveTwapOrderPDA(new anchor.BN(orderId), program.programId);
+  const parent = await program.account.twapOrderState.fetch(twapOrderState);
+  // The n-th slice trades under the parent's id + n
+  const sliceOrderId = new anchor.BN(orderId).add(parent.slicesCreated).addn(1);
+
+  let outcome = { sliceOrderId: sliceOrderId.toNumber(), released: false, completed: false };
+  const listener = program.addEventListener(
+    "twapSliceReleasedEvent",
+    (event) => {
+      if (event.sliceOrderId.eq(sliceOrderId)) {
+        outcome = { ...outcome, released: event.released, completed: event.completed };
+      }
+    }
+  );
+
+  const offset = newComputationOffset();
+  await program.methods
+    .releaseTwapSlice(offset, sliceOrderId, newNonce())
+    .accountsPartial({
+      ...queueAccounts(program, clusterAccount, offset, "release_twap_slice"),
+      payer: payer.publicKey,
+      orderbookState: deriveOrderbookPDA(program.programId)[0],
+      twapOrderState,
+      userLedger: deriveUserLedgerPDA(trader.keypair.publicKey, program.programId)[0],
+      sliceOrderAccount: deriveOrderAccountPDA(sliceOrderId, program.programId)[0],
+      userTradingState: deriveUserTradingStatePDA(trader.keypair.publicKey, program.programId)[0],
+    })
+    .signers([payer])
+    .rpc({ commitment: "confirmed" });
+
+  await finalize(program, offset);
+  await new Promise((resolve) => setTimeout(resolve, 2000));
+  await program.removeEventListener(listener);
+  return outcome;
+}
+
+/**
+ * Set up a recurring buy of quoteAmount every period with create_recurring_order
+ */
+export async function createRecurringOrder(
+  program: Program<MatchingEngine>,
+  clusterAccount: PublicKey,
+  trader: Trader,
+  orderId: number,
+  quoteAmount: number,
+  maxPrice: number,
+  period: number,
+  totalPeriods: number
+): Promise<PublicKey> {
+  const id = new anchor.BN(orderId);
+  const [recurringOrderState] = deriveRecurringOrderPDA(id, program.programId);
+  const nonce = randomBytes(16);
+  const ciphertext = trader.cipher.encrypt(
+    [BigInt(quoteAmount), BigInt(maxPrice)],
+    nonce
+  );
+
+  const offset = newComputationOffset();
+  await program.methods
+    .createRecurringOrder(
+      Array.from(ciphertext[0]),
+      Array.from(ciphertext[1]),
+      Array.from(trader.encPublicKey),
+      new anchor.BN(period),
+      new anchor.BN(totalPeriods),
+      offset,
+      id,
+      new anchor.BN(deserializeLE(nonce).toString()),
+      newNonce() // schedule nonce
+    )
+    .accountsPartial({
+      ...queueAccounts(program, clusterAccount, offset, "create_recurring_order"),
+      user: trader.keypair.publicKey,
+      orderAccount: deriveOrderAccountPDA(id, program.programId)[0],
+      orderbookState: deriveOrderbookPDA(program.programId)[0],
+      recurringOrderState,
+    })
+    .signers([trader.keypair])
+    .rpc({ commitment: "confirmed" });
+
+  await finalize(program, offset);
+  return recurringOrderState;
+}
+
+/**
+ * Crank execute_recurring_order for the schedule's next child and report the
+ * child's order id and whether it was funded and placed
+ */
+export async function executeRecurringOrder(
+  program: Program<MatchingEngine>,
+  clusterAccount: PublicKey,
+  payer: Keypair,
+  trader: Trader,
+  orderId: number
+): Promise<{ childOrderId: number; funded: boolean; placed: boolean }> {
+  const [recurringOrderState] = deriveRecurringOrderPDA(
+    new anchor.BN(orderId),
+    program.programId
+  );
+  const schedule = await program.account.recurringOrderState.fetch(recurringOrderState);
+  // The n-th child trades under the schedule's id + n
+  const childOrderId = new anchor.BN(orderId).add(schedule.childrenCreated).addn(1);
+
+  let outcome = { childOrderId: childOrderId.toNumber(), funded: false, placed: false };
+  const listener = program.addEventListener(
+    "recurringOrderExecutedEvent",
+    (event) => {
+      if (event.childOrderId.eq(childOrderId)) {
+        outcome = { ...outcome, funded: event.funded, placed: event.placed };
+      }
+    }
+  );
+
+  const offset = newComputationOffset();
+  await program.methods
+    .executeRecurringOrder(offset, childOrderId, newNonce())
+    .accountsPartial({
+      ...queueAccounts(program, clusterAccount, offset, "execute_recurring_order"),
+      payer: payer.publicKey,
+      orderbookState: deriveOrderbookPDA(program.programId)[0],
+      recurringOrderState,
+      userLedger: deriveUserLedgerPDA(trader.keypair.publicKey, program.programId)[0],
+      childOrderAccount: deriveOrderAccountPDA(childOrderId, program.programId)[0],
+      user: trader.keypair.publicKey,
+      userTradingState: deriveUserTradingStatePDA(trader.keypair.publicKey, program.programId)[0],
+    })
+    .signers([payer])
+    .rpc({ commitment: "confirmed" });
+
+  await finalize(program, offset);
+  await new Promise((resolve) => setTimeout(resolve, 2000));
+  await program.removeEventListener(listener);
+  return outcome;
+}
+
+/**
+ * Hand an order's lock back through release_order_lock, as the cranker does
+ * for every release it decrypts out of a match result
+ */
+export async function releaseOrderLock(
+  program: Program<MatchingEngine>,
+  clusterAccount: PublicKey,
+  cranker: Keypair,
+  trader: Trader,
+  orderId: number,
+  amount: bigint,
+  orderType: number
+): Promise<void> {
+  const offset = newComputationOffset();
+  await program.methods
+    .releaseOrderLock(
+      Array.from(trader.encPublicKey),
+      new anchor.BN(orderId),
+      new anchor.BN(amount.toString()),
+      orderType,
+      offset
+    )
+    .accountsPartial({
+      ...queueAccounts(program, clusterAccount, offset, "release_order_lock"),
+      user: cranker.publicKey,
+      orderAccount: deriveOrderAccountPDA(
+        new anchor.BN(orderId),
+        program.programId
+      )[0],
+      userLedger: deriveUserLedgerPDA(
+        trader.keypair.publicKey,
+        program.programId
+      )[0],
+    })
+    .signers([cranker])
+    .rpc({ commitment: "confirmed" });
+
+  await finalize(program, offset);
+}
+
+/**
+ * Free an order's open order slot once it is filled, cancelled or expired
+ */
+export async function closeOpenOrder(
+  program: Program<MatchingEngine>,
+  cranker: Keypair,
+  trader: Trader,
+  orderId: number
+): Promise<void> {
+  await program.methods
+    .closeOpenOrder()
+    .accountsPartial({
+      payer: cranker.publicKey,
+      orderAccount: deriveOrderAccountPDA(
+        new anchor.BN(orderId),
+        program.programId
+      )[0],
+      userTradingState: deriveUserTradingStatePDA(
+        trader.keypair.publicKey,
+        program.programId
+      )[0],
+    })
+    .signers([cranker])
+    .rpc({ commitment: "confirmed" });
+}
+
+/**
+ * Refund a closed order's bond to its owner, or slash it to the market authority
+ */
+export async function releaseOrderBond(
+  program: Program<MatchingEngine>,
+  cranker: Keypair,
+  trader: Trader,
+  treasury: PublicKey,
+  orderId: number,
+  slash: boolean
+): Promise<void> {
+  await program.methods
+    .releaseOrderBond(slash)
+    .accountsPartial({
+      payer: cranker.publicKey,
+      orderAccount: deriveOrderAccountPDA(
+        new anchor.BN(orderId),
+        program.programId
+      )[0],
+      user: trader.keypair.publicKey,
+      orderbookState: deriveOrderbookPDA(program.programId)[0],
+      protocolTreasury: treasury,
+    })
+    .signers([cranker])
+    .rpc({ commitment: "confirmed" });
+}
+
+export interface DecryptedRelease {
+  orderId: bigint;
+  orderType: bigint;
+  amount: bigint;
+}
+
+// Releases sit after the 4 matches (5 fields each) and num_matches in the
+// encrypted MatchResult
+const RELEASES_OFFSET = 4 * 5 + 1;
+
+/**
+ * Decrypt the (order_id, order_type, amount) releases of an
+ * OrderLocksReleasedEvent with the backend's cipher
+ */
+export function decryptReleases(
+  backendCipher: RescueCipher,
+  event: { numReleases: number; releases: number[][]; nonce: anchor.BN }
+): DecryptedRelease[] {
+  // The keystream is positional, so the fields in front of the releases are
+  // padded back in and their plaintext thrown away
+  const padding = Array.from({ length: RELEASES_OFFSET }, () =>
+    new Array(32).fill(0)
+  );
+  const plain = backendCipher
+    .decrypt(
+      [...padding, ...event.releases],
+      Uint8Array.from(event.nonce.toArray("le", 16))
+    )
+    .slice(RELEASES_OFFSET);
+  const releases: DecryptedRelease[] = [];
+  for (let i = 0; i < event.numReleases; i++) {
+    releases.push({
+      orderId: plain[3 * i],
+      orderType: plain[3 * i + 1],
+      amount: plain[3 * i + 2],
+    });
+  }
+  return releases;
+}
+
+export interface DecryptedAuctionFill {
+  buyerOrderId: bigint;
+  sellerOrderId: bigint;
+  quantity: bigint;
+}
+
+// fills (buyer_order_id, seller_order_id, quantity) come first in the
+// encrypted AuctionResult, followed by num_fills
+const MAX_AUCTION_FILLS = 8;
+
+/**
+ * Decrypt the fills of a BatchAuctionClearedEvent with the backend's cipher
+ */
+export function decryptAuctionFills(
+  backendCipher: RescueCipher,
+  event: { result: number[][]; nonce: anchor.BN }
+): DecryptedAuctionFill[] {
+  const plain = backendCipher.decrypt(
+    [...event.result],
+    Uint8Array.from(event.nonce.toArray("le", 16))
+  );
+  const numFills = Number(plain[3 * MAX_AUCTION_FILLS]);
+  const fills: DecryptedAuctionFill[] = [];
+  for (let i = 0; i < numFills; i++) {
+    fills.push({
+      buyerOrderId: plain[3 * i],
+      sellerOrderId: plain[3 * i + 1],
+      quantity: plain[3 * i + 2],
+    });
+  }
+  return fills;
+}
+
+/**
+ * Settle one leg of a fill with execute_settlement: payer's ledger pays
+ * amount of one asset to the receiver's, out of what payerOrder still has
+ * locked. The fee ledger stands in for the referrer, the test traders are
+ * never referred.
+ */
+export async function settleLeg(
+  program: Program<MatchingEngine>,
+  clusterAccount: PublicKey,
+  cranker: Keypair,
+  feeTrader: Trader,
+  payerOrder: PublicKey,
+  receiverOrder: PublicKey,
+  amount: bigint,
+  isBase: boolean,
+  receiverIsMaker: boolean
+): Promise<void> {
+  const payer = await program.account.orderAccount.fetch(payerOrder);
+  const receiver = await program.account.orderAccount.fetch(receiverOrder);
+  const [feeLedger] = deriveUserLedgerPDA(
+    feeTrader.keypair.publicKey,
+    program.programId
+  );
+  const [sellerLedger] = deriveUserLedgerPDA(receiver.user, program.programId);
+
+  const offset = newComputationOffset();
+  await program.methods
+    .executeSettlement(
+      payer.userEncPubkey,
+      receiver.userEncPubkey,
+      Array.from(feeTrader.encPublicKey),
+      Array.from(feeTrader.encPublicKey),
+      new anchor.BN(amount.toString()),
+      // The circuits take is_base 0 as the base asset
+      !isBase,
+      receiverIsMaker,
+      offset
+    )
+    .accountsPartial({
+      ...queueAccounts(program, clusterAccount, offset, "execute_settlement"),
+      user: cranker.publicKey,
+      buyerLedger: deriveUserLedgerPDA(payer.user, program.programId)[0],
+      sellerLedger,
+      orderbookState: deriveOrderbookPDA(program.programId)[0],
+      feeLedger,
+      referralLink: deriveReferralLinkPDA(sellerLedger, program.programId)[0],
+      referrerLedger: feeLedger,
+      payerOrder,
+    })
+    .signers([cranker])
+    .rpc({ commitment: "confirmed" });
+
+  await finalize(program, offset);
+}
@@ -0,0 +1,955 @@
+/**
+ * Plaintext reference for the execution price rules in the match_orders circuit.
+ * Mirrors encrypted-ixs/src/lib.rs so backend and tests can predict match prices.
+ */
+
+// Price scale of the test markets, 10^base decimals with 2 decimal mints
+export const SCALE_FACTOR = 100;
+export const MAX_MATCHES_PER_BATCH = 4;
+export const MAX_ORDERS = 4;
+const MAX_MATCH_ROUNDS = MAX_MATCHES_PER_BATCH + 2 * MAX_ORDERS;
+const MAX_RANK_PRICE = Number.MAX_SAFE_INTEGER;
+
+export const ORDER_KIND_LIMIT = 0;
+export const ORDER_KIND_MARKET = 1;
+export const ORDER_KIND_PEG = 2;
+
+export const ORDER_KIND_STOP = 3;
+export const ORDER_KIND_STOP_LIMIT = 4;
+
+export const PRICING_RULE_MIDPOINT = 0;
+export const PRICING_RULE_MAKER = 1;
+export const PRICING_RULE_REFERENCE = 2;
+export const PRICING_RULE_UNIFORM = 3;
+
+export const STP_NONE = 0;
+export const STP_CANCEL_NEWEST = 1;
+export const STP_CANCEL_OLDEST = 2;
+export const STP_CANCEL_BOTH = 3;
+export const STP_DECREMENT_AND_CANCEL = 4;
+
+export const PRICE_BAND_SOURCE_CLEARING = 0;
+export const PRICE_BAND_SOURCE_ORACLE = 1;
+
+export const ALLOCATION_PRICE_TIME = 0;
+export const ALLOCATION_PRO_RATA = 1;
+
+export const POST_ONLY_NONE = 0;
+export const POST_ONLY_REJECT = 1;
+export const POST_ONLY_SLIDE = 2;
+
+export const GROUP_TYPE_OCO = 0;
+export const GROUP_TYPE_BRACKET = 1;
+
+export const GROUP_ROLE_NONE = 0;
+export const GROUP_ROLE_OCO = 1;
+export const GROUP_ROLE_ENTRY = 2;
+export const GROUP_ROLE_EXIT = 3;
+
+export interface PlainOrder {
+  orderId: number;
+  amount: number;
+  price: number;
+  orderType: number; // 0 = buy, 1 = sell
+  timestamp: number;
+  orderKind: number;
+  quoteBudget: number;
+  minFillQty: number;
+  ownerTag: number;
+  displaySize: number;
+  reserve: number;
+  groupId: number;
+  groupRole: number;
+  postOnly: boolean;
+}
+
+export interface PlainRelease {
+  orderId: number;
+  orderType: number;
+  amount: number;
+}
+
+export interface PlainBatch {
+  matches: PlainMatch[];
+  releases: PlainRelease[];
+  doneGroups: number[];
+  armedGroups: number[];
+  skipped: number; // crossing pairs held back by the price band
+}
+
+export interface PlainMatch {
+  buyerOrderId: number;
+  sellerOrderId: number;
+  quantity: number;
+  executionPrice: number;
+}
+
+export function limitOrder(
+  orderId: number,
+  orderType: number,
+  amount: number,
+  price: number,
+  timestamp: number
+): PlainOrder {
+  return {
+    orderId,
+    amount,
+    price,
+    orderType,
+    timestamp,
+    orderKind: ORDER_KIND_LIMIT,
+    quoteBudget: 0,
+    minFillQty: 0,
+    ownerTag: orderId,
+    displaySize: 0,
+    reserve: 0,
+    groupId: 0,
+    groupRole: GROUP_ROLE_NONE,
+    postOnly: false,
+  };
+}
+
+/**
+ * Limit order showing displaySize at a time, the way submit_order splits it
+ */
+export function icebergOrder(
+  orderId: number,
+  orderType: number,
+  amount: number,
+  price: number,
+  timestamp: number,
+  displaySize: number
+): PlainOrder {
+  return {
+    ...limitOrder(orderId, orderType, Math.min(amount, displaySize), price, timestamp),
+    displaySize,
+    reserve: Math.max(amount - displaySize, 0),
+  };
+}
+
+// Refills a spent iceberg clip from its reserve at the back of the queue
+function replenish(o: PlainOrder, timestamp: number) {
+  if (o.amount === 0 && o.reserve > 0) {
+    o.amount = Math.min(o.reserve, o.displaySize);
+    o.reserve -= o.amount;
+    o.timestamp = timestamp;
+  }
+}
+
+const isMarket = (o: PlainOrder) => o.orderKind === ORDER_KIND_MARKET;
+const isPeg = (o: PlainOrder) => o.orderKind === ORDER_KIND_PEG;
+const usesQuoteBudget = (o: PlainOrder) => isMarket(o) || isPeg(o);
+
+export function buyRankPrice(o: PlainOrder, referencePrice: number): number {
+  if (isMarket(o)) return MAX_RANK_PRICE;
+  if (isPeg(o)) {
+    if (referencePrice === 0) return 0;
+    return o.price > 0 && o.price < referencePrice ? o.price : referencePrice;
+  }
+  return o.price;
+}
+
+export function sellRankPrice(o: PlainOrder, referencePrice: number): number {
+  if (isMarket(o)) return 0;
+  if (isPeg(o)) {
+    if (referencePrice === 0) return MAX_RANK_PRICE;
+    return o.price > referencePrice ? o.price : referencePrice;
+  }
+  return o.price;
+}
+
+/**
+ * Price a crossing pair trades at under the given pricing rule
+ */
+export function pairExecutionPrice(
+  buy: PlainOrder,
+  sell: PlainOrder,
+  referencePrice: number,
+  pricingRule: number,
+  clearingPrice: number
+): number {
+  const buyPrice = buyRankPrice(buy, referencePrice);
+  const sellPrice = sellRankPrice(sell, referencePrice);
+
+  let midpoint: number;
+  if (isMarket(buy)) midpoint = sellPrice;
+  else if (isMarket(sell)) midpoint = buyPrice;
+  else if (isPeg(buy) && !isPeg(sell)) midpoint = buyPrice;
+  else if (isPeg(sell) && !isPeg(buy)) midpoint = sellPrice;
+  else midpoint = Math.floor((buyPrice + sellPrice) / 2);
+
+  switch (pricingRule) {
+    case PRICING_RULE_MAKER:
+      if (isMarket(buy) || isMarket(sell)) return midpoint;
+      if (buy.timestamp < sell.timestamp) return buyPrice;
+      if (sell.timestamp < buy.timestamp) return sellPrice;
+      return midpoint;
+    case PRICING_RULE_REFERENCE:
+      if (referencePrice === 0) return midpoint;
+      return Math.min(Math.max(referencePrice, sellPrice), buyPrice);
+    case PRICING_RULE_UNIFORM:
+      return clearingPrice === 0 ? midpoint : clearingPrice;
+    default:
+      return midpoint;
+  }
+}
+
+function buyPreferred(a: PlainOrder, b: PlainOrder, referencePrice: number) {
+  const pa = buyRankPrice(a, referencePrice);
+  const pb = buyRankPrice(b, referencePrice);
+  return pa !== pb ? pa > pb : a.timestamp < b.timestamp;
+}
+
+function sellPreferred(a: PlainOrder, b: PlainOrder, referencePrice: number) {
+  const pa = sellRankPrice(a, referencePrice);
+  const pb = sellRankPrice(b, referencePrice);
+  return pa !== pb ? pa < pb : a.timestamp < b.timestamp;
+}
+
+/**
+ * Quote a buyer locks or pays for quantity at price, rounded up. Amounts are
+ * raw base units and prices raw quote units per whole base token.
+ */
+export function quoteOwed(
+  quantity: number,
+  price: number,
+  priceScale: number = SCALE_FACTOR
+): number {
+  return Math.ceil((quantity * price) / priceScale);
+}
+
+/**
+ * Quote handed back to a buyer for quantity at price, rounded down
+ */
+export function quoteDue(
+  quantity: number,
+  price: number,
+  priceScale: number = SCALE_FACTOR
+): number {
+  return Math.floor((quantity * price) / priceScale);
+}
+
+/**
+ * Base quantity quote pays for at price, rounded down
+ */
+export function baseForQuote(
+  quote: number,
+  price: number,
+  priceScale: number = SCALE_FACTOR
+): number {
+  return Math.floor((quote * priceScale) / Math.max(price, 1));
+}
+
+export const MAX_FEE_BPS = 1000;
+
+/**
+ * Fee a side pays out of what it receives in settlement, rounded up
+ */
+export function settlementFee(amount: number, feeBps: number): number {
+  return Math.ceil((amount * feeBps) / 10000);
+}
+
+/**
+ * Referrer's cut of a settlement fee, rounded down so the protocol keeps dust
+ */
+export function referralFee(fee: number, referralShareBps: number): number {
+  return Math.floor((fee * referralShareBps) / 10000);
+}
+
+export const VOLUME_WINDOW = 30 * 86400;
+
+export interface TradingVolume {
+  current: number;
+  previous: number;
+}
+
+/**
+ * Volume counter moved into the current window, windowsElapsed >= 2 clears it
+ */
+export function rollVolume(
+  volume: TradingVolume,
+  windowsElapsed: number
+): TradingVolume {
+  if (windowsElapsed === 0) return { ...volume };
+  if (windowsElapsed === 1) return { current: 0, previous: volume.current };
+  return { current: 0, previous: 0 };
+}
+
+/**
+ * Trailing 30 day estimate: the current window plus the share of the
+ * previous one still inside it at unix time now
+ */
+export function rollingVolume(volume: TradingVolume, now: number): number {
+  const remaining = VOLUME_WINDOW - (now % VOLUME_WINDOW);
+  const weightBps = Math.floor((remaining * 10000) / VOLUME_WINDOW);
+  return volume.current + Math.floor((volume.previous * weightBps) / 10000);
+}
+
+/**
+ * Rate of the highest tier the volume reaches, 0 thresholds are unused
+ */
+export function tierFeeBps(
+  volume: number,
+  baseFeeBps: number,
+  thresholds: number[],
+  tierFeesBps: number[]
+): number {
+  let feeBps = baseFeeBps;
+  for (let i = 0; i < thresholds.length; i++) {
+    if (thresholds[i] > 0 && volume >= thresholds[i]) {
+      feeBps = tierFeesBps[i];
+    }
+  }
+  return feeBps;
+}
+
+// Quantity a bid can take at a price, budget orders are capped by their budget
+function buyCapacity(o: PlainOrder, executionPrice: number): number {
+  if (usesQuoteBudget(o) && executionPrice > 0) {
+    return Math.min(
+      o.amount,
+      baseForQuote(o.quoteBudget, executionPrice)
+    );
+  }
+  return o.amount;
+}
+
+// Hands out amount in time priority (slot order on ties), each up to its capacity
+function fillByTime(
+  capacity: number[],
+  eligible: boolean[],
+  timestamps: number[],
+  amount: number
+): number[] {
+  const order = capacity
+    .map((_, i) => i)
+    .filter((i) => eligible[i])
+    .sort((a, b) => timestamps[a] - timestamps[b] || a - b);
+  const fills = capacity.map(() => 0);
+  let left = amount;
+  for (const i of order) {
+    fills[i] = Math.min(left, capacity[i]);
+    left -= fills[i];
+  }
+  return fills;
+}
+
+/**
+ * Splits total across the eligible orders of one price level the way
+ * allocate_level does: the earliest order gets up to topPriorityPct percent
+ * first, the rest is shared by size and the rounding dust goes out by time
+ */
+export function allocateLevel(
+  quantities: number[],
+  eligible: boolean[],
+  timestamps: number[],
+  total: number,
+  topPriorityPct: number
+): number[] {
+  const topAllotment = Math.floor((total * topPriorityPct) / 100);
+  const earliest = fillByTime(
+    quantities.map(() => 1),
+    eligible,
+    timestamps,
+    1
+  );
+  const top = quantities.map((q, i) =>
+    earliest[i] > 0 ? Math.min(q, topAllotment) : 0
+  );
+
+  const rest = total - top.reduce((a, b) => a + b, 0);
+  const room = quantities.map((q, i) => (eligible[i] ? q - top[i] : 0));
+  const roomTotal = room.reduce((a, b) => a + b, 0);
+  const shares = room.map((r, i) =>
+    eligible[i] && roomTotal > 0 ? Number((BigInt(r) * BigInt(rest)) / BigInt(roomTotal)) : 0
+  );
+  const allocated = shares.reduce((a, b) => a + b, 0);
+  const dust = fillByTime(
+    room.map((r, i) => r - shares[i]),
+    eligible,
+    timestamps,
+    rest - allocated
+  );
+
+  return quantities.map((_, i) => top[i] + shares[i] + dust[i]);
+}
+
+// A remainder below the minimum can still fill in one go
+const acceptsFill = (o: PlainOrder, quantity: number) =>
+  quantity >= o.minFillQty || quantity === o.amount;
+
+/**
+ * Quantity and price a bid and an ask would trade at, null if they can't trade
+ */
+export function pairFill(
+  buy: PlainOrder,
+  sell: PlainOrder,
+  referencePrice: number,
+  pricingRule: number,
+  clearingPrice: number
+): { quantity: number; executionPrice: number } | null {
+  const executionPrice = pairExecutionPrice(
+    buy,
+    sell,
+    referencePrice,
+    pricingRule,
+    clearingPrice
+  );
+  const priced =
+    !(isMarket(buy) && isMarket(sell)) &&
+    (!isPeg(buy) || referencePrice > 0) &&
+    (!isPeg(sell) || referencePrice > 0);
+
+  const quantity = Math.min(buyCapacity(buy, executionPrice), sell.amount);
+
+  const buyAccepts =
+    usesQuoteBudget(buy) && buy.price === 0
+      ? true
+      : executionPrice <= buy.price;
+  const compatible =
+    priced &&
+    buyRankPrice(buy, referencePrice) >= sellRankPrice(sell, referencePrice) &&
+    buyAccepts &&
+    executionPrice >= sell.price &&
+    quantity > 0 &&
+    acceptsFill(buy, quantity) &&
+    acceptsFill(sell, quantity) &&
+    // A post-only order only ever trades as the maker
+    !(buy.postOnly && sell.timestamp < buy.timestamp) &&
+    !(sell.postOnly && buy.timestamp < sell.timestamp);
+
+  return compatible ? { quantity, executionPrice } : null;
+}
+
+/**
+ * Whether the circuit breaker lets a match trade at executionPrice, off
+ * without a reference or a band
+ */
+export function withinBand(
+  executionPrice: number,
+  bandReference: number,
+  bandBps: number
+): boolean {
+  if (bandBps === 0 || bandReference === 0) return true;
+  const price = executionPrice * 10000;
+  return (
+    price >= bandReference * (10000 - bandBps) &&
+    price <= bandReference * (10000 + bandBps)
+  );
+}
+
+export const REJECT_REASON_NONE = 0;
+export const REJECT_REASON_INSUFFICIENT_BALANCE = 1;
+export const REJECT_REASON_PRICE_BAND = 2;
+export const REJECT_REASON_TICK_SIZE = 3;
+export const REJECT_REASON_LOT_SIZE = 4;
+export const REJECT_REASON_MIN_NOTIONAL = 5;
+
+export interface EntryRules {
+  bandReference: number;
+  bandBps: number;
+  tickSize: number;
+  lotSize: number;
+  minNotional: number;
+}
+
+export const NO_ENTRY_RULES: EntryRules = {
+  bandReference: 0,
+  bandBps: 0,
+  tickSize: 1,
+  lotSize: 1,
+  minNotional: 0,
+};
+
+/**
+ * Reason submit_order_check turns an order away with. Later checks win:
+ * tick, lot, minimum notional, entry band, then the balance.
+ */
+export function entryRejectReason(
+  orderType: number,
+  orderKind: number,
+  amount: number,
+  price: number,
+  quoteBudget: number,
+  available: number,
+  rules: EntryRules = NO_ENTRY_RULES
+): number {
+  const priced = orderKind === ORDER_KIND_LIMIT || orderKind === ORDER_KIND_STOP_LIMIT;
+  const required =
+    orderType === 0 && !priced
+      ? quoteBudget
+      : orderType === 0
+      ? quoteOwed(amount, price)
+      : amount;
+  // Unpriced sells can't be valued until they trade
+  const notional = priced
+    ? quoteDue(amount, price)
+    : orderType === 0
+    ? quoteBudget
+    : rules.minNotional;
+
+  if (priced && price % rules.tickSize !== 0) return REJECT_REASON_TICK_SIZE;
+  if (amount % rules.lotSize !== 0) return REJECT_REASON_LOT_SIZE;
+  if (notional < rules.minNotional) return REJECT_REASON_MIN_NOTIONAL;
+  if (priced && !withinBand(price, rules.bandReference, rules.bandBps)) {
+    return REJECT_REASON_PRICE_BAND;
+  }
+  if (available < required) return REJECT_REASON_INSUFFICIENT_BALANCE;
+  return REJECT_REASON_NONE;
+}
+
+/**
+ * OrderStatus.status a rejected order is stored with
+ */
+export function rejectStatus(reason: number): number {
+  switch (reason) {
+    case REJECT_REASON_PRICE_BAND:
+      return 6;
+    case REJECT_REASON_TICK_SIZE:
+      return 7;
+    case REJECT_REASON_LOT_SIZE:
+      return 8;
+    case REJECT_REASON_MIN_NOTIONAL:
+      return 9;
+    default:
+      return 5;
+  }
+}
+
+function matchBatch(
+  buyOrders: PlainOrder[],
+  sellOrders: PlainOrder[],
+  referencePrice: number,
+  pricingRule: number,
+  clearingPrice: number,
+  stpPolicy: number,
+  allocationMode: number,
+  topPriorityPct: number,
+  bandReference: number,
+  bandBps: number,
+  timestamp: number
+): PlainBatch {
+  const buys = buyOrders.map((o) => ({ ...o }));
+  const sells = sellOrders.map((o) => ({ ...o }));
+  const released = new Map<PlainOrder, number>();
+  const release = (o: PlainOrder, amount: number) =>
+    released.set(o, (released.get(o) ?? 0) + amount);
+  const matches: PlainMatch[] = [];
+
+  const replenishAll = () =>
+    [...buys, ...sells].forEach((o) => replenish(o, timestamp));
+
+  // A leg's first fill finishes a one-cancels-other group or arms a bracket
+  const doneGroups = new Set<number>();
+  const armedGroups = new Set<number>();
+  const cancelled = (o: PlainOrder) =>
+    o.groupRole === GROUP_ROLE_OCO && doneGroups.has(o.groupId);
+  const recordFill = (o: PlainOrder) => {
+    if (o.groupRole === GROUP_ROLE_OCO) doneGroups.add(o.groupId);
+    if (o.groupRole === GROUP_ROLE_ENTRY) armedGroups.add(o.groupId);
+    o.groupRole = GROUP_ROLE_NONE;
+  };
+
+  // Fills priced outside the band don't trade
+  const bandedFill = (buy: PlainOrder, sell: PlainOrder) => {
+    const fill = pairFill(buy, sell, referencePrice, pricingRule, clearingPrice);
+    return fill && withinBand(fill.executionPrice, bandReference, bandBps)
+      ? fill
+      : null;
+  };
+
+  for (let round = 0; round < MAX_MATCH_ROUNDS; round++) {
+    replenishAll();
+    // Best bid with a compatible ask, paired with the best such ask
+    let best: { buy: PlainOrder; sell: PlainOrder; quantity: number; executionPrice: number } | null = null;
+    for (const buy of buys) {
+      for (const sell of sells) {
+        if (cancelled(buy) || cancelled(sell)) continue;
+        const fill = bandedFill(buy, sell);
+        if (!fill) continue;
+        if (
+          !best ||
+          buyPreferred(buy, best.buy, referencePrice) ||
+          (buy === best.buy && sellPreferred(sell, best.sell, referencePrice))
+        ) {
+          best = { buy, sell, ...fill };
+        }
+      }
+    }
+    if (!best) break;
+
+    const { buy, sell } = best;
+    if (stpPolicy !== STP_NONE && buy.ownerTag === sell.ownerTag) {
+      const buyNewest = buy.timestamp >= sell.timestamp;
+      const cancelBuy =
+        stpPolicy === STP_CANCEL_BOTH ||
+        (stpPolicy === STP_CANCEL_NEWEST && buyNewest) ||
+        (stpPolicy === STP_CANCEL_OLDEST && !buyNewest);
+      const cancelSell =
+        stpPolicy === STP_CANCEL_BOTH ||
+        (stpPolicy === STP_CANCEL_NEWEST && !buyNewest) ||
+        (stpPolicy === STP_CANCEL_OLDEST && buyNewest);
+      const decrement =
+        stpPolicy === STP_DECREMENT_AND_CANCEL
+          ? Math.min(buy.amount, sell.amount)
+          : 0;
+      const buyRemoved = cancelBuy ? buy.amount : decrement;
+      const sellRemoved = cancelSell ? sell.amount : decrement;
+      // A cancelled iceberg takes its reserve with it
+      const buyDropped = buyRemoved + (cancelBuy ? buy.reserve : 0);
+      const sellDropped = sellRemoved + (cancelSell ? sell.reserve : 0);
+
+      if (!usesQuoteBudget(buy)) {
+        release(buy, quoteDue(buyDropped, buy.price));
+      }
+      release(sell, sellDropped);
+      buy.amount -= buyRemoved;
+      sell.amount -= sellRemoved;
+      if (cancelBuy) buy.reserve = 0;
+      if (cancelSell) sell.reserve = 0;
+      continue;
+    }
+
+    if (matches.length === MAX_MATCHES_PER_BATCH) break;
+
+    if (allocationMode === ALLOCATION_PRO_RATA) {
+      // The newer order trades against its whole opposite price level
+      const buyAggressor = buy.timestamp > sell.timestamp;
+      const aggressor = buyAggressor ? buy : sell;
+      const level = buyAggressor ? sells : buys;
+      const levelPrice = buyAggressor
+        ? sellRankPrice(sell, referencePrice)
+        : buyRankPrice(buy, referencePrice);
+
+      const fills = level.map((resting) =>
+        buyAggressor ? bandedFill(aggressor, resting) : bandedFill(resting, aggressor)
+      );
+      const prices = fills.map((f) => (f ? f.executionPrice : 0));
+      const eligible = level.map((resting, k) => {
+        const rankPrice = buyAggressor
+          ? sellRankPrice(resting, referencePrice)
+          : buyRankPrice(resting, referencePrice);
+        const sameOwner =
+          stpPolicy !== STP_NONE && resting.ownerTag === aggressor.ownerTag;
+        return (
+          fills[k] !== null &&
+          rankPrice === levelPrice &&
+          !sameOwner &&
+          !cancelled(resting)
+        );
+      });
+      const quantities = level.map((resting, k) =>
+        buyAggressor ? resting.amount : buyCapacity(resting, prices[k])
+      );
+      const levelTotal = quantities.reduce(
+        (sum, q, k) => (eligible[k] ? sum + q : sum),
+        0
+      );
+      const aggressorCapacity = buyAggressor
+        ? buyCapacity(aggressor, best.executionPrice)
+        : aggressor.amount;
+      const shares = allocateLevel(
+        quantities,
+        eligible,
+        level.map((o) => o.timestamp),
+        Math.min(aggressorCapacity, levelTotal),
+        topPriorityPct
+      );
+
+      level.forEach((resting, k) => {
+        const quantity = shares[k];
+        if (
+          !eligible[k] ||
+          quantity === 0 ||
+          !acceptsFill(resting, quantity) ||
+          !acceptsFill(aggressor, quantity) ||
+          cancelled(resting) ||
+          matches.length === MAX_MATCHES_PER_BATCH
+        ) {
+          return;
+        }
+        const bid = buyAggressor ? aggressor : resting;
+        const ask = buyAggressor ? resting : aggressor;
+        matches.push({
+          buyerOrderId: bid.orderId,
+          sellerOrderId: ask.orderId,
+          quantity,
+          executionPrice: prices[k],
+        });
+        bid.amount -= quantity;
+        ask.amount -= quantity;
+        if (usesQuoteBudget(bid)) {
+          bid.quoteBudget -= quoteOwed(quantity, prices[k]);
+        }
+        recordFill(resting);
+        recordFill(aggressor);
+      });
+      continue;
+    }
+
+    matches.push({
+      buyerOrderId: buy.orderId,
+      sellerOrderId: sell.orderId,
+      quantity: best.quantity,
+      executionPrice: best.executionPrice,
+    });
+
+    buy.amount -= best.quantity;
+    sell.amount -= best.quantity;
+    if (usesQuoteBudget(buy)) {
+      buy.quoteBudget -= quoteOwed(best.quantity, best.executionPrice);
+    }
+    recordFill(buy);
+    recordFill(sell);
+  }
+
+  replenishAll();
+
+  // Crossing pairs the band kept apart
+  let skipped = 0;
+  for (const buy of buys) {
+    for (const sell of sells) {
+      if (buy.amount === 0 || sell.amount === 0) continue;
+      if (cancelled(buy) || cancelled(sell)) continue;
+      const fill = pairFill(buy, sell, referencePrice, pricingRule, clearingPrice);
+      if (fill && !withinBand(fill.executionPrice, bandReference, bandBps)) {
+        skipped++;
+      }
+    }
+  }
+
+  // Spent budget buys and market remainders give up the rest of their lock,
+  // a cancelled sibling's lock stays with the leg that won
+  for (const buy of buys) {
+    if (cancelled(buy)) continue;
+    if ((buy.amount === 0 || isMarket(buy)) && usesQuoteBudget(buy)) {
+      release(buy, buy.quoteBudget);
+    }
+  }
+  for (const sell of sells) {
+    if (isMarket(sell) && !cancelled(sell)) release(sell, sell.amount);
+  }
+
+  const releases: PlainRelease[] = [];
+  for (const o of [...buys, ...sells]) {
+    const amount = released.get(o) ?? 0;
+    if (amount > 0) {
+      releases.push({ orderId: o.orderId, orderType: o.orderType, amount });
+    }
+  }
+
+  return {
+    matches,
+    releases,
+    doneGroups: [...doneGroups],
+    armedGroups: [...armedGroups],
+    skipped,
+  };
+}
+
+/**
+ * Matches a batch the way match_orders does, including the midpoint pre-pass
+ * that finds the uniform clearing price
+ */
+export function referenceMatchBatch(
+  buyOrders: PlainOrder[],
+  sellOrders: PlainOrder[],
+  referencePrice: number,
+  pricingRule: number,
+  stpPolicy: number = STP_NONE,
+  allocationMode: number = ALLOCATION_PRICE_TIME,
+  topPriorityPct: number = 0,
+  timestamp: number = 0,
+  bandReference: number = 0,
+  bandBps: number = 0
+): PlainBatch {
+  const midpointMatches = matchBatch(
+    buyOrders,
+    sellOrders,
+    referencePrice,
+    PRICING_RULE_MIDPOINT,
+    0,
+    stpPolicy,
+    allocationMode,
+    topPriorityPct,
+    bandReference,
+    bandBps,
+    timestamp
+  ).matches;
+  const clearingPrice =
+    midpointMatches.length > 0
+      ? midpointMatches[midpointMatches.length - 1].executionPrice
+      : 0;
+
+  return matchBatch(
+    buyOrders,
+    sellOrders,
+    referencePrice,
+    pricingRule,
+    clearingPrice,
+    stpPolicy,
+    allocationMode,
+    topPriorityPct,
+    bandReference,
+    bandBps,
+    timestamp
+  );
+}
+
+export function referenceMatchOrders(
+  buyOrders: PlainOrder[],
+  sellOrders: PlainOrder[],
+  referencePrice: number,
+  pricingRule: number
+): PlainMatch[] {
+  return referenceMatchBatch(buyOrders, sellOrders, referencePrice, pricingRule)
+    .matches;
+}
+
+/**
+ * Whether evaluate_triggers fires a stop: buys once the reference reaches the
+ * trigger from below, sells once it reaches it from above, never without a price
+ */
+export function stopTriggered(
+  orderType: number,
+  triggerPrice: number,
+  referencePrice: number
+): boolean {
+  if (referencePrice === 0) return false;
+  return orderType === 0
+    ? referencePrice >= triggerPrice
+    : referencePrice <= triggerPrice;
+}
+
+/**
+ * What evaluate_triggers does with a grouped stop: drop it once another leg
+ * has won its group, keep a bracket exit waiting until its entry has filled,
+ * rest an armed exit without a trigger price straight away
+ */
+export function groupStopAction(
+  stop: PlainOrder,
+  triggerPrice: number,
+  referencePrice: number,
+  doneGroups: number[],
+  armedGroups: number[]
+): "drop" | "wait" | "rest" | "trigger" {
+  const exit = stop.groupRole === GROUP_ROLE_EXIT;
+  const exitArmed = exit && armedGroups.indexOf(stop.groupId) >= 0;
+  const sibling = stop.groupRole === GROUP_ROLE_OCO || exitArmed;
+  if (sibling && doneGroups.indexOf(stop.groupId) >= 0) return "drop";
+  if (exit && !exitArmed) return "wait";
+  if (exitArmed && triggerPrice === 0) return "rest";
+  return stopTriggered(stop.orderType, triggerPrice, referencePrice)
+    ? "trigger"
+    : "wait";
+}
+
+/**
+ * Where submit_order places a post-only limit order given the best resting
+ * bid and ask (null when that side is empty): its own price when it doesn't
+ * cross, one tick behind the best opposite price when sliding, null when
+ * it is rejected
+ */
+export function postOnlyPrice(
+  orderType: number,
+  price: number,
+  bestBid: number | null,
+  bestAsk: number | null,
+  postOnly: number,
+  tickSize: number
+): number | null {
+  const crosses =
+    orderType === 0
+      ? bestAsk !== null && price >= bestAsk
+      : bestBid !== null && price <= bestBid;
+  if (postOnly === POST_ONLY_NONE || !crosses) return price;
+  if (postOnly === POST_ONLY_REJECT) return null;
+  if (orderType === 0) return bestAsk > tickSize ? bestAsk - tickSize : null;
+  return bestBid + tickSize;
+}
+
+export interface TwapStep {
+  released: number; // quantity of the child order placed in the book
+  remaining: number;
+  refund: number; // lock handed back to the ledger
+}
+
+/**
+ * One release_twap_slice run: the next slice (the whole remainder when the
+ * slice size is 0 or larger) goes into the book if its side has room, and
+ * once the parent has expired the lock of the unreleased remainder is
+ * refunded instead
+ */
+export function twapStep(
+  orderType: number,
+  remaining: number,
+  price: number,
+  sliceSize: number,
+  expired: boolean,
+  bookHasRoom: boolean = true
+): TwapStep {
+  if (expired) {
+    const refund =
+      orderType === 0 ? quoteDue(remaining, price) : remaining;
+    return { released: 0, remaining: 0, refund };
+  }
+  const slice = sliceSize === 0 || sliceSize > remaining ? remaining : sliceSize;
+  const released = bookHasRoom ? slice : 0;
+  return { released, remaining: remaining - released, refund: 0 };
+}
+
+export interface RecurringChild {
+  funded: boolean;
+  placed: boolean;
+  available: number; // ledger balance funding the children afterwards
+}
+
+/**
+ * One execute_recurring_order run: the period's quote amount is locked the
+ * way submit_order_check locks a market buy, and handed back when the bid
+ * side is full. An unfunded child ends the schedule.
+ */
+export function recurringChild(
+  available: number,
+  quoteAmount: number,
+  bookHasRoom: boolean = true
+): RecurringChild {
+  const funded = quoteAmount > 0 && available >= quoteAmount;
+  const placed = funded && bookHasRoom;
+  return {
+    funded,
+    placed,
+    available: placed ? available - quoteAmount : available,
+  };
+}
+
+export const SESSION_CLOSED = 0;
+export const SESSION_PRE_OPEN = 1;
+export const SESSION_OPENING_AUCTION = 2;
+export const SESSION_CONTINUOUS = 3;
+export const SESSION_CLOSING_AUCTION = 4;
+
+export interface SessionSchedule {
+  preOpenTime: number; // seconds after UTC midnight
+  openingAuctionTime: number;
+  continuousTime: number;
+  closingAuctionTime: number;
+  closeTime: number;
+}
+
+/**
+ * Session phase the program puts a market in at unix time `now`, null
+ * schedule meaning sessions are off
+ */
+export function sessionPhase(
+  schedule: SessionSchedule | null,
+  now: number
+): number {
+  if (schedule === null) return SESSION_CONTINUOUS;
+  const t = ((now % 86400) + 86400) % 86400;
+  if (t < schedule.preOpenTime || t >= schedule.closeTime) return SESSION_CLOSED;
+  if (t < schedule.openingAuctionTime) return SESSION_PRE_OPEN;
+  if (t < schedule.continuousTime) return SESSION_OPENING_AUCTION;
+  if (t < schedule.closingAuctionTime) return SESSION_CONTINUOUS;
+  return SESSION_CLOSING_AUCTION;
+}
@@ -0,0 +1,636 @@
+import { expect } from "chai";
+import {
+  allocateLevel,
+  groupStopAction,
+  icebergOrder,
+  postOnlyPrice,
+  limitOrder,
+  stopTriggered,
+  twapStep,
+  recurringChild,
+  sessionPhase,
+  withinBand,
+  entryRejectReason,
+  rejectStatus,
+  quoteOwed,
+  quoteDue,
+  baseForQuote,
+  settlementFee,
+  referralFee,
+  rollVolume,
+  rollingVolume,
+  tierFeeBps,
+  VOLUME_WINDOW,
+  MAX_FEE_BPS,
+  NO_ENTRY_RULES,
+  ORDER_KIND_LIMIT,
+  ORDER_KIND_MARKET,
+  REJECT_REASON_NONE,
+  REJECT_REASON_INSUFFICIENT_BALANCE,
+  REJECT_REASON_PRICE_BAND,
+  REJECT_REASON_TICK_SIZE,
+  REJECT_REASON_LOT_SIZE,
+  REJECT_REASON_MIN_NOTIONAL,
+  SESSION_CLOSED,
+  SESSION_PRE_OPEN,
+  SESSION_OPENING_AUCTION,
+  SESSION_CONTINUOUS,
+  SESSION_CLOSING_AUCTION,
+  referenceMatchOrders,
+  referenceMatchBatch,
+  pairExecutionPrice,
+  ORDER_KIND_PEG,
+  PRICING_RULE_MIDPOINT,
+  PRICING_RULE_MAKER,
+  PRICING_RULE_REFERENCE,
+  PRICING_RULE_UNIFORM,
+  STP_CANCEL_NEWEST,
+  STP_CANCEL_OLDEST,
+  STP_CANCEL_BOTH,
+  STP_DECREMENT_AND_CANCEL,
+  ALLOCATION_PRICE_TIME,
+  ALLOCATION_PRO_RATA,
+  GROUP_ROLE_OCO,
+  GROUP_ROLE_ENTRY,
+  GROUP_ROLE_EXIT,
+  POST_ONLY_REJECT,
+  POST_ONLY_SLIDE,
+  STP_NONE,
+} from "./helpers/pricing";
+
+describe("Execution price rules", () => {
+  // Two crossing pairs: 105 bid (t=1) vs 95 ask (t=2), 102 bid (t=3) vs 100 ask (t=4)
+  const buys = [limitOrder(1, 0, 1000, 10500, 1), limitOrder(3, 0, 1000, 10200, 3)];
+  const sells = [limitOrder(2, 1, 1000, 9500, 2), limitOrder(4, 1, 1000, 10000, 4)];
+
+  const prices = (rule: number, referencePrice = 0) =>
+    referenceMatchOrders(buys, sells, referencePrice, rule).map(
+      (m) => m.executionPrice
+    );
+
+  it("midpoint trades each pair at the average of its limits", () => {
+    expect(prices(PRICING_RULE_MIDPOINT)).to.deep.equal([10000, 10100]);
+  });
+
+  it("maker trades each pair at the earlier order's limit", () => {
+    expect(prices(PRICING_RULE_MAKER)).to.deep.equal([10500, 10200]);
+
+    const lateBuy = limitOrder(5, 0, 1000, 10500, 9);
+    const earlySell = limitOrder(6, 1, 1000, 9500, 1);
+    expect(
+      pairExecutionPrice(lateBuy, earlySell, 0, PRICING_RULE_MAKER, 0)
+    ).to.equal(9500);
+  });
+
+  it("reference trades at the reference price clamped into each spread", () => {
+    expect(prices(PRICING_RULE_REFERENCE, 10050)).to.deep.equal([10050, 10050]);
+    expect(prices(PRICING_RULE_REFERENCE, 11000)).to.deep.equal([10500, 10200]);
+    // No reference known falls back to the midpoint
+    expect(prices(PRICING_RULE_REFERENCE, 0)).to.deep.equal([10000, 10100]);
+  });
+
+  it("uniform trades every pair at the marginal pair's midpoint", () => {
+    expect(prices(PRICING_RULE_UNIFORM)).to.deep.equal([10100, 10100]);
+  });
+
+  it("peg orders are priced from the reference under the midpoint rule", () => {
+    const pegBuy = {
+      ...limitOrder(7, 0, 1000, 0, 1),
+      orderKind: ORDER_KIND_PEG,
+      quoteBudget: 200000,
+    };
+    const matches = referenceMatchOrders(
+      [pegBuy],
+      [limitOrder(8, 1, 1000, 9500, 2)],
+      10000,
+      PRICING_RULE_MIDPOINT
+    );
+    expect(matches).to.have.length(1);
+    expect(matches[0].executionPrice).to.equal(10000);
+    expect(matches[0].quantity).to.equal(1000);
+  });
+});
+
+describe("Minimum fill quantity", () => {
+  it("skips a counterparty below the block's minimum and keeps looking", () => {
+    // 500 lot block bid, the best ask only has 100
+    const block = { ...limitOrder(1, 0, 500, 10500, 1), minFillQty: 300 };
+    const smallAsk = limitOrder(2, 1, 100, 9500, 2);
+    const bigAsk = limitOrder(3, 1, 400, 10000, 3);
+
+    const matches = referenceMatchOrders(
+      [block],
+      [smallAsk, bigAsk],
+      0,
+      PRICING_RULE_MIDPOINT
+    );
+    expect(matches[0].sellerOrderId).to.equal(3);
+    expect(matches[0].quantity).to.equal(400);
+    // The 100 lot remainder is below the minimum, so it may take the small ask
+    expect(matches[1].sellerOrderId).to.equal(2);
+    expect(matches[1].quantity).to.equal(100);
+  });
+
+  it("lets the skipped order trade with another compatible bid", () => {
+    const block = { ...limitOrder(1, 0, 500, 10500, 1), minFillQty: 300 };
+    const smallBid = limitOrder(4, 0, 100, 9800, 4);
+    const smallAsk = limitOrder(2, 1, 100, 9500, 2);
+
+    const matches = referenceMatchOrders(
+      [block, smallBid],
+      [smallAsk],
+      0,
+      PRICING_RULE_MIDPOINT
+    );
+    expect(matches).to.have.length(1);
+    expect(matches[0].buyerOrderId).to.equal(4);
+  });
+
+  it("fills a remainder smaller than the minimum", () => {
+    const block = { ...limitOrder(1, 0, 200, 10500, 1), minFillQty: 300 };
+    const ask = limitOrder(2, 1, 200, 9500, 2);
+    expect(
+      referenceMatchOrders([block], [ask], 0, PRICING_RULE_MIDPOINT)
+    ).to.have.length(1);
+  });
+});
+
+describe("Self trade prevention", () => {
+  // Owner 7 rests a 100 lot ask at t=1, then sends a crossing 60 lot bid at t=5.
+  // Another owner's 100 lot ask sits behind it.
+  const ownBid = { ...limitOrder(1, 0, 60, 10500, 5), ownerTag: 7 };
+  const ownAsk = { ...limitOrder(2, 1, 100, 9500, 1), ownerTag: 7 };
+  const otherAsk = limitOrder(3, 1, 100, 10000, 2);
+
+  const run = (stpPolicy: number) =>
+    referenceMatchBatch([ownBid], [ownAsk, otherAsk], 0, PRICING_RULE_MIDPOINT, stpPolicy);
+
+  it("trades with itself when the market has no policy", () => {
+    const { matches } = run(0);
+    expect(matches[0].sellerOrderId).to.equal(2);
+  });
+
+  it("cancel newest drops the incoming bid", () => {
+    const { matches, releases } = run(STP_CANCEL_NEWEST);
+    expect(matches).to.have.length(0);
+    expect(releases).to.deep.equal([{ orderId: 1, orderType: 0, amount: 6300 }]);
+  });
+
+  it("cancel oldest drops the resting ask and keeps matching", () => {
+    const { matches, releases } = run(STP_CANCEL_OLDEST);
+    expect(matches).to.have.length(1);
+    expect(matches[0].sellerOrderId).to.equal(3);
+    expect(releases).to.deep.equal([{ orderId: 2, orderType: 1, amount: 100 }]);
+  });
+
+  it("cancel both drops both orders", () => {
+    const { matches, releases } = run(STP_CANCEL_BOTH);
+    expect(matches).to.have.length(0);
+    expect(releases.map((r) => r.orderId)).to.have.members([1, 2]);
+  });
+
+  it("decrement and cancel shrinks both by the smaller size", () => {
+    const { matches, releases } = run(STP_DECREMENT_AND_CANCEL);
+    expect(matches).to.have.length(0);
+    expect(releases).to.have.deep.members([
+      { orderId: 1, orderType: 0, amount: 6300 },
+      { orderId: 2, orderType: 1, amount: 60 },
+    ]);
+  });
+});
+
+describe("Pro rata allocation", () => {
+  // A 10 lot bid rests ahead of a 990 lot bid at the same price, then a 500 lot ask arrives
+  const smallBid = limitOrder(1, 0, 10, 10000, 1);
+  const largeBid = limitOrder(2, 0, 990, 10000, 2);
+  const ask = limitOrder(3, 1, 500, 10000, 3);
+
+  const fills = (allocationMode: number, topPriorityPct = 0) =>
+    referenceMatchBatch(
+      [smallBid, largeBid],
+      [ask],
+      0,
+      PRICING_RULE_MIDPOINT,
+      0,
+      allocationMode,
+      topPriorityPct
+    ).matches.map((m) => [m.buyerOrderId, m.quantity]);
+
+  it("price-time fills the earlier order first", () => {
+    expect(fills(0)).to.deep.equal([
+      [1, 10],
+      [2, 490],
+    ]);
+  });
+
+  it("pro rata splits the level by size", () => {
+    expect(fills(ALLOCATION_PRO_RATA)).to.deep.equal([
+      [1, 5],
+      [2, 495],
+    ]);
+  });
+
+  it("top of book allotment goes to the earliest order first", () => {
+    expect(fills(ALLOCATION_PRO_RATA, 20)).to.deep.equal([
+      [1, 10],
+      [2, 490],
+    ]);
+  });
+
+  it("rounding dust goes out in time priority", () => {
+    const even = [100, 100, 100, 0];
+    const eligible = [true, true, true, false];
+    expect(allocateLevel(even, eligible, [3, 1, 2, 0], 100, 0)).to.deep.equal([
+      33, 34, 33, 0,
+    ]);
+  });
+
+  it("the earliest order keeps its pro rata share of the rest", () => {
+    expect(
+      allocateLevel([100, 300, 0, 0], [true, true, false, false], [1, 2, 0, 0], 200, 20)
+    ).to.deep.equal([67, 133, 0, 0]);
+  });
+});
+
+describe("Iceberg orders", () => {
+  // A 300 lot iceberg ask showing 100 rests ahead of a plain 200 lot ask at the same price
+  const iceberg = icebergOrder(1, 1, 300, 10000, 1, 100);
+  const plainAsk = limitOrder(2, 1, 200, 10000, 5);
+  const bid = limitOrder(3, 0, 250, 10000, 6);
+
+  it("fills at most one clip per match and refills at the back of the queue", () => {
+    const { matches } = referenceMatchBatch(
+      [bid],
+      [iceberg, plainAsk],
+      0,
+      PRICING_RULE_MIDPOINT,
+      STP_NONE,
+      ALLOCATION_PRICE_TIME,
+      0,
+      10
+    );
+    expect(matches.map((m) => [m.sellerOrderId, m.quantity])).to.deep.equal([
+      [1, 100],
+      [2, 150],
+    ]);
+  });
+
+  it("a cancelled iceberg releases its hidden reserve too", () => {
+    const ownBid = { ...limitOrder(4, 0, 50, 10000, 6), ownerTag: 7 };
+    const { releases } = referenceMatchBatch(
+      [ownBid],
+      [{ ...iceberg, ownerTag: 7 }],
+      0,
+      PRICING_RULE_MIDPOINT,
+      STP_CANCEL_OLDEST,
+      ALLOCATION_PRICE_TIME,
+      0,
+      10
+    );
+    expect(releases).to.deep.equal([{ orderId: 1, orderType: 1, amount: 300 }]);
+  });
+});
+
+describe("Stop triggers", () => {
+  it("a buy stop fires once the reference reaches its trigger", () => {
+    expect(stopTriggered(0, 10500, 10499)).to.equal(false);
+    expect(stopTriggered(0, 10500, 10500)).to.equal(true);
+    expect(stopTriggered(0, 10500, 11000)).to.equal(true);
+  });
+
+  it("a sell stop fires once the reference falls to its trigger", () => {
+    expect(stopTriggered(1, 9500, 9501)).to.equal(false);
+    expect(stopTriggered(1, 9500, 9500)).to.equal(true);
+  });
+
+  it("nothing fires without a reference price", () => {
+    expect(stopTriggered(1, 9500, 0)).to.equal(false);
+  });
+});
+
+describe("Order groups", () => {
+  const leg = (
+    orderId: number,
+    orderType: number,
+    amount: number,
+    price: number,
+    timestamp: number,
+    groupId: number,
+    groupRole: number
+  ) => ({ ...limitOrder(orderId, orderType, amount, price, timestamp), groupId, groupRole });
+
+  it("a one-cancels-other fill cancels the sibling leg in the same batch", () => {
+    const takeProfit = leg(1, 1, 500, 11000, 1, 7, GROUP_ROLE_OCO);
+    const otherLeg = leg(2, 1, 500, 12000, 2, 7, GROUP_ROLE_OCO);
+    const bid = limitOrder(3, 0, 1000, 12500, 3);
+
+    const { matches, doneGroups } = referenceMatchBatch(
+      [bid],
+      [takeProfit, otherLeg],
+      0,
+      PRICING_RULE_MIDPOINT
+    );
+    expect(matches.map((m) => [m.sellerOrderId, m.quantity])).to.deep.equal([[1, 500]]);
+    expect(doneGroups).to.deep.equal([7]);
+  });
+
+  it("a bracket entry fill arms its exits", () => {
+    const entry = leg(1, 0, 500, 10000, 1, 9, GROUP_ROLE_ENTRY);
+    const { matches, armedGroups, doneGroups } = referenceMatchBatch(
+      [entry],
+      [limitOrder(2, 1, 500, 10000, 2)],
+      0,
+      PRICING_RULE_MIDPOINT
+    );
+    expect(matches).to.have.length(1);
+    expect(armedGroups).to.deep.equal([9]);
+    expect(doneGroups).to.deep.equal([]);
+  });
+
+  it("bracket exits wait for the entry, then stop or rest, and drop once the group is done", () => {
+    const stopLoss = leg(3, 1, 500, 0, 0, 9, GROUP_ROLE_EXIT);
+    const takeProfit = leg(4, 1, 500, 11000, 0, 9, GROUP_ROLE_EXIT);
+
+    expect(groupStopAction(stopLoss, 9000, 8900, [], [])).to.equal("wait");
+    expect(groupStopAction(stopLoss, 9000, 8900, [], [9])).to.equal("trigger");
+    expect(groupStopAction(takeProfit, 0, 10000, [], [9])).to.equal("rest");
+    expect(groupStopAction(stopLoss, 9000, 8900, [9], [9])).to.equal("drop");
+  });
+});
+
+describe("Post-only orders", () => {
+  it("a crossing post-only order is rejected or slid one tick behind the touch", () => {
+    // Best bid 99.00, best ask 101.00, tick 0.01
+    expect(postOnlyPrice(0, 10000, 9900, 10100, POST_ONLY_REJECT, 1)).to.equal(10000);
+    expect(postOnlyPrice(0, 10200, 9900, 10100, POST_ONLY_REJECT, 1)).to.equal(null);
+    expect(postOnlyPrice(0, 10200, 9900, 10100, POST_ONLY_SLIDE, 1)).to.equal(10099);
+    expect(postOnlyPrice(1, 9800, 9900, 10100, POST_ONLY_SLIDE, 5)).to.equal(9905);
+    expect(postOnlyPrice(1, 9800, null, 10100, POST_ONLY_REJECT, 1)).to.equal(9800);
+  });
+
+  it("a resting post-only order never trades against an older order", () => {
+    const olderAsk = limitOrder(1, 1, 100, 10000, 1);
+    const postOnlyBid = { ...limitOrder(2, 0, 100, 10000, 2), postOnly: true };
+    expect(
+      referenceMatchOrders([postOnlyBid], [olderAsk], 0, PRICING_RULE_MIDPOINT)
+    ).to.deep.equal([]);
+
+    const newerAsk = limitOrder(3, 1, 100, 10000, 3);
+    expect(
+      referenceMatchOrders([postOnlyBid], [newerAsk], 0, PRICING_RULE_MIDPOINT)
+    ).to.have.length(1);
+  });
+});
+
+describe("TWAP orders", () => {
+  it("releases one slice per run, the last one only what is left", () => {
+    expect(twapStep(0, 1000, 10000, 400, false)).to.deep.equal({
+      released: 400,
+      remaining: 600,
+      refund: 0,
+    });
+    expect(twapStep(0, 200, 10000, 400, false)).to.deep.equal({
+      released: 200,
+      remaining: 0,
+      refund: 0,
+    });
+    expect(twapStep(1, 1000, 10000, 0, false).released).to.equal(1000);
+  });
+
+  it("holds the slice back while its side of the book is full", () => {
+    expect(twapStep(1, 1000, 10000, 400, false, false)).to.deep.equal({
+      released: 0,
+      remaining: 1000,
+      refund: 0,
+    });
+  });
+
+  it("refunds the lock of the unreleased quantity once expired", () => {
+    // 6.00 base left at 100.00 locked 600.00 quote for a buy
+    expect(twapStep(0, 600, 10000, 400, true)).to.deep.equal({
+      released: 0,
+      remaining: 0,
+      refund: 60000,
+    });
+    expect(twapStep(1, 600, 10000, 400, true).refund).to.equal(600);
+  });
+});
+
+describe("Recurring orders", () => {
+  it("funds each child from the ledger until the balance runs out", () => {
+    let available = 25000;
+    const placed: boolean[] = [];
+    for (let period = 0; period < 3; period++) {
+      const child = recurringChild(available, 10000);
+      available = child.available;
+      placed.push(child.placed);
+    }
+    expect(placed).to.deep.equal([true, true, false]);
+    expect(available).to.equal(5000);
+  });
+
+  it("keeps the balance when the bid side is full", () => {
+    expect(recurringChild(25000, 10000, false)).to.deep.equal({
+      funded: true,
+      placed: false,
+      available: 25000,
+    });
+  });
+});
+
+describe("Trading sessions", () => {
+  // 08:00 pre-open, 09:30 opening auction, 09:35 continuous, 15:50 closing auction, 16:00 close
+  const schedule = {
+    preOpenTime: 28800,
+    openingAuctionTime: 34200,
+    continuousTime: 34500,
+    closingAuctionTime: 57000,
+    closeTime: 57600,
+  };
+  const day = 19000 * 86400;
+
+  it("walks through the phases of a trading day", () => {
+    expect(sessionPhase(schedule, day + 3600)).to.equal(SESSION_CLOSED);
+    expect(sessionPhase(schedule, day + 30000)).to.equal(SESSION_PRE_OPEN);
+    expect(sessionPhase(schedule, day + 34200)).to.equal(SESSION_OPENING_AUCTION);
+    expect(sessionPhase(schedule, day + 40000)).to.equal(SESSION_CONTINUOUS);
+    expect(sessionPhase(schedule, day + 57000)).to.equal(SESSION_CLOSING_AUCTION);
+    expect(sessionPhase(schedule, day + 57600)).to.equal(SESSION_CLOSED);
+  });
+
+  it("skips a phase whose boundaries are equal and trades continuously without a schedule", () => {
+    const noOpeningAuction = { ...schedule, continuousTime: 34200 };
+    expect(sessionPhase(noOpeningAuction, day + 34200)).to.equal(SESSION_CONTINUOUS);
+    expect(sessionPhase(null, day + 3600)).to.equal(SESSION_CONTINUOUS);
+  });
+});
+
+describe("Price band", () => {
+  it("keeps execution prices within the band around the reference", () => {
+    expect(withinBand(10500, 10000, 500)).to.equal(true);
+    expect(withinBand(9500, 10000, 500)).to.equal(true);
+    expect(withinBand(10501, 10000, 500)).to.equal(false);
+    expect(withinBand(20000, 0, 500)).to.equal(true);
+    expect(withinBand(20000, 10000, 0)).to.equal(true);
+  });
+
+  it("skips crossings outside the band and counts them", () => {
+    const bid = limitOrder(1, 0, 100, 12000, 5);
+    const farAsk = limitOrder(2, 1, 100, 11000, 2);
+    const nearAsk = limitOrder(3, 1, 50, 10200, 3);
+    const { matches, skipped } = referenceMatchBatch(
+      [bid],
+      [farAsk, nearAsk],
+      0,
+      PRICING_RULE_MAKER,
+      STP_NONE,
+      ALLOCATION_PRICE_TIME,
+      0,
+      10,
+      10000,
+      500
+    );
+    expect(matches.map((m) => [m.sellerOrderId, m.executionPrice])).to.deep.equal([
+      [3, 10200],
+    ]);
+    expect(skipped).to.equal(1);
+  });
+});
+
+describe("Order entry band", () => {
+  const band = { ...NO_ENTRY_RULES, bandReference: 10000, bandBps: 1000 };
+
+  it("rejects a limit price 100x off the feed before checking the balance", () => {
+    expect(entryRejectReason(0, ORDER_KIND_LIMIT, 100, 1000000, 0, 0, band)).to.equal(
+      REJECT_REASON_PRICE_BAND
+    );
+    expect(entryRejectReason(0, ORDER_KIND_LIMIT, 100, 10500, 0, 0, band)).to.equal(
+      REJECT_REASON_INSUFFICIENT_BALANCE
+    );
+    expect(entryRejectReason(0, ORDER_KIND_LIMIT, 100, 10500, 0, 10500, band)).to.equal(
+      REJECT_REASON_NONE
+    );
+  });
+
+  it("leaves unpriced orders and markets without a band alone", () => {
+    expect(entryRejectReason(0, ORDER_KIND_MARKET, 100, 0, 5000, 5000, band)).to.equal(
+      REJECT_REASON_NONE
+    );
+    expect(entryRejectReason(1, ORDER_KIND_LIMIT, 100, 1000000, 0, 100)).to.equal(
+      REJECT_REASON_NONE
+    );
+  });
+});
+
+describe("Order size rules", () => {
+  // Prices on a 0.05 grid, sizes in whole units, at least 10.00 of quote
+  const rules = { ...NO_ENTRY_RULES, tickSize: 5, lotSize: 100, minNotional: 1000 };
+
+  it("rejects off grid prices and sizes with their own status", () => {
+    const offTick = entryRejectReason(1, ORDER_KIND_LIMIT, 500, 10003, 0, 500, rules);
+    const offLot = entryRejectReason(1, ORDER_KIND_LIMIT, 550, 10000, 0, 550, rules);
+    expect(offTick).to.equal(REJECT_REASON_TICK_SIZE);
+    expect(offLot).to.equal(REJECT_REASON_LOT_SIZE);
+    expect(rejectStatus(offTick)).to.equal(7);
+    expect(rejectStatus(offLot)).to.equal(8);
+  });
+
+  it("rejects dust below the minimum notional", () => {
+    // 1.00 at 5.00 is worth 5.00 of quote
+    const dust = entryRejectReason(1, ORDER_KIND_LIMIT, 100, 500, 0, 100, rules);
+    expect(dust).to.equal(REJECT_REASON_MIN_NOTIONAL);
+    expect(rejectStatus(dust)).to.equal(9);
+    expect(entryRejectReason(0, ORDER_KIND_MARKET, 0, 0, 900, 900, rules)).to.equal(
+      REJECT_REASON_MIN_NOTIONAL
+    );
+    expect(entryRejectReason(1, ORDER_KIND_LIMIT, 100, 1000, 0, 100, rules)).to.equal(
+      REJECT_REASON_NONE
+    );
+  });
+});
+
+describe("Fixed point amounts", () => {
+  // A 6 decimal base priced in a 6 decimal quote, 1 base token at 150.000001
+  const scale = 10 ** 6;
+  const price = 150000001;
+
+  it("rounds what a buyer owes up and what it gets back down", () => {
+    // 0.5 base tokens are worth 75.0000005 quote units
+    expect(quoteOwed(500000, price, scale)).to.equal(75000001);
+    expect(quoteDue(500000, price, scale)).to.equal(75000000);
+    expect(quoteOwed(1000000, price, scale)).to.equal(quoteDue(1000000, price, scale));
+  });
+
+  it("never lets a budget buy more than it paid for", () => {
+    const budget = 75000000;
+    const quantity = baseForQuote(budget, price, scale);
+    expect(quoteOwed(quantity, price, scale)).to.be.at.most(budget);
+    expect(quoteOwed(quantity + 1, price, scale)).to.be.above(budget);
+  });
+});
+
+describe("Settlement fees", () => {
+  it("takes the fee out of what the receiver gets", () => {
+    // 10 bps of 15000 quote units
+    const fee = settlementFee(15000, 10);
+    expect(fee).to.equal(15);
+  });
+
+  it("rounds the fee up so dust fills still pay", () => {
+    expect(settlementFee(1, 1)).to.equal(1);
+    expect(settlementFee(10001, 1)).to.equal(2);
+    expect(settlementFee(15000, 0)).to.equal(0);
+  });
+
+  it("never takes more than the receiver gets at the maximum rate", () => {
+    const amount = 999;
+    expect(settlementFee(amount, MAX_FEE_BPS)).to.be.at.most(amount);
+  });
+});
+
+describe("Volume fee tiers", () => {
+  const thresholds = [100000, 1000000, 0];
+  const tierFees = [8, 5, 0];
+
+  it("charges the base rate below the first threshold", () => {
+    expect(tierFeeBps(99999, 10, thresholds, tierFees)).to.equal(10);
+  });
+
+  it("charges the highest tier the volume reaches", () => {
+    expect(tierFeeBps(100000, 10, thresholds, tierFees)).to.equal(8);
+    expect(tierFeeBps(5000000, 10, thresholds, tierFees)).to.equal(5);
+  });
+
+  it("rolls the counter forward one window at a time", () => {
+    const volume = { current: 500, previous: 300 };
+    expect(rollVolume(volume, 0)).to.deep.equal(volume);
+    expect(rollVolume(volume, 1)).to.deep.equal({ current: 0, previous: 500 });
+    expect(rollVolume(volume, 2)).to.deep.equal({ current: 0, previous: 0 });
+  });
+
+  it("fades the previous window out over the current one", () => {
+    const volume = { current: 1000, previous: 2000 };
+    const start = 10 * VOLUME_WINDOW;
+    expect(rollingVolume(volume, start)).to.equal(3000);
+    expect(rollingVolume(volume, start + VOLUME_WINDOW / 2)).to.equal(2000);
+    expect(rollingVolume(volume, start + VOLUME_WINDOW - 1)).to.be.below(1001);
+  });
+});
+
+describe("Referral fee sharing", () => {
+  it("splits the fee between the referrer and the protocol", () => {
+    const fee = settlementFee(15000, 10);
+    const referrerCut = referralFee(fee, 2000);
+    expect(referrerCut).to.equal(3);
+    expect(fee - referrerCut).to.equal(12);
+  });
+
+  it("leaves rounding dust with the protocol", () => {
+    expect(referralFee(1, 5000)).to.equal(0);
+    expect(referralFee(3, 5000)).to.equal(1);
+  });
+
+  it("pays nothing without a referral share", () => {
+    expect(referralFee(15, 0)).to.equal(0);
+  });
+});